@@ -0,0 +1,41 @@
+import java.lang.annotation.ElementType;
+import java.lang.annotation.Retention;
+import java.lang.annotation.RetentionPolicy;
+import java.lang.annotation.Target;
+import java.util.List;
+
+@Deprecated
+@Annotations.Info(name = "sample", tags = {"a", "b"}, kind = ElementType.TYPE, type = String[].class, nested = @Annotations.Marker, flag = true, big = 1L << 40)
+class Annotations {
+    @Retention(RetentionPolicy.RUNTIME)
+    @interface Info {
+        String name();
+        int count() default 1;
+        String[] tags() default {};
+        ElementType kind();
+        Class<?> type();
+        Marker nested();
+        boolean flag() default false;
+        double ratio() default 0.5;
+        char letter() default 'a';
+        long big() default 0;
+        byte small() default -1;
+        short medium() default 300;
+        float part() default 0.25f;
+    }
+
+    @interface Marker {
+    }
+
+    @Target({ElementType.TYPE_USE, ElementType.TYPE_PARAMETER})
+    @Retention(RetentionPolicy.RUNTIME)
+    @interface Nullable {
+    }
+
+    @Marker
+    List<@Nullable String> names;
+
+    void greet(@Marker String name, @Deprecated int times) throws @Nullable Exception {
+        @Nullable Object local = name;
+    }
+}
@@ -0,0 +1,17 @@
+import java.io.IOException;
+import java.io.Serializable;
+import java.util.AbstractMap;
+import java.util.Collection;
+import java.util.List;
+import java.util.Map;
+
+abstract class Generics<K extends Comparable<K>, V> extends AbstractMap<K, V> implements Serializable {
+    List<? super Map.Entry<K, V>>[] listeners;
+
+    <E extends Exception> void copy(Collection<? extends V> values, int limit) throws E, IOException {
+    }
+
+    public int size() {
+        return 0;
+    }
+}
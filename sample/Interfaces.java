@@ -0,0 +1,5 @@
+import java.io.Serializable;
+
+abstract class Interfaces implements Runnable, Serializable {
+    public abstract int size();
+}
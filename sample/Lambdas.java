@@ -0,0 +1,8 @@
+import java.util.function.Function;
+
+class Lambdas {
+    static String greet(String name, int times) {
+        Function<String, String> shout = s -> s.toUpperCase();
+        return shout.apply(name) + " x" + times;
+    }
+}
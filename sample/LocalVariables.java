@@ -0,0 +1,15 @@
+import java.util.ArrayList;
+import java.util.List;
+
+class LocalVariables {
+    public static int testMe() {
+        int summe = 3;
+        List<String> names = new ArrayList<>();
+        for (int i = 0; i < 10; i++) {
+            names.add("name");
+            summe += i;
+        }
+
+        return summe + names.size();
+    }
+}
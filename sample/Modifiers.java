@@ -0,0 +1,20 @@
+abstract class Modifiers implements Comparable<Modifiers> {
+    private transient volatile int counter;
+
+    public static strictfp double sum(double... values) {
+        double total = 0;
+        for (double value : values) {
+            total += value;
+        }
+
+        return total;
+    }
+
+    public int compareTo(Modifiers other) {
+        return 0;
+    }
+
+    protected abstract void run();
+
+    static synchronized native void poke();
+}
@@ -0,0 +1,22 @@
+class Nesting {
+    class Member {
+    }
+
+    static final class StaticMember {
+        private int secret;
+    }
+
+    Runnable anonymous() {
+        return new Runnable() {
+            public void run() {
+            }
+        };
+    }
+
+    Object local() {
+        class Local {
+        }
+
+        return new Local();
+    }
+}
@@ -0,0 +1,15 @@
+import java.lang.annotation.Retention;
+import java.lang.annotation.RetentionPolicy;
+import java.util.List;
+
+sealed interface Shapes permits Shapes.Circle, Shapes.Polygon {
+    @Retention(RetentionPolicy.RUNTIME)
+    @interface Tag {
+    }
+
+    record Circle(double radius) implements Shapes {
+    }
+
+    record Polygon(@Tag String name, List<int[]> points) implements Shapes {
+    }
+}
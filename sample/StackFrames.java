@@ -0,0 +1,38 @@
+class StackFrames {
+    private final int base;
+
+    StackFrames(int base) {
+        this.base = base;
+    }
+
+    static long sum(int[] values, long start) {
+        long total = start;
+        for (int i = 0; i < values.length; i++) {
+            total += values[i];
+        }
+
+        if (total > 100) {
+            String text = "big";
+            total += text.length();
+        }
+
+        return total;
+    }
+
+    int pick(boolean first, String a, String b) {
+        String picked;
+        if (first) {
+            int unused = 1;
+            picked = a;
+        } else {
+            picked = b;
+        }
+
+        return base + Math.max(picked.length(), first ? a.length() : b.length());
+    }
+
+    public static void main(String[] argv) {
+        long result = sum(new int[] { 1, 2, 3 }, 4L);
+        int picked = new StackFrames(1).pick(true, "a", "bb");
+    }
+}
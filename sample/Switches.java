@@ -0,0 +1,52 @@
+public class Switches {
+
+    enum Unit { SECONDS, MINUTES, HOURS }
+
+    // dense keys become a tableswitch
+    private static int dense(int key) {
+        switch (key) {
+            case 1: return 10;
+            case 2: return 20;
+            case 3: return 30;
+            case 4: return 40;
+            default: return -1;
+        }
+    }
+
+    // sparse keys become a lookupswitch
+    private static int sparse(int key) {
+        switch (key) {
+            case -100: return 1;
+            case 7: return 2;
+            case 1000: return 3;
+            default: return 0;
+        }
+    }
+
+    // a lookupswitch on the hash code, then a second switch on the case that matched
+    private static int named(String name) {
+        switch (name) {
+            case "one": return 1;
+            case "two": return 2;
+            default: return 0;
+        }
+    }
+
+    // a switch on the ordinal, mapped through the array in Switches$1
+    private static int seconds(Unit unit) {
+        switch (unit) {
+            case SECONDS: return 1;
+            case MINUTES: return 60;
+            default: return 0;
+        }
+    }
+
+    public static int testMe() {
+        int sum = 0;
+        for (int key = 0; key < 6; key++) {
+            sum += dense(key);
+        }
+
+        return sum + sparse(7) + sparse(1000) + sparse(8);
+    }
+}
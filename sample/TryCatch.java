@@ -0,0 +1,21 @@
+class TryCatch {
+    private static int divide(int a, int b) {
+        try {
+            return a / b;
+        } catch (ArithmeticException e) {
+            return 0;
+        }
+    }
+
+    private static int cleanup(int a) {
+        try {
+            return divide(a, a);
+        } finally {
+            a = 0;
+        }
+    }
+
+    public static void main(String[] argv) {
+        int result = divide(10, 0) + cleanup(3);
+    }
+}
@@ -0,0 +1,15 @@
+class WideConstants {
+    private static long big() {
+        return 1234567890123L;
+    }
+
+    private static double pi() {
+        return 3.14159;
+    }
+
+    public static void main(String[] argv) {
+        long l = big();
+        double d = pi();
+        String text = "after the wide constants";
+    }
+}
@@ -0,0 +1,33 @@
+public class WideLocals {
+
+    // the last locals have an index above 255, which needs the wide form of iload, istore and iinc
+    public static int testMe() {
+        int small = 3;
+        // the constant does not fit into a byte, so this is a wide iinc
+        small += 1000;
+        small -= 100;
+        int v000 = 0, v001 = 0, v002 = 0, v003 = 0, v004 = 0, v005 = 0, v006 = 0, v007 = 0, v008 = 0, v009 = 0, v010 = 0, v011 = 0, v012 = 0;
+        int v013 = 0, v014 = 0, v015 = 0, v016 = 0, v017 = 0, v018 = 0, v019 = 0, v020 = 0, v021 = 0, v022 = 0, v023 = 0, v024 = 0, v025 = 0;
+        int v026 = 0, v027 = 0, v028 = 0, v029 = 0, v030 = 0, v031 = 0, v032 = 0, v033 = 0, v034 = 0, v035 = 0, v036 = 0, v037 = 0, v038 = 0;
+        int v039 = 0, v040 = 0, v041 = 0, v042 = 0, v043 = 0, v044 = 0, v045 = 0, v046 = 0, v047 = 0, v048 = 0, v049 = 0, v050 = 0, v051 = 0;
+        int v052 = 0, v053 = 0, v054 = 0, v055 = 0, v056 = 0, v057 = 0, v058 = 0, v059 = 0, v060 = 0, v061 = 0, v062 = 0, v063 = 0, v064 = 0;
+        int v065 = 0, v066 = 0, v067 = 0, v068 = 0, v069 = 0, v070 = 0, v071 = 0, v072 = 0, v073 = 0, v074 = 0, v075 = 0, v076 = 0, v077 = 0;
+        int v078 = 0, v079 = 0, v080 = 0, v081 = 0, v082 = 0, v083 = 0, v084 = 0, v085 = 0, v086 = 0, v087 = 0, v088 = 0, v089 = 0, v090 = 0;
+        int v091 = 0, v092 = 0, v093 = 0, v094 = 0, v095 = 0, v096 = 0, v097 = 0, v098 = 0, v099 = 0, v100 = 0, v101 = 0, v102 = 0, v103 = 0;
+        int v104 = 0, v105 = 0, v106 = 0, v107 = 0, v108 = 0, v109 = 0, v110 = 0, v111 = 0, v112 = 0, v113 = 0, v114 = 0, v115 = 0, v116 = 0;
+        int v117 = 0, v118 = 0, v119 = 0, v120 = 0, v121 = 0, v122 = 0, v123 = 0, v124 = 0, v125 = 0, v126 = 0, v127 = 0, v128 = 0, v129 = 0;
+        int v130 = 0, v131 = 0, v132 = 0, v133 = 0, v134 = 0, v135 = 0, v136 = 0, v137 = 0, v138 = 0, v139 = 0, v140 = 0, v141 = 0, v142 = 0;
+        int v143 = 0, v144 = 0, v145 = 0, v146 = 0, v147 = 0, v148 = 0, v149 = 0, v150 = 0, v151 = 0, v152 = 0, v153 = 0, v154 = 0, v155 = 0;
+        int v156 = 0, v157 = 0, v158 = 0, v159 = 0, v160 = 0, v161 = 0, v162 = 0, v163 = 0, v164 = 0, v165 = 0, v166 = 0, v167 = 0, v168 = 0;
+        int v169 = 0, v170 = 0, v171 = 0, v172 = 0, v173 = 0, v174 = 0, v175 = 0, v176 = 0, v177 = 0, v178 = 0, v179 = 0, v180 = 0, v181 = 0;
+        int v182 = 0, v183 = 0, v184 = 0, v185 = 0, v186 = 0, v187 = 0, v188 = 0, v189 = 0, v190 = 0, v191 = 0, v192 = 0, v193 = 0, v194 = 0;
+        int v195 = 0, v196 = 0, v197 = 0, v198 = 0, v199 = 0, v200 = 0, v201 = 0, v202 = 0, v203 = 0, v204 = 0, v205 = 0, v206 = 0, v207 = 0;
+        int v208 = 0, v209 = 0, v210 = 0, v211 = 0, v212 = 0, v213 = 0, v214 = 0, v215 = 0, v216 = 0, v217 = 0, v218 = 0, v219 = 0, v220 = 0;
+        int v221 = 0, v222 = 0, v223 = 0, v224 = 0, v225 = 0, v226 = 0, v227 = 0, v228 = 0, v229 = 0, v230 = 0, v231 = 0, v232 = 0, v233 = 0;
+        int v234 = 0, v235 = 0, v236 = 0, v237 = 0, v238 = 0, v239 = 0, v240 = 0, v241 = 0, v242 = 0, v243 = 0, v244 = 0, v245 = 0, v246 = 0;
+        int v247 = 0, v248 = 0, v249 = 0, v250 = 0, v251 = 0, v252 = 0, v253 = 0, v254 = 0, v255 = 0, v256 = 0, v257 = 0, v258 = 0, v259 = 0;
+        v259 = small;
+        v259 += 2;
+        return v259;
+    }
+}
@@ -0,0 +1,6 @@
+package com.example.app;
+
+public class Main implements com.example.spi.Plugin {
+    public static void main(String[] args) {
+    }
+}
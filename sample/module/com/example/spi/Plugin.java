@@ -0,0 +1,4 @@
+package com.example.spi;
+
+public interface Plugin {
+}
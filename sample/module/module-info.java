@@ -0,0 +1,8 @@
+open module com.example.app {
+    requires transitive java.logging;
+    requires static java.sql;
+    exports com.example.spi;
+    exports com.example.app to java.base, java.logging;
+    uses com.example.spi.Plugin;
+    provides com.example.spi.Plugin with com.example.app.Main;
+}
@@ -0,0 +1,299 @@
+//! access flags of classes, fields, methods and modules (JVMS 4.1, 4.5, 4.6, 4.7.6 and 4.7.25).
+//!
+//! the same bit means different things depending on where it is used, e.g. `0x0040` is
+//! `ACC_VOLATILE` on a field but `ACC_BRIDGE` on a method. every context gets its own flag type,
+//! so a flag can only be tested where it exists.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitOrAssign};
+
+/// declares a flag type together with the matching `*Access` enum.
+///
+/// flags have to be listed in bit order, which is also the order `javap` prints modifiers in.
+/// flags without a java keyword (like `ACC_SUPER`) use `""`.
+macro_rules! access_flags {
+    (
+        $(#[$attr:meta])*
+        $flags:ident, $access:ident {
+            $( $variant:ident, $name:ident = $bits:expr, $keyword:expr; )*
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Default, Eq, PartialEq, Hash)]
+        pub struct $flags {
+            bits: u16,
+        }
+
+        #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+        pub enum $access {
+            $( $variant, )*
+        }
+
+        impl $flags {
+            $( pub const $name: $flags = $flags { bits: $bits }; )*
+
+            /// all flags defined for this context, with their `javap` name and java keyword
+            const ALL: &'static [($flags, $access, &'static str, &'static str)] = &[
+                $( ($flags::$name, $access::$variant, concat!("ACC_", stringify!($name)), $keyword), )*
+            ];
+
+            /// keeps all bits, including the ones that are not defined for this context
+            pub fn from_bits(bits: u16) -> $flags {
+                $flags { bits }
+            }
+
+            pub fn empty() -> $flags {
+                $flags { bits: 0 }
+            }
+
+            pub fn bits(&self) -> u16 {
+                self.bits
+            }
+
+            pub fn is_empty(&self) -> bool {
+                self.bits == 0
+            }
+
+            pub fn contains(&self, other: $flags) -> bool {
+                self.bits & other.bits == other.bits
+            }
+
+            pub fn insert(&mut self, other: $flags) {
+                self.bits |= other.bits;
+            }
+
+            pub fn remove(&mut self, other: $flags) {
+                self.bits &= !other.bits;
+            }
+
+            /// the bits that have no meaning in this context
+            pub fn unknown_bits(&self) -> u16 {
+                $flags::ALL.iter().fold(self.bits, |bits, (flag, _, _, _)| bits & !flag.bits)
+            }
+
+            /// the flags as they are named in the spec (and by `javap -v`), e.g. `ACC_PUBLIC`
+            pub fn names(&self) -> Vec<&'static str> {
+                $flags::ALL.iter()
+                    .filter(|(flag, _, _, _)| self.contains(*flag))
+                    .map(|(_, _, name, _)| *name)
+                    .collect()
+            }
+
+            /// the java modifiers for the set flags, in the order `javap` prints them
+            pub fn keywords(&self) -> Vec<&'static str> {
+                $flags::ALL.iter()
+                    .filter(|(flag, _, _, keyword)| !keyword.is_empty() && self.contains(*flag))
+                    .map(|(_, _, _, keyword)| *keyword)
+                    .collect()
+            }
+
+            pub fn to_set(&self) -> HashSet<$access> {
+                $flags::ALL.iter()
+                    .filter(|(flag, _, _, _)| self.contains(*flag))
+                    .map(|(_, access, _, _)| *access)
+                    .collect()
+            }
+        }
+
+        impl BitOr for $flags {
+            type Output = $flags;
+
+            fn bitor(self, other: $flags) -> $flags {
+                $flags { bits: self.bits | other.bits }
+            }
+        }
+
+        impl BitOrAssign for $flags {
+            fn bitor_assign(&mut self, other: $flags) {
+                self.bits |= other.bits;
+            }
+        }
+
+        impl BitAnd for $flags {
+            type Output = $flags;
+
+            fn bitand(self, other: $flags) -> $flags {
+                $flags { bits: self.bits & other.bits }
+            }
+        }
+
+        impl fmt::Debug for $flags {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "{}(0x{:04x}: {})", stringify!($flags), self.bits, self.names().join(" | "))
+            }
+        }
+    }
+}
+
+access_flags! {
+    /// access flags of a class or interface (JVMS table 4.1-B)
+    ClassFlags, ClassAccess {
+        Public, PUBLIC = 0x0001, "public";
+        Final, FINAL = 0x0010, "final";
+        Super, SUPER = 0x0020, "";
+        Interface, INTERFACE = 0x0200, "";
+        Abstract, ABSTRACT = 0x0400, "abstract";
+        Synthetic, SYNTHETIC = 0x1000, "";
+        Annotation, ANNOTATION = 0x2000, "";
+        Enum, ENUM = 0x4000, "";
+        Module, MODULE = 0x8000, "";
+    }
+}
+
+access_flags! {
+    /// access flags of a field (JVMS table 4.5-A)
+    FieldFlags, FieldAccess {
+        Public, PUBLIC = 0x0001, "public";
+        Private, PRIVATE = 0x0002, "private";
+        Protected, PROTECTED = 0x0004, "protected";
+        Static, STATIC = 0x0008, "static";
+        Final, FINAL = 0x0010, "final";
+        Volatile, VOLATILE = 0x0040, "volatile";
+        Transient, TRANSIENT = 0x0080, "transient";
+        Synthetic, SYNTHETIC = 0x1000, "";
+        Enum, ENUM = 0x4000, "";
+    }
+}
+
+access_flags! {
+    /// access flags of a method (JVMS table 4.6-A)
+    MethodFlags, MethodAccess {
+        Public, PUBLIC = 0x0001, "public";
+        Private, PRIVATE = 0x0002, "private";
+        Protected, PROTECTED = 0x0004, "protected";
+        Static, STATIC = 0x0008, "static";
+        Final, FINAL = 0x0010, "final";
+        Synchronized, SYNCHRONIZED = 0x0020, "synchronized";
+        Bridge, BRIDGE = 0x0040, "";
+        Varargs, VARARGS = 0x0080, "";
+        Native, NATIVE = 0x0100, "native";
+        Abstract, ABSTRACT = 0x0400, "abstract";
+        Strict, STRICT = 0x0800, "strictfp";
+        Synthetic, SYNTHETIC = 0x1000, "";
+    }
+}
+
+access_flags! {
+    /// access flags of a nested class as declared in the source, from `InnerClasses` (JVMS table 4.7.6-A)
+    InnerClassFlags, InnerClassAccess {
+        Public, PUBLIC = 0x0001, "public";
+        Private, PRIVATE = 0x0002, "private";
+        Protected, PROTECTED = 0x0004, "protected";
+        Static, STATIC = 0x0008, "static";
+        Final, FINAL = 0x0010, "final";
+        Interface, INTERFACE = 0x0200, "";
+        Abstract, ABSTRACT = 0x0400, "abstract";
+        Synthetic, SYNTHETIC = 0x1000, "";
+        Annotation, ANNOTATION = 0x2000, "";
+        Enum, ENUM = 0x4000, "";
+    }
+}
+
+access_flags! {
+    /// flags of a module (JVMS 4.7.25)
+    ModuleFlags, ModuleAccess {
+        Open, OPEN = 0x0020, "open";
+        Synthetic, SYNTHETIC = 0x1000, "";
+        Mandated, MANDATED = 0x8000, "";
+    }
+}
+
+access_flags! {
+    /// flags of a module dependency
+    RequiresFlags, RequiresAccess {
+        Transitive, TRANSITIVE = 0x0020, "transitive";
+        StaticPhase, STATIC_PHASE = 0x0040, "static";
+        Synthetic, SYNTHETIC = 0x1000, "";
+        Mandated, MANDATED = 0x8000, "";
+    }
+}
+
+access_flags! {
+    /// flags of an exported or opened package
+    ExportsFlags, ExportsAccess {
+        Synthetic, SYNTHETIC = 0x1000, "";
+        Mandated, MANDATED = 0x8000, "";
+    }
+}
+
+/// prints the modifiers like `javap` does in a class declaration. interfaces are implicitly
+/// abstract, so `abstract` is left out for them.
+impl fmt::Display for ClassFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut flags = *self;
+        if flags.contains(ClassFlags::INTERFACE) {
+            flags.remove(ClassFlags::ABSTRACT);
+        }
+
+        write!(f, "{}", flags.keywords().join(" "))
+    }
+}
+
+/// like `ClassFlags`, `abstract` is left out for interfaces
+impl fmt::Display for InnerClassFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut flags = *self;
+        if flags.contains(InnerClassFlags::INTERFACE) {
+            flags.remove(InnerClassFlags::ABSTRACT);
+        }
+
+        write!(f, "{}", flags.keywords().join(" "))
+    }
+}
+
+impl fmt::Display for FieldFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.keywords().join(" "))
+    }
+}
+
+impl fmt::Display for MethodFlags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.keywords().join(" "))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use java::class_file::{read_class_file, ClassQuery};
+    use java::samples::MODIFIERS;
+
+    #[test]
+    fn the_same_bit_depends_on_the_context() {
+        assert_eq!(vec!["ACC_PRIVATE", "ACC_VOLATILE", "ACC_TRANSIENT"], FieldFlags::from_bits(0x00c2).names());
+        assert_eq!(vec!["ACC_PRIVATE", "ACC_BRIDGE", "ACC_VARARGS"], MethodFlags::from_bits(0x00c2).names());
+        assert_eq!(0x0100, FieldFlags::from_bits(0x0101).unknown_bits());
+    }
+
+    #[test]
+    fn it_reads_method_flags() {
+        let cf = read_class_file(MODIFIERS).unwrap();
+        let method = |name: &str, descriptor: &str| {
+            cf.methods.iter().find(|m| m.name == name && m.descriptor == descriptor).unwrap().access()
+        };
+
+        let sum = method("sum", "([D)D");
+        assert_eq!(MethodFlags::PUBLIC | MethodFlags::STATIC | MethodFlags::VARARGS | MethodFlags::STRICT, sum);
+        assert_eq!("public static strictfp", sum.to_string());
+
+        let bridge = method("compareTo", "(Ljava/lang/Object;)I");
+        assert_eq!(vec!["ACC_PUBLIC", "ACC_BRIDGE", "ACC_SYNTHETIC"], bridge.names());
+        assert_eq!(
+            vec![MethodAccess::Public, MethodAccess::Bridge, MethodAccess::Synthetic].into_iter().collect::<HashSet<_>>(),
+            bridge.to_set()
+        );
+
+        assert_eq!("protected abstract", method("run", "()V").to_string());
+        assert_eq!("static synchronized native", method("poke", "()V").to_string());
+    }
+
+    #[test]
+    fn it_formats_modifiers_in_javap_order() {
+        let cf = read_class_file(MODIFIERS).unwrap();
+        assert_eq!("abstract", cf.access().to_string());
+        assert_eq!("private volatile transient", cf.fields[0].access().to_string());
+        assert_eq!("public", ClassFlags::from_bits(0x0601).to_string());
+    }
+}
@@ -0,0 +1,206 @@
+//! annotations from the `Runtime*Annotations`, `Runtime*ParameterAnnotations`,
+//! `Runtime*TypeAnnotations` and `AnnotationDefault` attributes (JVMS 4.7.16 - 4.7.22).
+
+use java::class_file::owned;
+use std::borrow::Cow;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Annotation<'a> {
+    /// the field descriptor of the annotation type, e.g. `Lorg/junit/Test;`
+    pub type_name: Cow<'a, str>,
+    /// the element-value pairs in the order they are stored. defaults are not included.
+    pub elements: Vec<(Cow<'a, str>, ElementValue<'a>)>,
+}
+
+impl<'a> Annotation<'a> {
+    pub fn into_owned(self) -> Annotation<'static> {
+        Annotation {
+            type_name: owned(self.type_name),
+            elements: self.elements.into_iter().map(|(name, value)| (owned(name), value.into_owned())).collect(),
+        }
+    }
+
+    pub fn element(&self, name: &str) -> Option<&ElementValue<'a>> {
+        self.elements.iter()
+            .find(|(element_name, _)| element_name == name)
+            .map(|(_, value)| value)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ElementValue<'a> {
+    Byte(i8),
+    /// a UTF-16 code unit
+    Char(u16),
+    Double(f64),
+    Float(f32),
+    Integer(i32),
+    Long(i64),
+    Short(i16),
+    Boolean(bool),
+    String(Cow<'a, str>),
+    /// an enum constant. `type_name` is the field descriptor of the enum type
+    Enum { type_name: Cow<'a, str>, const_name: Cow<'a, str> },
+    /// a class literal as return descriptor, e.g. `Ljava/lang/String;`, `[I` or `V` for `void.class`
+    Class(Cow<'a, str>),
+    Annotation(Annotation<'a>),
+    Array(Vec<ElementValue<'a>>),
+}
+
+impl<'a> ElementValue<'a> {
+    pub fn into_owned(self) -> ElementValue<'static> {
+        match self {
+            ElementValue::Byte(value) => ElementValue::Byte(value),
+            ElementValue::Char(value) => ElementValue::Char(value),
+            ElementValue::Double(value) => ElementValue::Double(value),
+            ElementValue::Float(value) => ElementValue::Float(value),
+            ElementValue::Integer(value) => ElementValue::Integer(value),
+            ElementValue::Long(value) => ElementValue::Long(value),
+            ElementValue::Short(value) => ElementValue::Short(value),
+            ElementValue::Boolean(value) => ElementValue::Boolean(value),
+            ElementValue::String(value) => ElementValue::String(owned(value)),
+            ElementValue::Enum { type_name, const_name } => ElementValue::Enum { type_name: owned(type_name), const_name: owned(const_name) },
+            ElementValue::Class(name) => ElementValue::Class(owned(name)),
+            ElementValue::Annotation(annotation) => ElementValue::Annotation(annotation.into_owned()),
+            ElementValue::Array(values) => ElementValue::Array(values.into_iter().map(ElementValue::into_owned).collect()),
+        }
+    }
+}
+
+/// an annotation on a type use (JVMS 4.7.20)
+#[derive(Debug, Clone, PartialEq)]
+pub struct TypeAnnotation<'a> {
+    pub target: TypeAnnotationTarget,
+    /// where in `target` the annotated type is, empty if it is the whole type
+    pub path: Vec<TypePathEntry>,
+    pub annotation: Annotation<'a>,
+}
+
+impl<'a> TypeAnnotation<'a> {
+    pub fn into_owned(self) -> TypeAnnotation<'static> {
+        TypeAnnotation { target: self.target, path: self.path, annotation: self.annotation.into_owned() }
+    }
+}
+
+/// the `target_info` of a type annotation. variants shared by several `target_type`s keep it.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum TypeAnnotationTarget {
+    /// `0x00` (class) or `0x01` (method)
+    TypeParameter { target_type: u8, index: u8 },
+    /// `0x10`, the superclass (`65535`) or an interface by its index in `interfaces`
+    SuperType { index: u16 },
+    /// `0x11` (class) or `0x12` (method)
+    TypeParameterBound { target_type: u8, type_parameter_index: u8, bound_index: u8 },
+    /// `0x13` field type, `0x14` method return type or `0x15` receiver type
+    Empty { target_type: u8 },
+    /// `0x16`
+    FormalParameter { index: u8 },
+    /// `0x17`, by the index in the `Exceptions` attribute
+    Throws { index: u16 },
+    /// `0x40` (local variable) or `0x41` (resource variable)
+    LocalVariable { target_type: u8, table: Vec<LocalVariableTarget> },
+    /// `0x42`, by the index in the exception table
+    Catch { exception_table_index: u16 },
+    /// `0x43` - `0x46`: `instanceof`, `new` and method references
+    Offset { target_type: u8, offset: u16 },
+    /// `0x47` - `0x4B`: casts and type arguments of calls and method references
+    TypeArgument { target_type: u8, offset: u16, type_argument_index: u8 },
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LocalVariableTarget {
+    pub start_pc: u16,
+    pub length: u16,
+    pub index: u16,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum TypePathEntry {
+    /// deeper in an array type
+    Array,
+    /// deeper in a nested type
+    Nested,
+    /// on the bound of a wildcard
+    WildcardBound,
+    /// on a type argument, by its index
+    TypeArgument(u8),
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use java::class_file::{read_class_file, ClassQuery};
+    use java::samples::{ANNOTATIONS, ANNOTATIONS_INFO};
+
+    #[test]
+    fn it_decodes_element_values() {
+        let cf = read_class_file(ANNOTATIONS).unwrap();
+        assert!(cf.annotation("Ljava/lang/Deprecated;").unwrap().is_some());
+        assert!(cf.annotation("Lorg/junit/Test;").unwrap().is_none());
+
+        let info = cf.annotation("LAnnotations$Info;").unwrap().unwrap();
+        assert_eq!(Some(&ElementValue::String(Cow::Borrowed("sample"))), info.element("name"));
+        assert_eq!(
+            Some(&ElementValue::Array(vec![ElementValue::String(Cow::Borrowed("a")), ElementValue::String(Cow::Borrowed("b"))])),
+            info.element("tags")
+        );
+        assert_eq!(
+            Some(&ElementValue::Enum { type_name: Cow::Borrowed("Ljava/lang/annotation/ElementType;"), const_name: Cow::Borrowed("TYPE") }),
+            info.element("kind")
+        );
+        assert_eq!(Some(&ElementValue::Class(Cow::Borrowed("[Ljava/lang/String;"))), info.element("type"));
+        assert_eq!(
+            Some(&ElementValue::Annotation(Annotation { type_name: Cow::Borrowed("LAnnotations$Marker;"), elements: Vec::new() })),
+            info.element("nested")
+        );
+        assert_eq!(Some(&ElementValue::Boolean(true)), info.element("flag"));
+        assert_eq!(Some(&ElementValue::Long(1 << 40)), info.element("big"));
+        assert_eq!(None, info.element("count"));
+    }
+
+    #[test]
+    fn it_reads_member_and_parameter_annotations() {
+        let cf = read_class_file(ANNOTATIONS).unwrap();
+        assert!(cf.fields[0].annotation("LAnnotations$Marker;").is_some());
+
+        let greet = cf.methods.iter().find(|m| m.name == "greet").unwrap();
+        let names = |index| greet.parameter_annotations(index).iter().map(|a| a.type_name.to_string()).collect::<Vec<String>>();
+        assert_eq!(vec!["LAnnotations$Marker;"], names(0));
+        assert_eq!(vec!["Ljava/lang/Deprecated;"], names(1));
+        assert!(names(2).is_empty());
+    }
+
+    #[test]
+    fn it_reads_type_annotations() {
+        let cf = read_class_file(ANNOTATIONS).unwrap();
+        let greet = cf.methods.iter().find(|m| m.name == "greet").unwrap();
+        let throws = greet.type_annotations();
+        assert_eq!(TypeAnnotationTarget::Throws { index: 0 }, throws[0].target);
+        assert_eq!("LAnnotations$Nullable;", throws[0].annotation.type_name);
+
+        let field = cf.fields[0].type_annotations();
+        assert_eq!(TypeAnnotationTarget::Empty { target_type: 0x13 }, field[0].target);
+        assert_eq!(vec![TypePathEntry::TypeArgument(0)], field[0].path);
+
+        let local = greet.get_code().unwrap().type_annotations();
+        assert_eq!(
+            TypeAnnotationTarget::LocalVariable { target_type: 0x40, table: vec![LocalVariableTarget { start_pc: 2, length: 1, index: 3 }] },
+            local[0].target
+        );
+    }
+
+    #[test]
+    fn it_reads_annotation_defaults() {
+        let cf = read_class_file(ANNOTATIONS_INFO).unwrap();
+        let default = |name: &str| cf.methods.iter().find(|m| m.name == name).unwrap().annotation_default();
+
+        assert_eq!(None, default("name"));
+        assert_eq!(Some(&ElementValue::Integer(1)), default("count"));
+        assert_eq!(Some(&ElementValue::Array(Vec::new())), default("tags"));
+        assert_eq!(Some(&ElementValue::Double(0.5)), default("ratio"));
+        assert_eq!(Some(&ElementValue::Char(u16::from(b'a'))), default("letter"));
+        assert_eq!(Some(&ElementValue::Byte(-1)), default("small"));
+        assert_eq!(Some(&ElementValue::Short(300)), default("medium"));
+        assert_eq!(Some(&ElementValue::Float(0.25)), default("part"));
+    }
+}
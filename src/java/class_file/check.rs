@@ -0,0 +1,455 @@
+//! the format checks of JVMS 4.8 that the parser leaves out.
+//!
+//! the parser only makes sure the class file can be read. `check` looks at what was read:
+//! constant pool cross references, names and descriptors, access flag combinations, duplicate
+//! members and the layout of `Code` attributes. every violation is reported, not only the first.
+
+use java::class_file::error::{ErrorReason, Structure};
+use java::class_file::parser::{read_field_descriptor, read_method_descriptor};
+use java::class_file::{Attribute, ClassFile, ClassFileError, ClassFlags, ClassQuery, CodeBlock, ConstantPoolError, ConstantType, Field, FieldFlags, Method, MethodFlags};
+use java::instructions::Instruction;
+use std::collections::HashSet;
+use std::fmt;
+
+/// a single violation of the format constraints
+#[derive(Debug)]
+pub struct Diagnostic {
+    /// where the problem is, outermost first, like `ClassFileError::structure`
+    pub location: Vec<Structure>,
+    pub problem: Problem,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.problem)?;
+        for (idx, structure) in self.location.iter().enumerate() {
+            write!(f, "{}{}", if idx == 0 { " in " } else { " > " }, structure)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Fail)]
+pub enum Problem {
+    #[fail(display = "{}", _0)]
+    InvalidConstant(#[cause] ConstantPoolError),
+    /// an attribute a lookup needs cannot be decoded
+    #[fail(display = "{}", _0)]
+    Malformed(#[cause] ClassFileError),
+    #[fail(display = "invalid {} name {:?}", kind, name)]
+    InvalidName { kind: &'static str, name: String },
+    #[fail(display = "invalid {} descriptor {:?}", kind, descriptor)]
+    InvalidDescriptor { kind: &'static str, descriptor: String },
+    #[fail(display = "{}{} is declared more than once", name, descriptor)]
+    Duplicate { name: String, descriptor: String },
+    #[fail(display = "invalid access flags 0x{:04x}: {}", flags, reason)]
+    InvalidAccess { flags: u16, reason: &'static str },
+    #[fail(display = "{}", reason)]
+    InvalidSuperclass { reason: &'static str },
+    #[fail(display = "{}", reason)]
+    InvalidCode { reason: &'static str },
+    #[fail(display = "code length {} is not between 1 and 65535", length)]
+    CodeLength { length: usize },
+    #[fail(display = "unknown or truncated instruction at pc {}", pc)]
+    InvalidInstruction { pc: usize },
+    #[fail(display = "exception handler #{} {}", index, reason)]
+    InvalidHandler { index: usize, reason: &'static str },
+}
+
+impl From<ConstantPoolError> for Problem {
+    fn from(err: ConstantPoolError) -> Problem {
+        Problem::InvalidConstant(err)
+    }
+}
+
+impl From<ClassFileError> for Problem {
+    fn from(err: ClassFileError) -> Problem {
+        match err {
+            ClassFileError { reason: ErrorReason::InvalidConstant(err), .. } => Problem::InvalidConstant(err),
+            err => Problem::Malformed(err)
+        }
+    }
+}
+
+/// checks the whole class, an empty list means the class is well-formed
+pub fn check(class: &ClassFile) -> Vec<Diagnostic> {
+    let mut checker = Checker { location: Vec::new(), diagnostics: Vec::new() };
+
+    check_constants(&mut checker, class);
+    check_header(&mut checker, class);
+
+    let mut members = HashSet::new();
+    for (idx, field) in class.fields.iter().enumerate() {
+        checker.enter(Structure::Field(idx as u16));
+        check_field(&mut checker, class, field);
+        if !members.insert((&*field.name, &*field.descriptor)) {
+            checker.report(Problem::Duplicate { name: field.name.to_string(), descriptor: field.descriptor.to_string() });
+        }
+        checker.leave();
+    }
+
+    let mut members = HashSet::new();
+    for (idx, method) in class.methods.iter().enumerate() {
+        checker.enter(Structure::Method(idx as u16));
+        check_method(&mut checker, class, method);
+        if !members.insert((&*method.name, &*method.descriptor)) {
+            checker.report(Problem::Duplicate { name: method.name.to_string(), descriptor: method.descriptor.to_string() });
+        }
+        checker.leave();
+    }
+
+    checker.diagnostics
+}
+
+/// collects diagnostics together with the location that is being checked
+struct Checker {
+    location: Vec<Structure>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Checker {
+    fn enter(&mut self, structure: Structure) {
+        self.location.push(structure);
+    }
+
+    fn leave(&mut self) {
+        self.location.pop();
+    }
+
+    fn report(&mut self, problem: Problem) {
+        self.diagnostics.push(Diagnostic { location: self.location.clone(), problem });
+    }
+
+    /// reports the error of a failed constant pool lookup
+    fn resolve<T, E: Into<Problem>>(&mut self, result: Result<T, E>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(err) => {
+                self.report(err.into());
+                None
+            }
+        }
+    }
+
+    fn expect(&mut self, valid: bool, problem: Problem) {
+        if !valid {
+            self.report(problem);
+        }
+    }
+
+    fn expect_access(&mut self, flags: u16, valid: bool, reason: &'static str) {
+        self.expect(valid, Problem::InvalidAccess { flags, reason });
+    }
+}
+
+/// unqualified names (JVMS 4.2.2) must not be empty or contain `.`, `;`, `[` or `/`
+fn is_unqualified_name(name: &str) -> bool {
+    !name.is_empty() && !name.contains(['.', ';', '[', '/'])
+}
+
+/// only the special methods may use `<` and `>`
+fn is_method_name(name: &str) -> bool {
+    name == "<init>" || name == "<clinit>" || (is_unqualified_name(name) && !name.contains(['<', '>']))
+}
+
+/// a binary name in internal form, like `java/lang/Object`
+fn is_class_name(name: &str) -> bool {
+    name.split('/').all(is_unqualified_name)
+}
+
+/// arrays are limited to 255 dimensions
+fn is_field_descriptor(descriptor: &str) -> bool {
+    read_field_descriptor(descriptor).is_ok()
+}
+
+fn is_method_descriptor(descriptor: &str) -> bool {
+    read_method_descriptor(descriptor).is_ok()
+}
+
+fn check_constants(checker: &mut Checker, class: &ClassFile) {
+    let constants = &class.constants;
+    for (index, constant) in constants.iter() {
+        checker.enter(Structure::Constant(index));
+        match constant {
+            ConstantType::Class { .. } => if let Some(name) = checker.resolve(constants.class_name(index)) {
+                let valid = if name.starts_with('[') { is_field_descriptor(name) } else { is_class_name(name) };
+                checker.expect(valid, Problem::InvalidName { kind: "class", name: name.to_string() });
+            },
+            ConstantType::String { string_index } => {
+                checker.resolve(constants.utf8(*string_index));
+            }
+            ConstantType::FieldRef { .. } => if let Some(member) = checker.resolve(constants.field_ref(index)) {
+                checker.expect(is_unqualified_name(member.name), Problem::InvalidName { kind: "field", name: member.name.to_string() });
+                checker.expect(is_field_descriptor(member.descriptor), Problem::InvalidDescriptor { kind: "field", descriptor: member.descriptor.to_string() });
+            },
+            ConstantType::MethodRef { .. } | ConstantType::InterfaceMethodRef { .. } => {
+                let member = match constant {
+                    ConstantType::MethodRef { .. } => constants.method_ref(index),
+                    _ => constants.interface_method_ref(index)
+                };
+                if let Some(member) = checker.resolve(member) {
+                    // `<clinit>` is never called
+                    let valid = is_method_name(member.name) && member.name != "<clinit>";
+                    checker.expect(valid, Problem::InvalidName { kind: "method", name: member.name.to_string() });
+                    checker.expect(is_method_descriptor(member.descriptor), Problem::InvalidDescriptor { kind: "method", descriptor: member.descriptor.to_string() });
+                    if member.name == "<init>" {
+                        checker.expect(member.descriptor.ends_with(")V"), Problem::InvalidDescriptor { kind: "constructor", descriptor: member.descriptor.to_string() });
+                    }
+                }
+            }
+            ConstantType::NameAndType { .. } => {
+                checker.resolve(constants.name_and_type(index));
+            }
+            ConstantType::MethodHandle { .. } => {
+                checker.resolve(constants.method_handle(index));
+            }
+            ConstantType::MethodType { descriptor_index } => if let Some(descriptor) = checker.resolve(constants.utf8(*descriptor_index)) {
+                checker.expect(is_method_descriptor(descriptor), Problem::InvalidDescriptor { kind: "method", descriptor: descriptor.to_string() });
+            },
+            ConstantType::Dynamic { .. } => if let Some(constant) = checker.resolve(class.dynamic_constant(index)) {
+                checker.expect(is_unqualified_name(constant.name), Problem::InvalidName { kind: "dynamic constant", name: constant.name.to_string() });
+                checker.expect(is_field_descriptor(constant.descriptor), Problem::InvalidDescriptor { kind: "field", descriptor: constant.descriptor.to_string() });
+            },
+            ConstantType::InvokeDynamic { .. } => if let Some(call_site) = checker.resolve(class.invoke_dynamic(index)) {
+                checker.expect(is_unqualified_name(call_site.name), Problem::InvalidName { kind: "call site", name: call_site.name.to_string() });
+                checker.expect(is_method_descriptor(call_site.descriptor), Problem::InvalidDescriptor { kind: "method", descriptor: call_site.descriptor.to_string() });
+            },
+            ConstantType::Module { .. } => {
+                checker.resolve(constants.module_name(index));
+            }
+            ConstantType::Package { .. } => {
+                checker.resolve(constants.package_name(index));
+            }
+            _ => ()
+        }
+        checker.leave();
+    }
+}
+
+fn check_header(checker: &mut Checker, class: &ClassFile) {
+    let flags = class.access();
+    let bits = flags.bits();
+
+    checker.enter(Structure::Header);
+    let name = checker.resolve(class.constants.class_name(class.this_index));
+    if let Some(name) = name {
+        checker.expect(!name.starts_with('['), Problem::InvalidName { kind: "class", name: name.to_string() });
+    }
+
+    if flags.contains(ClassFlags::MODULE) {
+        checker.expect_access(bits, bits == ClassFlags::MODULE.bits(), "a module must not have other flags");
+        checker.expect(class.super_index == 0, Problem::InvalidSuperclass { reason: "a module has no superclass" });
+    } else if class.super_index == 0 {
+        checker.expect(name == Some("java/lang/Object"), Problem::InvalidSuperclass { reason: "only java/lang/Object has no superclass" });
+    } else if let Some(superclass) = checker.resolve(class.constants.class_name(class.super_index)) {
+        let valid = !flags.contains(ClassFlags::INTERFACE) || superclass == "java/lang/Object";
+        checker.expect(valid, Problem::InvalidSuperclass { reason: "the superclass of an interface must be java/lang/Object" });
+    }
+
+    if flags.contains(ClassFlags::INTERFACE) {
+        checker.expect_access(bits, flags.contains(ClassFlags::ABSTRACT), "an interface must be abstract");
+        let forbidden = ClassFlags::FINAL | ClassFlags::SUPER | ClassFlags::ENUM | ClassFlags::MODULE;
+        checker.expect_access(bits, (flags & forbidden).is_empty(), "an interface must not be final, super, enum or a module");
+    } else {
+        checker.expect_access(bits, !flags.contains(ClassFlags::ANNOTATION), "only interfaces can be annotations");
+        checker.expect_access(bits, !flags.contains(ClassFlags::FINAL | ClassFlags::ABSTRACT), "a class must not be final and abstract");
+    }
+    checker.leave();
+
+    checker.enter(Structure::Interfaces);
+    for index in &class.interfaces {
+        checker.resolve(class.constants.class_name(*index));
+    }
+    checker.leave();
+}
+
+/// at most one of `public`, `private` and `protected`
+fn one_visibility(bits: u16) -> bool {
+    (bits & 0x0007).count_ones() <= 1
+}
+
+fn check_field(checker: &mut Checker, class: &ClassFile, field: &Field) {
+    checker.expect(is_unqualified_name(&field.name), Problem::InvalidName { kind: "field", name: field.name.to_string() });
+    checker.expect(is_field_descriptor(&field.descriptor), Problem::InvalidDescriptor { kind: "field", descriptor: field.descriptor.to_string() });
+
+    let flags = field.access();
+    let bits = flags.bits();
+    checker.expect_access(bits, one_visibility(bits), "a field can only have one visibility");
+    checker.expect_access(bits, !flags.contains(FieldFlags::FINAL | FieldFlags::VOLATILE), "a field must not be final and volatile");
+    if class.access().contains(ClassFlags::INTERFACE) {
+        let required = FieldFlags::PUBLIC | FieldFlags::STATIC | FieldFlags::FINAL;
+        let allowed = required | FieldFlags::SYNTHETIC;
+        checker.expect_access(bits, flags.contains(required) && bits & !allowed.bits() == 0, "an interface field must be public static final");
+    }
+}
+
+fn check_method(checker: &mut Checker, class: &ClassFile, method: &Method) {
+    checker.expect(is_method_name(&method.name), Problem::InvalidName { kind: "method", name: method.name.to_string() });
+    checker.expect(is_method_descriptor(&method.descriptor), Problem::InvalidDescriptor { kind: "method", descriptor: method.descriptor.to_string() });
+
+    let flags = method.access();
+    let bits = flags.bits();
+    checker.expect_access(bits, one_visibility(bits), "a method can only have one visibility");
+    if flags.contains(MethodFlags::ABSTRACT) {
+        let forbidden = MethodFlags::PRIVATE | MethodFlags::STATIC | MethodFlags::FINAL | MethodFlags::SYNCHRONIZED | MethodFlags::NATIVE;
+        checker.expect_access(bits, (flags & forbidden).is_empty(), "an abstract method must not be private, static, final, synchronized or native");
+    }
+    if class.access().contains(ClassFlags::INTERFACE) {
+        let forbidden = MethodFlags::PROTECTED | MethodFlags::FINAL | MethodFlags::SYNCHRONIZED | MethodFlags::NATIVE;
+        checker.expect_access(bits, (flags & forbidden).is_empty(), "an interface method must not be protected, final, synchronized or native");
+    }
+    if method.name == "<init>" {
+        let forbidden = MethodFlags::STATIC | MethodFlags::FINAL | MethodFlags::SYNCHRONIZED | MethodFlags::NATIVE | MethodFlags::ABSTRACT;
+        checker.expect_access(bits, (flags & forbidden).is_empty(), "a constructor must not be static, final, synchronized, native or abstract");
+        checker.expect(method.descriptor.ends_with(")V"), Problem::InvalidDescriptor { kind: "constructor", descriptor: method.descriptor.to_string() });
+    }
+
+    let code: Vec<&CodeBlock> = method.attributes.iter()
+        .filter_map(|attr| match attr {
+            Attribute::CodeAttribute(code) => Some(code),
+            _ => None
+        })
+        .collect();
+    let needs_code = !flags.contains(MethodFlags::ABSTRACT) && !flags.contains(MethodFlags::NATIVE);
+    match code.as_slice() {
+        [] => checker.expect(!needs_code, Problem::InvalidCode { reason: "the method has no code" }),
+        [code] => {
+            checker.expect(needs_code, Problem::InvalidCode { reason: "abstract and native methods must not have code" });
+            checker.enter(Structure::Attribute(String::from("Code")));
+            check_code(checker, code);
+            checker.leave();
+        }
+        _ => checker.report(Problem::InvalidCode { reason: "the method has more than one Code attribute" })
+    }
+}
+
+fn check_code(checker: &mut Checker, code: &CodeBlock) {
+    let length = code.code.len();
+    if length == 0 || length > usize::from(u16::MAX) {
+        return checker.report(Problem::CodeLength { length });
+    }
+
+    // where each instruction starts, handlers have to line up with them
+    let mut starts = vec![false; length];
+    let mut pc = 0;
+    while pc < length {
+        starts[pc] = true;
+        match Instruction::length_at(&code.code, pc) {
+            Some(size) => pc += size,
+            None => return checker.report(Problem::InvalidInstruction { pc })
+        }
+    }
+    let is_instruction = |pc: u16| starts.get(usize::from(pc)).cloned().unwrap_or(false);
+
+    for (index, handler) in code.exception_handlers.iter().enumerate() {
+        if handler.start_pc >= handler.end_pc {
+            checker.report(Problem::InvalidHandler { index, reason: "does not cover any code" });
+        } else {
+            checker.expect(is_instruction(handler.start_pc), Problem::InvalidHandler { index, reason: "does not start at an instruction" });
+            let ends = usize::from(handler.end_pc) == length || is_instruction(handler.end_pc);
+            checker.expect(ends, Problem::InvalidHandler { index, reason: "does not end at an instruction" });
+        }
+        checker.expect(is_instruction(handler.handler_pc), Problem::InvalidHandler { index, reason: "does not jump to an instruction" });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use java::class_file::read_class_file;
+    use std::borrow::Cow;
+    use java::samples::{self, LAMBDAS, TRY_CATCH};
+
+    fn problems(class: &ClassFile) -> Vec<String> {
+        check(class).iter().map(|diagnostic| diagnostic.to_string()).collect()
+    }
+
+    #[test]
+    fn compiled_classes_are_well_formed() {
+        for (name, bytes) in samples::ALL {
+            let class = read_class_file(bytes).unwrap();
+            assert_eq!(Vec::<String>::new(), problems(&class), "{}", name);
+        }
+    }
+
+    #[test]
+    fn it_checks_constant_pool_references() {
+        let mut class = read_class_file(TRY_CATCH).unwrap();
+        let utf8 = class.constants.iter().find(|(_, constant)| constant.name() == "Utf8").unwrap().0;
+        let this_index = class.this_index;
+        class.constants.push(ConstantType::NameAndType { name_index: this_index, descriptor_index: utf8 });
+        class.this_index = utf8;
+
+        let diagnostics = check(&class);
+        assert_eq!(2, diagnostics.len());
+        assert_eq!(vec![Structure::Constant(class.constants.len() as u16)], diagnostics[0].location);
+        assert_eq!(vec![Structure::Header], diagnostics[1].location);
+        match diagnostics[1].problem {
+            Problem::InvalidConstant(ConstantPoolError::WrongType { expected: "Class", .. }) => (),
+            ref other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn it_checks_dynamic_constants() {
+        let mut class = read_class_file(LAMBDAS).unwrap();
+        let call_site = class.constants.iter()
+            .find_map(|(_, constant)| match constant {
+                ConstantType::InvokeDynamic { name_and_type_index, .. } => Some(*name_and_type_index),
+                _ => None
+            })
+            .unwrap();
+        class.constants.push(ConstantType::Dynamic { bootstrap_method_attr_index: 0, name_and_type_index: call_site });
+        class.constants.push(ConstantType::Dynamic { bootstrap_method_attr_index: 99, name_and_type_index: call_site });
+
+        let problems = problems(&class);
+        assert_eq!(2, problems.len());
+        assert!(problems[0].starts_with("invalid field descriptor \"("), "{}", problems[0]);
+        assert!(problems[1].contains("missing bootstrap method"), "{}", problems[1]);
+    }
+
+    #[test]
+    fn it_checks_members() {
+        let mut class = read_class_file(TRY_CATCH).unwrap();
+        let method = class.methods[0].clone();
+        class.methods.push(method);
+        class.methods[1].access_flags |= MethodFlags::ABSTRACT.bits();
+        class.methods[2].name = Cow::Borrowed("a.b");
+        class.methods[2].descriptor = Cow::Borrowed("(X)V");
+
+        assert_eq!(
+            vec![
+                "invalid access flags 0x040a: an abstract method must not be private, static, final, synchronized or native in method #1",
+                "abstract and native methods must not have code in method #1",
+                "invalid method name \"a.b\" in method #2",
+                "invalid method descriptor \"(X)V\" in method #2",
+                "<init>()V is declared more than once in method #4",
+            ],
+            problems(&class)
+        );
+    }
+
+    #[test]
+    fn it_checks_exception_handlers() {
+        let mut class = read_class_file(TRY_CATCH).unwrap();
+        {
+            let code = match class.methods[2].attributes[0] {
+                Attribute::CodeAttribute(ref mut code) => code,
+                ref other => panic!("unexpected {:?}", other)
+            };
+            let handler = code.exception_handlers[0].clone();
+            code.exception_handlers.push(handler);
+            // inside the operands of `invokestatic`
+            code.exception_handlers[0].start_pc = 3;
+            code.exception_handlers[1].end_pc = 0;
+        }
+
+        let diagnostics = check(&class);
+        assert_eq!(vec![Structure::Method(2), Structure::Attribute(String::from("Code"))], diagnostics[0].location);
+        assert_eq!(
+            vec!["exception handler #0 does not start at an instruction", "exception handler #1 does not cover any code"],
+            diagnostics.iter().map(|diagnostic| diagnostic.problem.to_string()).collect::<Vec<String>>()
+        );
+    }
+}
@@ -0,0 +1,327 @@
+use java::class_file::ConstantType;
+use std::borrow::Cow;
+
+/// errors returned by the typed lookups on `ConstantPool`.
+#[derive(Debug, Fail)]
+pub enum ConstantPoolError {
+    #[fail(display = "constant pool index {} is out of range", index)]
+    InvalidIndex { index: u16 },
+    #[fail(display = "constant pool index {} points to the unusable slot after a long or double", index)]
+    UnusableSlot { index: u16 },
+    #[fail(display = "constant pool entry {} has the wrong type. expected: {}, found: {}", index, expected, found)]
+    WrongType { index: u16, expected: &'static str, found: &'static str },
+    #[fail(display = "method handle {} has the invalid reference kind {}", index, kind)]
+    InvalidReferenceKind { index: u16, kind: u8 },
+    #[fail(display = "invoke dynamic constant {} refers to a missing bootstrap method", index)]
+    MissingBootstrapMethod { index: u16 },
+}
+
+/// a resolved `FieldRef`, `MethodRef` or `InterfaceMethodRef` constant
+#[derive(Debug, Eq, PartialEq)]
+pub struct MemberRef<'a> {
+    pub class_name: &'a str,
+    pub name: &'a str,
+    pub descriptor: &'a str,
+}
+
+/// the `reference_kind` of a `MethodHandle` constant (JVMS 5.4.3.5)
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ReferenceKind {
+    GetField,
+    GetStatic,
+    PutField,
+    PutStatic,
+    InvokeVirtual,
+    InvokeStatic,
+    InvokeSpecial,
+    NewInvokeSpecial,
+    InvokeInterface,
+}
+
+/// a resolved `MethodHandle` constant
+#[derive(Debug, Eq, PartialEq)]
+pub struct MethodHandle<'a> {
+    pub kind: ReferenceKind,
+    pub member: MemberRef<'a>,
+}
+
+/// a resolved `InvokeDynamic` or `Dynamic` constant together with its entry in `BootstrapMethods`
+#[derive(Debug)]
+pub struct InvokeDynamic<'c, 'a: 'c> {
+    pub bootstrap: MethodHandle<'c>,
+    /// the static arguments for the bootstrap method
+    pub arguments: Vec<&'c ConstantType<'a>>,
+    pub name: &'c str,
+    pub descriptor: &'c str,
+}
+
+/// the constant pool of a class file.
+///
+/// the pool is indexed from 1 to `constant_pool_count - 1`. `Long` and `Double` constants
+/// take up two slots, the second one is unusable and stored as `None`.
+#[derive(Debug)]
+pub struct ConstantPool<'a> {
+    entries: Vec<Option<ConstantType<'a>>>,
+}
+
+impl<'a> Default for ConstantPool<'a> {
+    fn default() -> ConstantPool<'a> {
+        ConstantPool::new()
+    }
+}
+
+impl<'a> ConstantPool<'a> {
+    pub fn new() -> ConstantPool<'a> {
+        ConstantPool { entries: Vec::new() }
+    }
+
+    /// appends a constant, reserving the unusable second slot for `Long` and `Double`
+    pub fn push(&mut self, constant: ConstantType<'a>) {
+        let wide = constant.is_wide();
+        self.entries.push(Some(constant));
+        if wide {
+            self.entries.push(None);
+        }
+    }
+
+    /// the number of slots in the pool, which is `constant_pool_count - 1`
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn into_owned(self) -> ConstantPool<'static> {
+        ConstantPool {
+            entries: self.entries.into_iter().map(|entry| entry.map(ConstantType::into_owned)).collect(),
+        }
+    }
+
+    /// iterates over all usable entries together with their index
+    pub fn iter(&self) -> impl Iterator<Item=(u16, &ConstantType<'a>)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, entry)| entry.as_ref().map(|constant| ((idx + 1) as u16, constant)))
+    }
+
+    pub fn get(&self, index: u16) -> Result<&ConstantType<'a>, ConstantPoolError> {
+        if index == 0 {
+            return Err(ConstantPoolError::InvalidIndex { index });
+        }
+
+        match self.entries.get(usize::from(index) - 1) {
+            Some(Some(constant)) => Ok(constant),
+            Some(None) => Err(ConstantPoolError::UnusableSlot { index }),
+            None => Err(ConstantPoolError::InvalidIndex { index })
+        }
+    }
+
+    pub fn utf8(&self, index: u16) -> Result<&str, ConstantPoolError> {
+        self.utf8_cow(index).map(|value| &**value)
+    }
+
+    /// like `utf8`, but returns the `Cow` itself. cloning it does not copy borrowed strings.
+    pub fn utf8_cow(&self, index: u16) -> Result<&Cow<'a, str>, ConstantPoolError> {
+        match self.get(index)? {
+            ConstantType::Utf8 { value, .. } => Ok(value),
+            other => Err(ConstantPoolError::WrongType { index, expected: "Utf8", found: other.name() })
+        }
+    }
+
+    /// resolves a `Class` constant to its (internal) class name, e.g. `java/lang/Object`
+    pub fn class_name(&self, index: u16) -> Result<&str, ConstantPoolError> {
+        self.class_name_cow(index).map(|value| &**value)
+    }
+
+    pub fn class_name_cow(&self, index: u16) -> Result<&Cow<'a, str>, ConstantPoolError> {
+        match self.get(index)? {
+            ConstantType::Class { name_index } => self.utf8_cow(*name_index),
+            other => Err(ConstantPoolError::WrongType { index, expected: "Class", found: other.name() })
+        }
+    }
+
+    /// resolves a `Module` constant to the module name, e.g. `java.base`
+    pub fn module_name(&self, index: u16) -> Result<&str, ConstantPoolError> {
+        self.module_name_cow(index).map(|value| &**value)
+    }
+
+    pub fn module_name_cow(&self, index: u16) -> Result<&Cow<'a, str>, ConstantPoolError> {
+        match self.get(index)? {
+            ConstantType::Module { name_index } => self.utf8_cow(*name_index),
+            other => Err(ConstantPoolError::WrongType { index, expected: "Module", found: other.name() })
+        }
+    }
+
+    /// resolves a `Package` constant to the (internal) package name, e.g. `java/lang`
+    pub fn package_name(&self, index: u16) -> Result<&str, ConstantPoolError> {
+        self.package_name_cow(index).map(|value| &**value)
+    }
+
+    pub fn package_name_cow(&self, index: u16) -> Result<&Cow<'a, str>, ConstantPoolError> {
+        match self.get(index)? {
+            ConstantType::Package { name_index } => self.utf8_cow(*name_index),
+            other => Err(ConstantPoolError::WrongType { index, expected: "Package", found: other.name() })
+        }
+    }
+
+    /// resolves a `NameAndType` constant to `(name, descriptor)`
+    pub fn name_and_type(&self, index: u16) -> Result<(&str, &str), ConstantPoolError> {
+        match self.get(index)? {
+            ConstantType::NameAndType { name_index, descriptor_index } => {
+                Ok((self.utf8(*name_index)?, self.utf8(*descriptor_index)?))
+            }
+            other => Err(ConstantPoolError::WrongType { index, expected: "NameAndType", found: other.name() })
+        }
+    }
+
+    pub fn field_ref(&self, index: u16) -> Result<MemberRef<'_>, ConstantPoolError> {
+        match self.get(index)? {
+            ConstantType::FieldRef { class_index, name_and_type_index } => {
+                self.member_ref(*class_index, *name_and_type_index)
+            }
+            other => Err(ConstantPoolError::WrongType { index, expected: "FieldRef", found: other.name() })
+        }
+    }
+
+    pub fn method_ref(&self, index: u16) -> Result<MemberRef<'_>, ConstantPoolError> {
+        match self.get(index)? {
+            ConstantType::MethodRef { class_index, name_and_type_index } => {
+                self.member_ref(*class_index, *name_and_type_index)
+            }
+            other => Err(ConstantPoolError::WrongType { index, expected: "MethodRef", found: other.name() })
+        }
+    }
+
+    pub fn interface_method_ref(&self, index: u16) -> Result<MemberRef<'_>, ConstantPoolError> {
+        match self.get(index)? {
+            ConstantType::InterfaceMethodRef { class_index, name_and_type_index } => {
+                self.member_ref(*class_index, *name_and_type_index)
+            }
+            other => Err(ConstantPoolError::WrongType { index, expected: "InterfaceMethodRef", found: other.name() })
+        }
+    }
+
+    /// resolves a `MethodHandle` constant to its kind and the referenced field or method
+    pub fn method_handle(&self, index: u16) -> Result<MethodHandle<'_>, ConstantPoolError> {
+        let (reference_kind, reference_index) = match self.get(index)? {
+            ConstantType::MethodHandle { reference_kind, reference_index } => (*reference_kind, *reference_index),
+            other => return Err(ConstantPoolError::WrongType { index, expected: "MethodHandle", found: other.name() })
+        };
+
+        let kind = match reference_kind {
+            1 => ReferenceKind::GetField,
+            2 => ReferenceKind::GetStatic,
+            3 => ReferenceKind::PutField,
+            4 => ReferenceKind::PutStatic,
+            5 => ReferenceKind::InvokeVirtual,
+            6 => ReferenceKind::InvokeStatic,
+            7 => ReferenceKind::InvokeSpecial,
+            8 => ReferenceKind::NewInvokeSpecial,
+            9 => ReferenceKind::InvokeInterface,
+            _ => return Err(ConstantPoolError::InvalidReferenceKind { index, kind: reference_kind })
+        };
+
+        let member = match kind {
+            ReferenceKind::GetField | ReferenceKind::GetStatic | ReferenceKind::PutField | ReferenceKind::PutStatic =>
+                self.field_ref(reference_index)?,
+            ReferenceKind::InvokeVirtual | ReferenceKind::NewInvokeSpecial => self.method_ref(reference_index)?,
+            ReferenceKind::InvokeInterface => self.interface_method_ref(reference_index)?,
+            // since java 8 these may also point to interface methods
+            ReferenceKind::InvokeStatic | ReferenceKind::InvokeSpecial => match self.get(reference_index)? {
+                ConstantType::InterfaceMethodRef { .. } => self.interface_method_ref(reference_index)?,
+                _ => self.method_ref(reference_index)?
+            }
+        };
+
+        Ok(MethodHandle { kind, member })
+    }
+
+    fn member_ref(&self, class_index: u16, name_and_type_index: u16) -> Result<MemberRef<'_>, ConstantPoolError> {
+        let (name, descriptor) = self.name_and_type(name_and_type_index)?;
+        Ok(MemberRef { class_name: self.class_name(class_index)?, name, descriptor })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn utf8(value: &str) -> ConstantType<'_> {
+        ConstantType::Utf8 { value: Cow::Borrowed(value), bytes: Cow::Borrowed(value.as_bytes()) }
+    }
+
+    fn pool<'a>() -> ConstantPool<'a> {
+        let mut pool = ConstantPool::new();
+        pool.push(utf8("Foo"));
+        pool.push(ConstantType::Class { name_index: 1 });
+        pool.push(ConstantType::Long { value: 42 });
+        pool.push(utf8("bar"));
+        pool.push(utf8("()V"));
+        pool.push(ConstantType::NameAndType { name_index: 5, descriptor_index: 6 });
+        pool.push(ConstantType::MethodRef { class_index: 2, name_and_type_index: 7 });
+        pool
+    }
+
+    #[test]
+    fn long_constants_take_two_slots() {
+        let pool = pool();
+        assert_eq!(8, pool.len());
+        match pool.get(3) {
+            Ok(ConstantType::Long { value: 42 }) => (),
+            other => panic!("unexpected {:?}", other)
+        }
+        match pool.get(4) {
+            Err(ConstantPoolError::UnusableSlot { index: 4 }) => (),
+            other => panic!("unexpected {:?}", other)
+        }
+        assert_eq!("bar", pool.utf8(5).unwrap());
+    }
+
+    #[test]
+    fn typed_lookups_resolve_references() {
+        let pool = pool();
+        assert_eq!("Foo", pool.class_name(2).unwrap());
+        assert_eq!(("bar", "()V"), pool.name_and_type(7).unwrap());
+        assert_eq!(MemberRef { class_name: "Foo", name: "bar", descriptor: "()V" }, pool.method_ref(8).unwrap());
+    }
+
+    #[test]
+    fn method_handles_resolve_their_member() {
+        let mut pool = pool();
+        pool.push(ConstantType::MethodHandle { reference_kind: 6, reference_index: 8 });
+        pool.push(ConstantType::MethodHandle { reference_kind: 1, reference_index: 8 });
+        pool.push(ConstantType::MethodHandle { reference_kind: 10, reference_index: 8 });
+
+        let handle = pool.method_handle(9).unwrap();
+        assert_eq!(ReferenceKind::InvokeStatic, handle.kind);
+        assert_eq!(MemberRef { class_name: "Foo", name: "bar", descriptor: "()V" }, handle.member);
+        match pool.method_handle(10) {
+            Err(ConstantPoolError::WrongType { index: 8, expected: "FieldRef", found: "MethodRef" }) => (),
+            other => panic!("unexpected {:?}", other)
+        }
+        match pool.method_handle(11) {
+            Err(ConstantPoolError::InvalidReferenceKind { index: 11, kind: 10 }) => (),
+            other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn typed_lookups_report_bad_indexes_and_types() {
+        let pool = pool();
+        match pool.utf8(0) {
+            Err(ConstantPoolError::InvalidIndex { index: 0 }) => (),
+            other => panic!("unexpected {:?}", other)
+        }
+        match pool.utf8(9) {
+            Err(ConstantPoolError::InvalidIndex { index: 9 }) => (),
+            other => panic!("unexpected {:?}", other)
+        }
+        match pool.class_name(1) {
+            Err(ConstantPoolError::WrongType { index: 1, expected: "Class", found: "Utf8" }) => (),
+            other => panic!("unexpected {:?}", other)
+        }
+    }
+}
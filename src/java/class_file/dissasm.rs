@@ -8,9 +8,37 @@ pub fn disassemble<'a>(method: &Method<'a>) -> String {
         Err(err) => panic!("{:?}", err)
     };
 
-    instructions.iter().map(| instruction | {
+    let mut output = instructions.iter().map(| instruction | {
         format!("> {:?}\n", instruction)
-    }).collect::<String>()
+    }).collect::<String>();
 
+    if !code_block.exception_handlers().is_empty() {
+        output.push_str("exception table:\n");
+        for handler in code_block.exception_handlers() {
+            output.push_str(&format!(
+                "> {} {} {} {}\n",
+                handler.start_pc,
+                handler.end_pc,
+                handler.handler_pc,
+                handler.catch_type.as_deref().unwrap_or("any")
+            ));
+        }
+    }
+
+    if !code_block.local_variables().is_empty() {
+        output.push_str("local variables:\n");
+        for variable in code_block.local_variables() {
+            output.push_str(&format!(
+                "> {} {} {} {} {}\n",
+                variable.start_pc,
+                variable.length,
+                variable.index,
+                variable.name,
+                variable.descriptor
+            ));
+        }
+    }
+
+    output
 }
 
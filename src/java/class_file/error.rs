@@ -0,0 +1,94 @@
+use java::class_file::mutf8::ModifiedUtf8Error;
+use java::class_file::ConstantPoolError;
+use failure::Fail;
+use std::fmt;
+
+/// the part of a class file that was being parsed when an error occurred
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Structure {
+    Header,
+    /// constant pool entry, by its pool index
+    Constant(u16),
+    Interfaces,
+    /// field, by its position in the fields table
+    Field(u16),
+    /// method, by its position in the methods table
+    Method(u16),
+    /// record component, by its position in the `Record` attribute
+    RecordComponent(u16),
+    /// attribute, by its name
+    Attribute(String),
+    Descriptor(String),
+    /// generic signature
+    Signature(String),
+}
+
+impl fmt::Display for Structure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Structure::Header => write!(f, "header"),
+            Structure::Constant(index) => write!(f, "constant #{}", index),
+            Structure::Interfaces => write!(f, "interfaces"),
+            Structure::Field(index) => write!(f, "field #{}", index),
+            Structure::Method(index) => write!(f, "method #{}", index),
+            Structure::RecordComponent(index) => write!(f, "record component #{}", index),
+            Structure::Attribute(name) => write!(f, "attribute {}", name),
+            Structure::Descriptor(descriptor) => write!(f, "descriptor {:?}", descriptor),
+            Structure::Signature(signature) => write!(f, "signature {:?}", signature),
+        }
+    }
+}
+
+#[derive(Debug, Fail)]
+pub enum ErrorReason {
+    #[fail(display = "unexpected end of input")]
+    UnexpectedEnd,
+    #[fail(display = "not a class file (wrong magic number)")]
+    InvalidMagic,
+    #[fail(display = "unknown constant tag {}", tag)]
+    UnknownConstantTag { tag: u8 },
+    #[fail(display = "{}", _0)]
+    InvalidUtf8(#[cause] ModifiedUtf8Error),
+    #[fail(display = "{}", _0)]
+    InvalidConstant(#[cause] ConstantPoolError),
+    #[fail(display = "attribute declares {} bytes but {} were read", declared, consumed)]
+    AttributeLength { declared: usize, consumed: usize },
+    #[fail(display = "{} unexpected trailing bytes", count)]
+    TrailingBytes { count: usize },
+    #[fail(display = "array type with more than {} dimensions", max)]
+    TooManyDimensions { max: usize },
+    #[fail(display = "nested more than {} levels deep", max)]
+    NestedTooDeep { max: usize },
+    #[fail(display = "stack map frame #{} lies beyond the largest code offset", frame)]
+    FrameOffset { frame: usize },
+    #[fail(display = "malformed data ({})", kind)]
+    Malformed { kind: String },
+}
+
+/// returned when a class file (or a descriptor) cannot be parsed.
+///
+/// `structure` is the path to the broken part, outermost first, e.g.
+/// `[Method(2), Attribute("Code"), Attribute("LineNumberTable")]`.
+#[derive(Debug)]
+pub struct ClassFileError {
+    pub offset: usize,
+    pub structure: Vec<Structure>,
+    pub reason: ErrorReason,
+}
+
+impl fmt::Display for ClassFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "class file error at offset {}", self.offset)?;
+        for (idx, structure) in self.structure.iter().enumerate() {
+            write!(f, "{}{}", if idx == 0 { " in " } else { " > " }, structure)?;
+        }
+
+        write!(f, ": {}", self.reason)
+    }
+}
+
+impl Fail for ClassFileError {
+    fn cause(&self) -> Option<&dyn Fail> {
+        Some(&self.reason)
+    }
+}
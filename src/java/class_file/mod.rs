@@ -1,15 +1,43 @@
 mod parser;
+mod constant_pool;
+mod access;
+mod writer;
+pub mod annotation;
+pub mod check;
+pub mod error;
+pub mod module;
+pub mod mutf8;
+pub mod nesting;
+pub mod signature;
+pub mod stack_map;
+pub mod version;
 pub mod dissasm;
 
 use java::instructions::*;
-pub use self::parser::read_class_file;
+pub use self::parser::{read_class_file, read_class_file_lazy};
+pub use self::writer::{write_class_file, WriteError};
+pub use self::access::{ClassAccess, ClassFlags, FieldAccess, FieldFlags, MethodAccess, MethodFlags};
+pub use self::access::{InnerClassAccess, InnerClassFlags};
+pub use self::access::{ExportsAccess, ExportsFlags, ModuleAccess, ModuleFlags, RequiresAccess, RequiresFlags};
+pub use self::constant_pool::{ConstantPool, ConstantPoolError, InvokeDynamic, MemberRef, MethodHandle, ReferenceKind};
+pub use self::error::ClassFileError;
+use self::error::{ErrorReason, Structure};
+use self::annotation::{Annotation, ElementValue, TypeAnnotation};
+use self::module::{Module, ModuleDescriptor};
+use self::nesting::{EnclosingMethod, InnerClass};
+use self::signature::{ClassSignature, MethodSignature, TypeSignature};
+use self::stack_map::{FrameState, StackMapFrame};
+use std::borrow::Cow;
 use std::collections::HashSet;
 use std::str::FromStr;
 
 #[derive(Debug)]
 pub struct ClassFile<'a> {
     pub version: (u16, u16),
-    pub constants: Vec<ConstantType<'a>>,
+    pub constants: ConstantPool<'a>,
+    /// the constant pool index of every name and value the parser resolved, in order.
+    /// the writer reuses them, so duplicate entries keep pointing where they did
+    pub references: Vec<u16>,
     pub access_flags: u16,
     pub this_index: u16,
     pub super_index: u16,
@@ -19,108 +47,640 @@ pub struct ClassFile<'a> {
     pub attributes: Vec<Attribute<'a>>,
 }
 
-impl<'a> ClassFile<'a> {
-    pub fn get_constant(&self, index: u16) -> Option<&ConstantType> {
-        self.constants.get(index as usize - 1)
+/// the queries `ClassFile` and `LazyClassFile` have in common.
+///
+/// both decode the header and the constant pool up front. a `ClassFile` hands out its tables,
+/// a `LazyClassFile` parses them again on every call.
+pub trait ClassQuery {
+    /// `(major, minor)`
+    fn version(&self) -> (u16, u16);
+
+    fn constants(&self) -> &ConstantPool<'_>;
+
+    fn access_flags(&self) -> u16;
+
+    fn this_index(&self) -> u16;
+
+    fn super_index(&self) -> u16;
+
+    /// the `Class` constants of the direct superinterfaces
+    fn interfaces(&self) -> &[u16];
+
+    fn fields(&self) -> Result<Cow<'_, [Field<'_>]>, ClassFileError>;
+
+    fn methods(&self) -> Result<Cow<'_, [Method<'_>]>, ClassFileError>;
+
+    fn attributes(&self) -> Result<Cow<'_, [Attribute<'_>]>, ClassFileError>;
+
+    /// finds a method by name and descriptor
+    fn find_method(&self, name: &str, descriptor: &str) -> Result<Option<Method<'_>>, ClassFileError> {
+        Ok(self.methods()?.iter().find(|method| method.name == name && method.descriptor == descriptor).cloned())
     }
 
-    pub fn get_class_name(&self) -> &str {
-        let cls = self.get_constant(self.this_index).unwrap();
-        let cls_name = match cls {
-            ConstantType::Class { name_index: idx } => self.get_constant(*idx).unwrap(),
-            _ => panic!("cannot read class name")
-        };
+    fn get_constant(&self, index: u16) -> Result<&ConstantType<'_>, ConstantPoolError> {
+        self.constants().get(index)
+    }
 
+    fn get_class_name(&self) -> Result<&str, ConstantPoolError> {
+        self.constants().class_name(self.this_index())
+    }
 
-        match cls_name {
-            ConstantType::Utf8 { value } => &value,
-            _ => panic!("cannot read class name")
+    /// the name of the direct superclass, `None` for `java/lang/Object` (and `module-info`)
+    fn super_class_name(&self) -> Result<Option<&str>, ConstantPoolError> {
+        if self.super_index() == 0 {
+            return Ok(None);
         }
+
+        self.constants().class_name(self.super_index()).map(Some)
     }
 
-    pub fn get_method_from_nat(&self, nat_index: u16) -> Option<&Method> {
-        let name_and_type = self.get_constant(nat_index);
+    /// the names of the direct superinterfaces, in the order of the `implements` clause
+    fn interface_names(&self) -> Result<Vec<&str>, ConstantPoolError> {
+        self.interfaces().iter().map(|index| self.constants().class_name(*index)).collect()
+    }
 
-        let (name_index, type_index) = match name_and_type {
-            Some(ConstantType::NameAndType { name_index, descriptor_index }) => {
-                (name_index, descriptor_index)
-            }
-            _ => return None
-        };
+    /// true if the class uses preview features of its java release
+    fn is_preview(&self) -> bool {
+        version::is_preview(self.version())
+    }
 
-        let name = match self.get_constant(*name_index) {
-            Some(ConstantType::Utf8 { value }) => {
-                *value
-            }
-            _ => return None
+    fn access(&self) -> ClassFlags {
+        ClassFlags::from_bits(self.access_flags())
+    }
+
+    fn get_access(&self) -> HashSet<ClassAccess> {
+        self.access().to_set()
+    }
+
+    /// finds the method a `NameAndType` constant refers to
+    fn get_method_from_nat(&self, nat_index: u16) -> Result<Option<Cow<'_, Method<'_>>>, ClassFileError> {
+        let (name, descriptor) = self.constants().name_and_type(nat_index).map_err(|err| constant_error(nat_index, err))?;
+        let matches = |method: &Method| method.name == name && method.descriptor == descriptor;
+        Ok(match self.methods()? {
+            Cow::Borrowed(methods) => methods.iter().find(|method| matches(method)).map(Cow::Borrowed),
+            Cow::Owned(methods) => methods.into_iter().find(|method| matches(method)).map(Cow::Owned),
+        })
+    }
+
+    /// resolves the `InvokeDynamic` constant at `index` together with its bootstrap method
+    fn invoke_dynamic(&self, index: u16) -> Result<InvokeDynamic<'_, '_>, ClassFileError> {
+        match self.constants().get(index).map_err(|err| constant_error(index, err))? {
+            ConstantType::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } =>
+                bootstrap_call(self, index, *bootstrap_method_attr_index, *name_and_type_index),
+            other => Err(constant_error(index, ConstantPoolError::WrongType { index, expected: "InvokeDynamic", found: other.name() }))
+        }
+    }
+
+    /// resolves the `Dynamic` constant at `index` together with its bootstrap method. the
+    /// descriptor is the field type of the constant.
+    fn dynamic_constant(&self, index: u16) -> Result<InvokeDynamic<'_, '_>, ClassFileError> {
+        match self.constants().get(index).map_err(|err| constant_error(index, err))? {
+            ConstantType::Dynamic { bootstrap_method_attr_index, name_and_type_index } =>
+                bootstrap_call(self, index, *bootstrap_method_attr_index, *name_and_type_index),
+            other => Err(constant_error(index, ConstantPoolError::WrongType { index, expected: "Dynamic", found: other.name() }))
+        }
+    }
+
+    /// the visible and invisible type annotations
+    fn type_annotations(&self) -> Result<Vec<Cow<'_, TypeAnnotation<'_>>>, ClassFileError> {
+        Ok(match self.attributes()? {
+            Cow::Borrowed(attributes) => find_type_annotations(attributes).into_iter().map(Cow::Borrowed).collect(),
+            Cow::Owned(attributes) => find_type_annotations(&attributes).into_iter().cloned().map(Cow::Owned).collect(),
+        })
+    }
+
+    /// finds a visible or invisible annotation by its type descriptor, e.g. `Lorg/junit/Test;`
+    fn annotation(&self, type_name: &str) -> Result<Option<Cow<'_, Annotation<'_>>>, ClassFileError> {
+        Ok(find_in(&self.attributes()?, |attr| match attr {
+            Attribute::RuntimeVisibleAnnotations(annotations) |
+            Attribute::RuntimeInvisibleAnnotations(annotations) => annotations.iter().find(|annotation| annotation.type_name == type_name),
+            _ => None
+        }))
+    }
+
+    /// the generic signature, if the class has one
+    fn generic_signature(&self) -> Result<Option<ClassSignature>, ClassFileError> {
+        match signature_attribute(&self.attributes()?) {
+            Some(signature) => ClassSignature::from_str(signature).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    /// the name of the source file without its path, e.g. `HelloWorld.java`
+    fn source_file(&self) -> Result<Option<Cow<'_, str>>, ClassFileError> {
+        Ok(find_in(&self.attributes()?, |attr| match attr {
+            Attribute::SourceFile(name) => Some(&**name),
+            _ => None
+        }))
+    }
+
+    /// the raw bytes of the `SourceDebugExtension`, e.g. an SMAP for classes compiled from JSP
+    fn source_debug_extension(&self) -> Result<Option<Cow<'_, [u8]>>, ClassFileError> {
+        Ok(find_in(&self.attributes()?, |attr| match attr {
+            Attribute::SourceDebugExtension(value) => Some(&**value),
+            _ => None
+        }))
+    }
+
+    /// the `SourceDebugExtension` as text, `None` if it is missing or not modified UTF-8
+    fn source_debug_extension_text(&self) -> Result<Option<Cow<'_, str>>, ClassFileError> {
+        Ok(match self.source_debug_extension()? {
+            Some(Cow::Borrowed(bytes)) => mutf8::decode(bytes).ok(),
+            Some(Cow::Owned(bytes)) => mutf8::decode(&bytes).ok().map(|text| Cow::Owned(text.into_owned())),
+            None => None
+        })
+    }
+
+    /// the entries of the `InnerClasses` attribute, empty if there is none
+    fn inner_classes(&self) -> Result<Cow<'_, [InnerClass<'_>]>, ClassFileError> {
+        Ok(find_in(&self.attributes()?, |attr| match attr {
+            Attribute::InnerClasses(classes) => Some(classes.as_slice()),
+            _ => None
+        }).unwrap_or(Cow::Borrowed(&[])))
+    }
+
+    /// the method (or class) a local or anonymous class is declared in
+    fn enclosing_method(&self) -> Result<Option<Cow<'_, EnclosingMethod<'_>>>, ClassFileError> {
+        Ok(find_in(&self.attributes()?, |attr| match attr {
+            Attribute::EnclosingMethod(method) => Some(method),
+            _ => None
+        }))
+    }
+
+    /// the members of the nest hosted by this class, empty if it is not a nest host
+    fn nest_members(&self) -> Result<Cow<'_, [Cow<'_, str>]>, ClassFileError> {
+        Ok(find_in(&self.attributes()?, |attr| match attr {
+            Attribute::NestMembers(members) => Some(members.as_slice()),
+            _ => None
+        }).unwrap_or(Cow::Borrowed(&[])))
+    }
+
+    /// the host of the nest this class belongs to. a class without `NestHost` hosts its own nest.
+    fn nest_host(&self) -> Result<Cow<'_, str>, ClassFileError> {
+        let host = find_in(&self.attributes()?, |attr| match attr {
+            Attribute::NestHost(host) => Some(&**host),
+            _ => None
+        });
+
+        match host {
+            Some(host) => Ok(host),
+            None => self.get_class_name().map(Cow::Borrowed).map_err(header_error)
+        }
+    }
+
+    /// the `InnerClasses` entry describing this class, `None` if it is a top level class
+    fn inner_class_entry(&self) -> Result<Option<Cow<'_, InnerClass<'_>>>, ClassFileError> {
+        let name = self.get_class_name().map_err(header_error)?;
+        Ok(find_in(&self.attributes()?, |attr| match attr {
+            Attribute::InnerClasses(classes) => classes.iter().find(|inner| inner.inner_class == name),
+            _ => None
+        }))
+    }
+
+    /// the class this class is declared in. for local and anonymous classes, this is the class
+    /// of the enclosing method.
+    fn outer_class(&self) -> Result<Option<Cow<'_, str>>, ClassFileError> {
+        let outer = match self.inner_class_entry()? {
+            Some(Cow::Borrowed(inner)) => inner.outer_class.as_ref().map(|outer| Cow::Borrowed(&**outer)),
+            Some(Cow::Owned(inner)) => inner.outer_class,
+            None => None
         };
+        if outer.is_some() {
+            return Ok(outer);
+        }
 
-        let type_desc = match self.get_constant(*type_index) {
-            Some(ConstantType::Utf8 { value }) => {
-                *value
+        Ok(match self.enclosing_method()? {
+            Some(Cow::Borrowed(method)) => Some(Cow::Borrowed(&*method.class_name)),
+            Some(Cow::Owned(method)) => Some(method.class_name),
+            None => None
+        })
+    }
+
+    /// the name in the source, without the package and outer classes. empty for anonymous classes.
+    fn simple_name(&self) -> Result<Cow<'_, str>, ClassFileError> {
+        match self.inner_class_entry()? {
+            Some(Cow::Borrowed(inner)) => Ok(Cow::Borrowed(inner.inner_name.as_ref().map_or("", |name| &**name))),
+            Some(Cow::Owned(inner)) => Ok(inner.inner_name.unwrap_or(Cow::Borrowed(""))),
+            None => {
+                let name = self.get_class_name().map_err(header_error)?;
+                Ok(Cow::Borrowed(name.rsplit('/').next().unwrap_or(name)))
             }
-            _ => return None
-        };
+        }
+    }
+
+    fn is_anonymous(&self) -> Result<bool, ClassFileError> {
+        Ok(self.inner_class_entry()?.is_some_and(|inner| inner.is_anonymous()))
+    }
+
+    fn is_local(&self) -> Result<bool, ClassFileError> {
+        Ok(self.inner_class_entry()?.is_some_and(|inner| inner.is_local()))
+    }
+
+    /// the components of a record class, `None` if this is not a record
+    fn record_components(&self) -> Result<Option<Cow<'_, [RecordComponent<'_>]>>, ClassFileError> {
+        Ok(find_in(&self.attributes()?, |attr| match attr {
+            Attribute::Record(components) => Some(components.as_slice()),
+            _ => None
+        }))
+    }
+
+    fn is_record(&self) -> Result<bool, ClassFileError> {
+        Ok(self.attributes()?.iter().any(|attr| matches!(attr, Attribute::Record(_))))
+    }
+
+    /// the classes permitted to directly extend or implement this class, empty if it is not sealed
+    fn permitted_subclasses(&self) -> Result<Cow<'_, [Cow<'_, str>]>, ClassFileError> {
+        Ok(find_in(&self.attributes()?, |attr| match attr {
+            Attribute::PermittedSubclasses(classes) => Some(classes.as_slice()),
+            _ => None
+        }).unwrap_or(Cow::Borrowed(&[])))
+    }
+
+    fn is_sealed(&self) -> Result<bool, ClassFileError> {
+        Ok(!self.permitted_subclasses()?.is_empty())
+    }
+
+    /// the module declared by a `module-info.class`, `None` for all other class files
+    fn module_descriptor(&self) -> Result<Option<ModuleDescriptor<'_, '_>>, ClassFileError> {
+        let attributes = self.attributes()?;
+        let module = find_in(&attributes, |attr| match attr {
+            Attribute::Module(module) => Some(module),
+            _ => None
+        });
+        let packages = find_in(&attributes, |attr| match attr {
+            Attribute::ModulePackages(packages) => Some(packages.as_slice()),
+            _ => None
+        });
+        let main_class = find_in(&attributes, |attr| match attr {
+            Attribute::ModuleMainClass(main_class) => Some(&**main_class),
+            _ => None
+        });
+
+        Ok(module.map(|module| ModuleDescriptor { module, packages: packages.unwrap_or(Cow::Borrowed(&[])), main_class }))
+    }
 
-        self.methods.iter().find(|method| method.name == name && method.descriptor == type_desc)
+    /// the entries of the `BootstrapMethods` attribute, empty if there is none
+    fn bootstrap_methods(&self) -> Result<Cow<'_, [BootstrapMethod]>, ClassFileError> {
+        Ok(find_in(&self.attributes()?, |attr| match attr {
+            Attribute::BootstrapMethods(methods) => Some(methods.as_slice()),
+            _ => None
+        }).unwrap_or(Cow::Borrowed(&[])))
     }
 }
 
-#[derive(Debug)]
-pub struct Field<'a> {
-    access_flags: u16,
-    name_index: u16,
-    descriptor_index: u16,
-    attributes: Vec<Attribute<'a>>,
+impl<'a> ClassQuery for ClassFile<'a> {
+    fn version(&self) -> (u16, u16) {
+        self.version
+    }
+
+    fn constants(&self) -> &ConstantPool<'_> {
+        &self.constants
+    }
+
+    fn access_flags(&self) -> u16 {
+        self.access_flags
+    }
+
+    fn this_index(&self) -> u16 {
+        self.this_index
+    }
+
+    fn super_index(&self) -> u16 {
+        self.super_index
+    }
+
+    fn interfaces(&self) -> &[u16] {
+        &self.interfaces
+    }
+
+    fn fields(&self) -> Result<Cow<'_, [Field<'_>]>, ClassFileError> {
+        Ok(Cow::Borrowed(&self.fields))
+    }
+
+    fn methods(&self) -> Result<Cow<'_, [Method<'_>]>, ClassFileError> {
+        Ok(Cow::Borrowed(&self.methods))
+    }
+
+    fn attributes(&self) -> Result<Cow<'_, [Attribute<'_>]>, ClassFileError> {
+        Ok(Cow::Borrowed(&self.attributes))
+    }
 }
 
+/// a class file of which only the header and the constant pool are decoded, see
+/// `read_class_file_lazy`. the field, method and attribute tables stay bytes until they are
+/// queried, and the bytecode of methods is borrowed from the input.
 #[derive(Debug)]
-pub struct Method<'a> {
+pub struct LazyClassFile<'a> {
+    pub version: (u16, u16),
+    pub constants: ConstantPool<'a>,
     pub access_flags: u16,
-    pub name: &'a str,
-    pub descriptor: &'a str,
+    pub this_index: u16,
+    pub super_index: u16,
+    pub interfaces: Vec<u16>,
+    /// the whole class file, error offsets are relative to it
+    input: &'a [u8],
+    field_table: &'a [u8],
+    method_table: &'a [u8],
+    attribute_table: &'a [u8],
+}
+
+impl<'a> LazyClassFile<'a> {
+    /// decodes the remaining tables
+    pub fn into_class_file(self) -> Result<ClassFile<'a>, ClassFileError> {
+        let tables = (self.field_table, self.method_table, self.attribute_table);
+        let (fields, methods, attributes, references) = parser::read_tables(self.input, tables, &self.constants)?;
+
+        Ok(ClassFile {
+            version: self.version,
+            constants: self.constants,
+            references,
+            access_flags: self.access_flags,
+            this_index: self.this_index,
+            super_index: self.super_index,
+            interfaces: self.interfaces,
+            fields,
+            methods,
+            attributes,
+        })
+    }
+}
+
+impl<'a> ClassQuery for LazyClassFile<'a> {
+    fn version(&self) -> (u16, u16) {
+        self.version
+    }
+
+    fn constants(&self) -> &ConstantPool<'_> {
+        &self.constants
+    }
+
+    fn access_flags(&self) -> u16 {
+        self.access_flags
+    }
+
+    fn this_index(&self) -> u16 {
+        self.this_index
+    }
+
+    fn super_index(&self) -> u16 {
+        self.super_index
+    }
+
+    fn interfaces(&self) -> &[u16] {
+        &self.interfaces
+    }
+
+    fn fields(&self) -> Result<Cow<'_, [Field<'_>]>, ClassFileError> {
+        parser::read_fields(self.input, self.field_table, &self.constants).map(Cow::Owned)
+    }
+
+    fn methods(&self) -> Result<Cow<'_, [Method<'_>]>, ClassFileError> {
+        parser::read_methods(self.input, self.method_table, &self.constants).map(Cow::Owned)
+    }
+
+    fn attributes(&self) -> Result<Cow<'_, [Attribute<'_>]>, ClassFileError> {
+        parser::read_attributes(self.input, self.attribute_table, &self.constants).map(Cow::Owned)
+    }
+
+    /// only decodes the attributes of the method that matches
+    fn find_method(&self, name: &str, descriptor: &str) -> Result<Option<Method<'_>>, ClassFileError> {
+        parser::read_method(self.input, self.method_table, &self.constants, name, descriptor)
+    }
+}
+
+impl<'a> ClassFile<'a> {
+    /// copies everything that is still borrowed from the input buffer, so the class can be
+    /// kept in caches or sent to other threads
+    pub fn into_owned(self) -> ClassFile<'static> {
+        ClassFile {
+            version: self.version,
+            constants: self.constants.into_owned(),
+            references: self.references,
+            access_flags: self.access_flags,
+            this_index: self.this_index,
+            super_index: self.super_index,
+            interfaces: self.interfaces,
+            fields: self.fields.into_iter().map(Field::into_owned).collect(),
+            methods: self.methods.into_iter().map(Method::into_owned).collect(),
+            attributes: owned_attributes(self.attributes),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Field<'a> {
+    pub access_flags: u16,
+    pub name: Cow<'a, str>,
+    pub descriptor: Cow<'a, str>,
+    pub attributes: Vec<Attribute<'a>>,
+}
+
+impl<'a> Field<'a> {
+    pub fn into_owned(self) -> Field<'static> {
+        Field {
+            access_flags: self.access_flags,
+            name: owned(self.name),
+            descriptor: owned(self.descriptor),
+            attributes: owned_attributes(self.attributes),
+        }
+    }
+
+    pub fn get_type(&self) -> Result<ValueType, ClassFileError> {
+        ValueType::from_str(&self.descriptor)
+    }
+
+    /// the initial value of a static field, from its `ConstantValue` attribute
+    pub fn constant_value(&self) -> Option<&ConstantValue<'a>> {
+        self.attributes.iter()
+            .filter_map(|attr| match attr {
+                Attribute::ConstantValue(value) => Some(value),
+                _ => None
+            })
+            .next()
+    }
+
+    /// the visible and invisible type annotations
+    pub fn type_annotations(&self) -> Vec<&TypeAnnotation<'a>> {
+        find_type_annotations(&self.attributes)
+    }
+
+    /// finds a visible or invisible annotation by its type descriptor, e.g. `Lorg/junit/Test;`
+    pub fn annotation(&self, type_name: &str) -> Option<&Annotation<'a>> {
+        find_annotation(&self.attributes, type_name)
+    }
+
+    /// the generic type, if the field has one
+    pub fn generic_signature(&self) -> Result<Option<TypeSignature>, ClassFileError> {
+        match signature_attribute(&self.attributes) {
+            Some(signature) => TypeSignature::from_str(signature).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    pub fn access(&self) -> FieldFlags {
+        FieldFlags::from_bits(self.access_flags)
+    }
+
+    pub fn get_access(&self) -> HashSet<FieldAccess> {
+        self.access().to_set()
+    }
+}
+
+/// a component of a record class, from the `Record` attribute
+#[derive(Debug, Clone)]
+pub struct RecordComponent<'a> {
+    pub name: Cow<'a, str>,
+    pub descriptor: Cow<'a, str>,
     pub attributes: Vec<Attribute<'a>>,
 }
 
-#[derive(Debug, Eq, PartialEq, Hash)]
-pub enum MethodAccess {
-    Public,
-    Private,
-    Protected,
-    Static,
-    Final,
-    Synchronized,
-    Native,
-    Abstract,
-    Strict,
+impl<'a> RecordComponent<'a> {
+    pub fn into_owned(self) -> RecordComponent<'static> {
+        RecordComponent {
+            name: owned(self.name),
+            descriptor: owned(self.descriptor),
+            attributes: owned_attributes(self.attributes),
+        }
+    }
+
+    pub fn get_type(&self) -> Result<ValueType, ClassFileError> {
+        ValueType::from_str(&self.descriptor)
+    }
+
+    /// the generic type, if the component has one
+    pub fn generic_signature(&self) -> Result<Option<TypeSignature>, ClassFileError> {
+        match signature_attribute(&self.attributes) {
+            Some(signature) => TypeSignature::from_str(signature).map(Some),
+            None => Ok(None)
+        }
+    }
+
+    /// finds a visible or invisible annotation by its type descriptor
+    pub fn annotation(&self, type_name: &str) -> Option<&Annotation<'a>> {
+        find_annotation(&self.attributes, type_name)
+    }
+}
+
+/// the value of a `ConstantValue` attribute.
+/// `Integer` is also used for `boolean`, `byte`, `char` and `short` fields.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConstantValue<'a> {
+    Integer(i32),
+    Float(f32),
+    Long(i64),
+    Double(f64),
+    String(Cow<'a, str>),
+}
+
+impl<'a> ConstantValue<'a> {
+    pub fn into_owned(self) -> ConstantValue<'static> {
+        match self {
+            ConstantValue::Integer(value) => ConstantValue::Integer(value),
+            ConstantValue::Float(value) => ConstantValue::Float(value),
+            ConstantValue::Long(value) => ConstantValue::Long(value),
+            ConstantValue::Double(value) => ConstantValue::Double(value),
+            ConstantValue::String(value) => ConstantValue::String(owned(value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Method<'a> {
+    pub access_flags: u16,
+    pub name: Cow<'a, str>,
+    pub descriptor: Cow<'a, str>,
+    pub attributes: Vec<Attribute<'a>>,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+/// a java type as it appears in field and method descriptors
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum ValueType {
     Void,
+    Byte,
+    Char,
+    Double,
+    Float,
     Integer,
+    Long,
+    Short,
+    Boolean,
     Object(String),
     Array(Box<ValueType>),
 }
 
+impl ValueType {
+    /// the number of local variable or operand stack slots a value of this type takes up.
+    /// `Long` and `Double` take two, `Void` none.
+    pub fn slot_size(&self) -> usize {
+        match self {
+            ValueType::Void => 0,
+            ValueType::Long | ValueType::Double => 2,
+            _ => 1
+        }
+    }
+
+    pub fn is_reference(&self) -> bool {
+        matches!(self, ValueType::Object(_) | ValueType::Array(_))
+    }
+
+    /// the descriptor of this type, e.g. `[Ljava/lang/String;`
+    pub fn to_descriptor(&self) -> String {
+        match self {
+            ValueType::Void => String::from("V"),
+            ValueType::Byte => String::from("B"),
+            ValueType::Char => String::from("C"),
+            ValueType::Double => String::from("D"),
+            ValueType::Float => String::from("F"),
+            ValueType::Integer => String::from("I"),
+            ValueType::Long => String::from("J"),
+            ValueType::Short => String::from("S"),
+            ValueType::Boolean => String::from("Z"),
+            ValueType::Object(name) => format!("L{};", name),
+            ValueType::Array(element) => format!("[{}", element.to_descriptor()),
+        }
+    }
+
+    /// this type in java source syntax, e.g. `java.lang.String[]`
+    pub fn to_java(&self) -> String {
+        match self {
+            ValueType::Void => String::from("void"),
+            ValueType::Byte => String::from("byte"),
+            ValueType::Char => String::from("char"),
+            ValueType::Double => String::from("double"),
+            ValueType::Float => String::from("float"),
+            ValueType::Integer => String::from("int"),
+            ValueType::Long => String::from("long"),
+            ValueType::Short => String::from("short"),
+            ValueType::Boolean => String::from("boolean"),
+            ValueType::Object(name) => name.replace('/', "."),
+            ValueType::Array(element) => format!("{}[]", element.to_java()),
+        }
+    }
+}
+
+impl FromStr for ValueType {
+    type Err = ClassFileError;
+
+    /// parses a field descriptor
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        parser::read_field_descriptor(s)
+    }
+}
+
 #[derive(Debug)]
 pub struct MethodDescriptor {
     pub return_type: ValueType,
     pub arguments: Vec<ValueType>,
 }
 
-use nom::IResult;
-use std::slice::Iter;
 use std::collections::HashMap;
 
 impl FromStr for MethodDescriptor {
-    type Err = ();
+    type Err = ClassFileError;
 
     fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
-        match parser::method_desc(s.as_bytes()) {
-            Ok((rem, (args, ret))) => Ok(MethodDescriptor { arguments: args, return_type: ret }),
-            Err(_) => panic!("asdf"),
-        }
+        let (arguments, return_type) = parser::read_method_descriptor(s)?;
+        Ok(MethodDescriptor { arguments, return_type })
     }
 }
 
@@ -147,7 +707,7 @@ impl Instructions {
         let mut curr_offset = 0;
         let mut curr_index = 0;
         for instruction in self.instructions.iter() {
-            let size = instruction.get_size();
+            let size = instruction.get_size(curr_offset);
             if !self.index.contains_key(&curr_offset) {
                 self.index.insert(curr_offset, curr_index);
             }
@@ -188,8 +748,28 @@ impl Iterator for Instructions {
 }
 
 impl<'a> Method<'a> {
-    pub fn instructions(&self) -> Instructions {
-        Instructions::create(self.get_code().unwrap().instructions().unwrap())
+    pub fn into_owned(self) -> Method<'static> {
+        Method {
+            access_flags: self.access_flags,
+            name: owned(self.name),
+            descriptor: owned(self.descriptor),
+            attributes: owned_attributes(self.attributes),
+        }
+    }
+
+    pub fn instructions(&self) -> Result<Instructions, ClassFileError> {
+        let code_error = |kind: String| ClassFileError {
+            offset: 0,
+            structure: vec![Structure::Attribute("Code".to_string())],
+            reason: ErrorReason::Malformed { kind },
+        };
+
+        let code = self.get_code()
+            .ok_or_else(|| code_error(format!("method {} has no code", self.name)))?;
+        let instructions = code.instructions()
+            .map_err(|err| code_error(format!("{:?}", err)))?;
+
+        Ok(Instructions::create(instructions))
     }
 
     pub fn get_code(&self) -> Option<&CodeBlock<'a>> {
@@ -205,57 +785,215 @@ impl<'a> Method<'a> {
             .map(|x| *x)
     }
 
-    pub fn get_signature(&self) -> MethodDescriptor {
-        match MethodDescriptor::from_str(self.descriptor) {
-            Ok(method) => method,
-            Err(err) => panic!("{:?}", err)
-        }
+    /// the absolute type states of the method's `StackMapTable`. `class_name` is the class
+    /// declaring the method, it is needed for the type of `this`.
+    pub fn stack_map_states(&self, class_name: &str) -> Result<Vec<FrameState<'a>>, ClassFileError> {
+        let frames = match self.get_code() {
+            Some(code) => code.stack_map_table(),
+            None => return Ok(Vec::new())
+        };
+
+        stack_map::expand(frames, stack_map::initial_locals(class_name, self)?)
     }
 
-    pub fn get_access(&self) -> HashSet<MethodAccess> {
-        let mut set = HashSet::new();
-        if self.access_flags & 0x0001 == 0x0001 {
-            set.insert(MethodAccess::Public);
-        }
-        if self.access_flags & 0x0002 == 0x0002 {
-            set.insert(MethodAccess::Private);
-        }
-        if self.access_flags & 0x0004 == 0x0004 {
-            set.insert(MethodAccess::Protected);
-        }
-        if self.access_flags & 0x0008 == 0x0008 {
-            set.insert(MethodAccess::Static);
-        }
-        if self.access_flags & 0x0010 == 0x0010 {
-            set.insert(MethodAccess::Final);
-        }
-        if self.access_flags & 0x0020 == 0x0020 {
-            set.insert(MethodAccess::Synchronized);
-        }
-        if self.access_flags & 0x0040 == 0x0040 {
-            set.insert(MethodAccess::Native);
-        }
-        if self.access_flags & 0x0080 == 0x0080 {
-            set.insert(MethodAccess::Strict);
+    pub fn get_signature(&self) -> Result<MethodDescriptor, ClassFileError> {
+        MethodDescriptor::from_str(&self.descriptor)
+    }
+
+    /// the visible and invisible type annotations
+    pub fn type_annotations(&self) -> Vec<&TypeAnnotation<'a>> {
+        find_type_annotations(&self.attributes)
+    }
+
+    /// finds a visible or invisible annotation by its type descriptor, e.g. `Lorg/junit/Test;`
+    pub fn annotation(&self, type_name: &str) -> Option<&Annotation<'a>> {
+        find_annotation(&self.attributes, type_name)
+    }
+
+    /// the annotations of the parameter at `index`, visible and invisible ones
+    pub fn parameter_annotations(&self, index: usize) -> Vec<&Annotation<'a>> {
+        self.attributes.iter()
+            .filter_map(|attr| match attr {
+                Attribute::RuntimeVisibleParameterAnnotations(parameters) |
+                Attribute::RuntimeInvisibleParameterAnnotations(parameters) => parameters.get(index),
+                _ => None
+            })
+            .flat_map(|annotations| annotations.iter())
+            .collect()
+    }
+
+    /// the default value, if the method is an element of an annotation type
+    pub fn annotation_default(&self) -> Option<&ElementValue<'a>> {
+        self.attributes.iter()
+            .filter_map(|attr| match attr {
+                Attribute::AnnotationDefault(value) => Some(value),
+                _ => None
+            })
+            .next()
+    }
+
+    /// the generic signature, if the method has one. `get_signature` returns the erased descriptor.
+    pub fn generic_signature(&self) -> Result<Option<MethodSignature>, ClassFileError> {
+        match signature_attribute(&self.attributes) {
+            Some(signature) => MethodSignature::from_str(signature).map(Some),
+            None => Ok(None)
         }
+    }
+
+    pub fn access(&self) -> MethodFlags {
+        MethodFlags::from_bits(self.access_flags)
+    }
 
-        return set;
+    pub fn get_access(&self) -> HashSet<MethodAccess> {
+        self.access().to_set()
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CodeBlock<'a> {
     pub max_stack: u16,
     pub max_locals: u16,
-    code: Vec<u8>,
+    /// borrowed from the class file buffer
+    code: Cow<'a, [u8]>,
+    exception_handlers: Vec<ExceptionHandler<'a>>,
     attributes: Vec<Attribute<'a>>,
 }
 
+/// an entry of the exception table of a `Code` attribute.
+/// the handler is active for `start_pc <= pc < end_pc`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ExceptionHandler<'a> {
+    pub start_pc: u16,
+    pub end_pc: u16,
+    pub handler_pc: u16,
+    /// the class name of the caught exception, `None` catches everything (used for `finally`)
+    pub catch_type: Option<Cow<'a, str>>,
+}
+
+impl<'a> ExceptionHandler<'a> {
+    pub fn into_owned(self) -> ExceptionHandler<'static> {
+        ExceptionHandler {
+            start_pc: self.start_pc,
+            end_pc: self.end_pc,
+            handler_pc: self.handler_pc,
+            catch_type: self.catch_type.map(owned),
+        }
+    }
+
+    pub fn covers(&self, pc: u16) -> bool {
+        self.start_pc <= pc && pc < self.end_pc
+    }
+}
+
+/// an entry of the `LocalVariableTable` or `LocalVariableTypeTable` attribute.
+/// the variable in slot `index` has a value for `start_pc <= pc < start_pc + length`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LocalVariableInfo<'a> {
+    pub start_pc: u16,
+    pub length: u16,
+    pub name: Cow<'a, str>,
+    /// the field descriptor, or the generic signature for entries of the `LocalVariableTypeTable`
+    pub descriptor: Cow<'a, str>,
+    pub index: u16,
+}
+
+impl<'a> LocalVariableInfo<'a> {
+    pub fn into_owned(self) -> LocalVariableInfo<'static> {
+        LocalVariableInfo {
+            start_pc: self.start_pc,
+            length: self.length,
+            name: owned(self.name),
+            descriptor: owned(self.descriptor),
+            index: self.index,
+        }
+    }
+
+    pub fn covers(&self, pc: u16) -> bool {
+        self.start_pc <= pc && u32::from(pc) < u32::from(self.start_pc) + u32::from(self.length)
+    }
+}
+
 impl<'a> CodeBlock<'a> {
+    pub fn into_owned(self) -> CodeBlock<'static> {
+        CodeBlock {
+            max_stack: self.max_stack,
+            max_locals: self.max_locals,
+            code: owned(self.code),
+            exception_handlers: self.exception_handlers.into_iter().map(ExceptionHandler::into_owned).collect(),
+            attributes: owned_attributes(self.attributes),
+        }
+    }
+
     pub fn instructions(&self) -> Result<Vec<Instruction>, ReadInstructionError<&[u8]>> {
         Instruction::read_all(&self.code[..])
     }
 
+    /// the raw bytecode
+    pub fn code(&self) -> &[u8] {
+        &self.code
+    }
+
+    /// the exception table, in the order the handlers have to be tried
+    pub fn exception_handlers(&self) -> &[ExceptionHandler<'a>] {
+        &self.exception_handlers
+    }
+
+    /// the frames of the `StackMapTable` attribute, empty if there is none
+    pub fn stack_map_table(&self) -> &[StackMapFrame<'a>] {
+        for attribute in self.attributes.iter() {
+            if let Attribute::StackMapTable(frames) = attribute {
+                return frames;
+            }
+        }
+
+        &[]
+    }
+
+    /// type annotations inside the code, e.g. on local variables, casts or `new`
+    pub fn type_annotations(&self) -> Vec<&TypeAnnotation<'a>> {
+        find_type_annotations(&self.attributes)
+    }
+
+    /// all entries of the `LocalVariableTable`, empty if there is none
+    pub fn local_variables(&self) -> &[LocalVariableInfo<'a>] {
+        for attribute in self.attributes.iter() {
+            if let Attribute::LocalVariableTable(variables) = attribute {
+                return variables;
+            }
+        }
+
+        &[]
+    }
+
+    /// all entries of the `LocalVariableTypeTable`, empty if there is none
+    pub fn local_variable_types(&self) -> &[LocalVariableInfo<'a>] {
+        for attribute in self.attributes.iter() {
+            if let Attribute::LocalVariableTypeTable(variables) = attribute {
+                return variables;
+            }
+        }
+
+        &[]
+    }
+
+    /// the entry of the `LocalVariableTable` for the variable in `slot` at `pc`
+    pub fn local_variable(&self, slot: u16, pc: u16) -> Option<&LocalVariableInfo<'a>> {
+        self.local_variables().iter().find(|variable| variable.index == slot && variable.covers(pc))
+    }
+
+    /// the generic signature of the variable in `slot` at `pc`, from the `LocalVariableTypeTable`.
+    /// only variables with a generic type have one.
+    pub fn local_variable_signature(&self, slot: u16, pc: u16) -> Option<&str> {
+        self.local_variable_types().iter()
+            .find(|variable| variable.index == slot && variable.covers(pc))
+            .map(|variable| &*variable.descriptor)
+    }
+
+    /// all handlers that are active at `pc`, in the order they have to be tried
+    pub fn handlers_at<'s>(&'s self, pc: u16) -> impl Iterator<Item=&'s ExceptionHandler<'a>> + 's {
+        self.exception_handlers.iter().filter(move |handler| handler.covers(pc))
+    }
+
     ///  Vec<usize>  pc -> ln
     pub fn get_line_numbers(&self) -> Vec<usize> {
         let line_number_attr = self.attributes.iter().find(|x| match x {
@@ -291,19 +1029,218 @@ impl<'a> CodeBlock<'a> {
     }
 }
 
-#[derive(Debug)]
+/// an entry of the `BootstrapMethods` attribute, referenced by `InvokeDynamic` constants
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct BootstrapMethod {
+    /// index of a `MethodHandle` constant
+    pub method_ref: u16,
+    /// indexes of the static arguments, which are loadable constants
+    pub arguments: Vec<u16>,
+}
+
+#[derive(Debug, Clone)]
 pub enum Attribute<'a> {
     LineNumberTable(Vec<(u16, u16)>),
+    StackMapTable(Vec<StackMapFrame<'a>>),
+    ConstantValue(ConstantValue<'a>),
+    LocalVariableTable(Vec<LocalVariableInfo<'a>>),
+    LocalVariableTypeTable(Vec<LocalVariableInfo<'a>>),
     CodeAttribute(CodeBlock<'a>),
+    BootstrapMethods(Vec<BootstrapMethod>),
+    InnerClasses(Vec<InnerClass<'a>>),
+    EnclosingMethod(EnclosingMethod<'a>),
+    /// the class hosting the nest this class belongs to
+    NestHost(Cow<'a, str>),
+    /// the other classes of the nest, only on its host
+    NestMembers(Vec<Cow<'a, str>>),
+    SourceFile(Cow<'a, str>),
+    /// kept as bytes, the JVM does not interpret them and not every tool writes modified UTF-8
+    SourceDebugExtension(Cow<'a, [u8]>),
+    Record(Vec<RecordComponent<'a>>),
+    /// the classes allowed to extend a sealed class or implement a sealed interface
+    PermittedSubclasses(Vec<Cow<'a, str>>),
+    Module(Module<'a>),
+    /// the internal names of all packages of a module
+    ModulePackages(Vec<Cow<'a, str>>),
+    ModuleMainClass(Cow<'a, str>),
+    /// the generic signature of a class, field or method, see `signature`
+    Signature(Cow<'a, str>),
+    RuntimeVisibleAnnotations(Vec<Annotation<'a>>),
+    RuntimeInvisibleAnnotations(Vec<Annotation<'a>>),
+    /// one list of annotations per parameter
+    RuntimeVisibleParameterAnnotations(Vec<Vec<Annotation<'a>>>),
+    RuntimeInvisibleParameterAnnotations(Vec<Vec<Annotation<'a>>>),
+    RuntimeVisibleTypeAnnotations(Vec<TypeAnnotation<'a>>),
+    RuntimeInvisibleTypeAnnotations(Vec<TypeAnnotation<'a>>),
+    /// the default value of an annotation type element
+    AnnotationDefault(ElementValue<'a>),
     GenericAttribute {
         name: String,
-        info: &'a [u8],
+        info: Cow<'a, [u8]>,
     },
 }
 
+impl<'a> Attribute<'a> {
+    /// the name the attribute is stored under in the class file
+    pub fn name(&self) -> &str {
+        match self {
+            Attribute::LineNumberTable(_) => "LineNumberTable",
+            Attribute::StackMapTable(_) => "StackMapTable",
+            Attribute::ConstantValue(_) => "ConstantValue",
+            Attribute::LocalVariableTable(_) => "LocalVariableTable",
+            Attribute::LocalVariableTypeTable(_) => "LocalVariableTypeTable",
+            Attribute::CodeAttribute(_) => "Code",
+            Attribute::BootstrapMethods(_) => "BootstrapMethods",
+            Attribute::InnerClasses(_) => "InnerClasses",
+            Attribute::EnclosingMethod(_) => "EnclosingMethod",
+            Attribute::NestHost(_) => "NestHost",
+            Attribute::NestMembers(_) => "NestMembers",
+            Attribute::SourceFile(_) => "SourceFile",
+            Attribute::SourceDebugExtension(_) => "SourceDebugExtension",
+            Attribute::Record(_) => "Record",
+            Attribute::PermittedSubclasses(_) => "PermittedSubclasses",
+            Attribute::Module(_) => "Module",
+            Attribute::ModulePackages(_) => "ModulePackages",
+            Attribute::ModuleMainClass(_) => "ModuleMainClass",
+            Attribute::Signature(_) => "Signature",
+            Attribute::RuntimeVisibleAnnotations(_) => "RuntimeVisibleAnnotations",
+            Attribute::RuntimeInvisibleAnnotations(_) => "RuntimeInvisibleAnnotations",
+            Attribute::RuntimeVisibleParameterAnnotations(_) => "RuntimeVisibleParameterAnnotations",
+            Attribute::RuntimeInvisibleParameterAnnotations(_) => "RuntimeInvisibleParameterAnnotations",
+            Attribute::RuntimeVisibleTypeAnnotations(_) => "RuntimeVisibleTypeAnnotations",
+            Attribute::RuntimeInvisibleTypeAnnotations(_) => "RuntimeInvisibleTypeAnnotations",
+            Attribute::AnnotationDefault(_) => "AnnotationDefault",
+            Attribute::GenericAttribute { name, .. } => name,
+        }
+    }
+
+    pub fn into_owned(self) -> Attribute<'static> {
+        match self {
+            Attribute::LineNumberTable(lines) => Attribute::LineNumberTable(lines),
+            Attribute::StackMapTable(frames) => Attribute::StackMapTable(frames.into_iter().map(StackMapFrame::into_owned).collect()),
+            Attribute::ConstantValue(value) => Attribute::ConstantValue(value.into_owned()),
+            Attribute::LocalVariableTable(variables) =>
+                Attribute::LocalVariableTable(variables.into_iter().map(LocalVariableInfo::into_owned).collect()),
+            Attribute::LocalVariableTypeTable(variables) =>
+                Attribute::LocalVariableTypeTable(variables.into_iter().map(LocalVariableInfo::into_owned).collect()),
+            Attribute::CodeAttribute(code) => Attribute::CodeAttribute(code.into_owned()),
+            Attribute::BootstrapMethods(methods) => Attribute::BootstrapMethods(methods),
+            Attribute::InnerClasses(classes) => Attribute::InnerClasses(classes.into_iter().map(InnerClass::into_owned).collect()),
+            Attribute::EnclosingMethod(enclosing) => Attribute::EnclosingMethod(enclosing.into_owned()),
+            Attribute::NestHost(name) => Attribute::NestHost(owned(name)),
+            Attribute::NestMembers(names) => Attribute::NestMembers(owned_all(names)),
+            Attribute::SourceFile(name) => Attribute::SourceFile(owned(name)),
+            Attribute::SourceDebugExtension(value) => Attribute::SourceDebugExtension(owned(value)),
+            Attribute::Record(components) => Attribute::Record(components.into_iter().map(RecordComponent::into_owned).collect()),
+            Attribute::PermittedSubclasses(names) => Attribute::PermittedSubclasses(owned_all(names)),
+            Attribute::Module(module) => Attribute::Module(module.into_owned()),
+            Attribute::ModulePackages(names) => Attribute::ModulePackages(owned_all(names)),
+            Attribute::ModuleMainClass(name) => Attribute::ModuleMainClass(owned(name)),
+            Attribute::Signature(signature) => Attribute::Signature(owned(signature)),
+            Attribute::RuntimeVisibleAnnotations(annotations) => Attribute::RuntimeVisibleAnnotations(owned_annotations(annotations)),
+            Attribute::RuntimeInvisibleAnnotations(annotations) => Attribute::RuntimeInvisibleAnnotations(owned_annotations(annotations)),
+            Attribute::RuntimeVisibleParameterAnnotations(parameters) =>
+                Attribute::RuntimeVisibleParameterAnnotations(parameters.into_iter().map(owned_annotations).collect()),
+            Attribute::RuntimeInvisibleParameterAnnotations(parameters) =>
+                Attribute::RuntimeInvisibleParameterAnnotations(parameters.into_iter().map(owned_annotations).collect()),
+            Attribute::RuntimeVisibleTypeAnnotations(annotations) =>
+                Attribute::RuntimeVisibleTypeAnnotations(annotations.into_iter().map(TypeAnnotation::into_owned).collect()),
+            Attribute::RuntimeInvisibleTypeAnnotations(annotations) =>
+                Attribute::RuntimeInvisibleTypeAnnotations(annotations.into_iter().map(TypeAnnotation::into_owned).collect()),
+            Attribute::AnnotationDefault(value) => Attribute::AnnotationDefault(value.into_owned()),
+            Attribute::GenericAttribute { name, info } => Attribute::GenericAttribute { name, info: owned(info) },
+        }
+    }
+}
+
+/// copies a value borrowed from the input buffer
+fn owned<T: ?Sized + ToOwned + 'static>(value: Cow<T>) -> Cow<'static, T> {
+    Cow::Owned(value.into_owned())
+}
+
+fn owned_all(values: Vec<Cow<str>>) -> Vec<Cow<'static, str>> {
+    values.into_iter().map(owned).collect()
+}
+
+fn owned_attributes(attributes: Vec<Attribute>) -> Vec<Attribute<'static>> {
+    attributes.into_iter().map(Attribute::into_owned).collect()
+}
+
+fn owned_annotations(annotations: Vec<Annotation>) -> Vec<Annotation<'static>> {
+    annotations.into_iter().map(Annotation::into_owned).collect()
+}
+
+/// the first value `find` picks from the attributes. decoded attributes are copied out,
+/// borrowed ones stay borrowed.
+fn find_in<'s, T, F>(attributes: &Cow<'s, [Attribute<'s>]>, find: F) -> Option<Cow<'s, T>>
+    where T: ?Sized + ToOwned + 's, F: for<'b> Fn(&'b Attribute<'s>) -> Option<&'b T>
+{
+    match attributes {
+        Cow::Borrowed(attributes) => attributes.iter().filter_map(find).next().map(Cow::Borrowed),
+        Cow::Owned(attributes) => attributes.iter().filter_map(find).next().map(|value| Cow::Owned(value.to_owned())),
+    }
+}
+
+/// a class query failed because `this_class` is not a valid `Class` constant
+fn header_error(err: ConstantPoolError) -> ClassFileError {
+    ClassFileError { offset: 0, structure: vec![Structure::Header], reason: ErrorReason::InvalidConstant(err) }
+}
+
+/// a class query failed because the constant at `index` does not resolve
+fn constant_error(index: u16, err: ConstantPoolError) -> ClassFileError {
+    ClassFileError { offset: 0, structure: vec![Structure::Constant(index)], reason: ErrorReason::InvalidConstant(err) }
+}
+
+/// resolves the `InvokeDynamic` or `Dynamic` constant at `index` with the bootstrap method it names
+fn bootstrap_call<Q: ClassQuery + ?Sized>(class: &Q, index: u16, bootstrap_index: u16, name_and_type_index: u16) -> Result<InvokeDynamic<'_, '_>, ClassFileError> {
+    let invalid = |err| constant_error(index, err);
+    let bootstrap = class.bootstrap_methods()?.get(usize::from(bootstrap_index)).cloned()
+        .ok_or_else(|| invalid(ConstantPoolError::MissingBootstrapMethod { index }))?;
+    let constants = class.constants();
+    let arguments = bootstrap.arguments.iter()
+        .map(|argument| constants.get(*argument))
+        .collect::<Result<Vec<&ConstantType>, ConstantPoolError>>()
+        .map_err(invalid)?;
+    let (name, descriptor) = constants.name_and_type(name_and_type_index).map_err(invalid)?;
+
+    Ok(InvokeDynamic { bootstrap: constants.method_handle(bootstrap.method_ref).map_err(invalid)?, arguments, name, descriptor })
+}
+
+fn find_annotation<'a, 'b>(attributes: &'b [Attribute<'a>], type_name: &str) -> Option<&'b Annotation<'a>> {
+    attributes.iter()
+        .filter_map(|attr| match attr {
+            Attribute::RuntimeVisibleAnnotations(annotations) |
+            Attribute::RuntimeInvisibleAnnotations(annotations) => Some(annotations),
+            _ => None
+        })
+        .flat_map(|annotations| annotations.iter())
+        .find(|annotation| annotation.type_name == type_name)
+}
+
+fn find_type_annotations<'a, 'b>(attributes: &'b [Attribute<'a>]) -> Vec<&'b TypeAnnotation<'a>> {
+    attributes.iter()
+        .filter_map(|attr| match attr {
+            Attribute::RuntimeVisibleTypeAnnotations(annotations) |
+            Attribute::RuntimeInvisibleTypeAnnotations(annotations) => Some(annotations),
+            _ => None
+        })
+        .flat_map(|annotations| annotations.iter())
+        .collect()
+}
+
+fn signature_attribute<'b>(attributes: &'b [Attribute]) -> Option<&'b str> {
+    attributes.iter()
+        .filter_map(|attr| match attr {
+            Attribute::Signature(signature) => Some(&**signature),
+            _ => None
+        })
+        .next()
+}
+
 #[derive(Debug)]
 pub enum ConstantType<'a> {
-    Utf8 { value: &'a str },
+    /// `value` is the decoded string, `bytes` the original modified utf-8 encoding
+    Utf8 { value: Cow<'a, str>, bytes: Cow<'a, [u8]> },
     Integer { value: i32 },
     Float { value: f32 },
     Long { value: i64 },
@@ -316,7 +1253,63 @@ pub enum ConstantType<'a> {
     NameAndType { name_index: u16, descriptor_index: u16 },
     MethodHandle { reference_kind: u8, reference_index: u16 },
     MethodType { descriptor_index: u16 },
+    /// a constant computed by a bootstrap method, `name_and_type_index` gives its field type
+    Dynamic { bootstrap_method_attr_index: u16, name_and_type_index: u16 },
     InvokeDynamic { bootstrap_method_attr_index: u16, name_and_type_index: u16 },
     Module { name_index: u16 },
     Package { name_index: u16 },
 }
+
+impl<'a> ConstantType<'a> {
+    /// `Long` and `Double` constants take up two slots in the constant pool
+    pub fn is_wide(&self) -> bool {
+        matches!(self, ConstantType::Long { .. } | ConstantType::Double { .. })
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            ConstantType::Utf8 { .. } => "Utf8",
+            ConstantType::Integer { .. } => "Integer",
+            ConstantType::Float { .. } => "Float",
+            ConstantType::Long { .. } => "Long",
+            ConstantType::Double { .. } => "Double",
+            ConstantType::Class { .. } => "Class",
+            ConstantType::String { .. } => "String",
+            ConstantType::FieldRef { .. } => "FieldRef",
+            ConstantType::MethodRef { .. } => "MethodRef",
+            ConstantType::InterfaceMethodRef { .. } => "InterfaceMethodRef",
+            ConstantType::NameAndType { .. } => "NameAndType",
+            ConstantType::MethodHandle { .. } => "MethodHandle",
+            ConstantType::MethodType { .. } => "MethodType",
+            ConstantType::Dynamic { .. } => "Dynamic",
+            ConstantType::InvokeDynamic { .. } => "InvokeDynamic",
+            ConstantType::Module { .. } => "Module",
+            ConstantType::Package { .. } => "Package",
+        }
+    }
+
+    pub fn into_owned(self) -> ConstantType<'static> {
+        match self {
+            ConstantType::Utf8 { value, bytes } => ConstantType::Utf8 { value: owned(value), bytes: owned(bytes) },
+            ConstantType::Integer { value } => ConstantType::Integer { value },
+            ConstantType::Float { value } => ConstantType::Float { value },
+            ConstantType::Long { value } => ConstantType::Long { value },
+            ConstantType::Double { value } => ConstantType::Double { value },
+            ConstantType::Class { name_index } => ConstantType::Class { name_index },
+            ConstantType::String { string_index } => ConstantType::String { string_index },
+            ConstantType::FieldRef { class_index, name_and_type_index } => ConstantType::FieldRef { class_index, name_and_type_index },
+            ConstantType::MethodRef { class_index, name_and_type_index } => ConstantType::MethodRef { class_index, name_and_type_index },
+            ConstantType::InterfaceMethodRef { class_index, name_and_type_index } =>
+                ConstantType::InterfaceMethodRef { class_index, name_and_type_index },
+            ConstantType::NameAndType { name_index, descriptor_index } => ConstantType::NameAndType { name_index, descriptor_index },
+            ConstantType::MethodHandle { reference_kind, reference_index } => ConstantType::MethodHandle { reference_kind, reference_index },
+            ConstantType::MethodType { descriptor_index } => ConstantType::MethodType { descriptor_index },
+            ConstantType::Dynamic { bootstrap_method_attr_index, name_and_type_index } =>
+                ConstantType::Dynamic { bootstrap_method_attr_index, name_and_type_index },
+            ConstantType::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } =>
+                ConstantType::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index },
+            ConstantType::Module { name_index } => ConstantType::Module { name_index },
+            ConstantType::Package { name_index } => ConstantType::Package { name_index },
+        }
+    }
+}
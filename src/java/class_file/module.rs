@@ -0,0 +1,193 @@
+//! the `Module`, `ModulePackages` and `ModuleMainClass` attributes of `module-info.class`
+//! (JVMS 4.7.25 - 4.7.27).
+//!
+//! module names are stored with dots (`java.base`), package and class names in their internal
+//! form (`java/lang`, `java/lang/Object`).
+
+use java::class_file::{owned, owned_all, ExportsFlags, ModuleFlags, RequiresFlags};
+use std::borrow::Cow;
+use std::fmt;
+
+/// the `Module` attribute
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Module<'a> {
+    pub name: Cow<'a, str>,
+    pub flags: ModuleFlags,
+    pub version: Option<Cow<'a, str>>,
+    pub requires: Vec<Requires<'a>>,
+    pub exports: Vec<Exports<'a>>,
+    /// same layout as `exports`, `to` lists the modules the package is opened to
+    pub opens: Vec<Exports<'a>>,
+    /// the service interfaces the module uses
+    pub uses: Vec<Cow<'a, str>>,
+    pub provides: Vec<Provides<'a>>,
+}
+
+impl<'a> Module<'a> {
+    pub fn into_owned(self) -> Module<'static> {
+        Module {
+            name: owned(self.name),
+            flags: self.flags,
+            version: self.version.map(owned),
+            requires: self.requires.into_iter().map(Requires::into_owned).collect(),
+            exports: self.exports.into_iter().map(Exports::into_owned).collect(),
+            opens: self.opens.into_iter().map(Exports::into_owned).collect(),
+            uses: owned_all(self.uses),
+            provides: self.provides.into_iter().map(Provides::into_owned).collect(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Requires<'a> {
+    pub module: Cow<'a, str>,
+    pub flags: RequiresFlags,
+    /// the version of the module at compile time, if it was recorded
+    pub version: Option<Cow<'a, str>>,
+}
+
+impl<'a> Requires<'a> {
+    pub fn into_owned(self) -> Requires<'static> {
+        Requires { module: owned(self.module), flags: self.flags, version: self.version.map(owned) }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Exports<'a> {
+    pub package: Cow<'a, str>,
+    pub flags: ExportsFlags,
+    /// empty if the package is exported (or opened) to all modules
+    pub to: Vec<Cow<'a, str>>,
+}
+
+impl<'a> Exports<'a> {
+    pub fn into_owned(self) -> Exports<'static> {
+        Exports { package: owned(self.package), flags: self.flags, to: owned_all(self.to) }
+    }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Provides<'a> {
+    pub service: Cow<'a, str>,
+    pub with: Vec<Cow<'a, str>>,
+}
+
+impl<'a> Provides<'a> {
+    pub fn into_owned(self) -> Provides<'static> {
+        Provides { service: owned(self.service), with: owned_all(self.with) }
+    }
+}
+
+/// everything `module-info.class` says about a module
+#[derive(Debug)]
+pub struct ModuleDescriptor<'c, 'a: 'c> {
+    pub module: Cow<'c, Module<'a>>,
+    /// all packages of the module, from `ModulePackages`. empty if the attribute is missing.
+    pub packages: Cow<'c, [Cow<'a, str>]>,
+    pub main_class: Option<Cow<'c, str>>,
+}
+
+impl<'c, 'a> ModuleDescriptor<'c, 'a> {
+    /// the names of all modules this module depends on
+    pub fn dependencies(&self) -> Vec<&str> {
+        self.module.requires.iter().map(|requires| &*requires.module).collect()
+    }
+}
+
+fn write_names(f: &mut fmt::Formatter, names: &[Cow<str>]) -> fmt::Result {
+    let names: Vec<String> = names.iter().map(|name| name.replace('/', ".")).collect();
+    write!(f, "{}", names.join(", "))
+}
+
+/// renders the descriptor as `module-info.java` source
+impl<'c, 'a> fmt::Display for ModuleDescriptor<'c, 'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let module = &*self.module;
+        for keyword in module.flags.keywords() {
+            write!(f, "{} ", keyword)?;
+        }
+        write!(f, "module {}", module.name)?;
+        if let Some(ref version) = module.version {
+            write!(f, "@{}", version)?;
+        }
+        writeln!(f, " {{")?;
+
+        for requires in &module.requires {
+            write!(f, "    requires ")?;
+            for keyword in requires.flags.keywords() {
+                write!(f, "{} ", keyword)?;
+            }
+            writeln!(f, "{};", requires.module)?;
+        }
+        for (keyword, packages) in [("exports", &module.exports), ("opens", &module.opens)] {
+            for exports in packages {
+                write!(f, "    {} {}", keyword, exports.package.replace('/', "."))?;
+                if !exports.to.is_empty() {
+                    write!(f, " to ")?;
+                    write_names(f, &exports.to)?;
+                }
+                writeln!(f, ";")?;
+            }
+        }
+        for service in &module.uses {
+            writeln!(f, "    uses {};", service.replace('/', "."))?;
+        }
+        for provides in &module.provides {
+            write!(f, "    provides {} with ", provides.service.replace('/', "."))?;
+            write_names(f, &provides.with)?;
+            writeln!(f, ";")?;
+        }
+
+        write!(f, "}}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use java::class_file::{read_class_file, read_class_file_lazy, ClassQuery};
+    use java::samples::MODULE_INFO;
+
+    #[test]
+    fn it_reads_the_module_descriptor() {
+        let cf = read_class_file(MODULE_INFO).unwrap();
+        let descriptor = cf.module_descriptor().unwrap().unwrap();
+        let module = &descriptor.module;
+
+        assert_eq!("com.example.app", module.name);
+        assert_eq!(ModuleFlags::OPEN, module.flags);
+        assert_eq!(Some(Cow::Borrowed("1.0")), module.version);
+        assert_eq!(vec!["java.base", "java.logging", "java.sql"], descriptor.dependencies());
+        assert_eq!(RequiresFlags::MANDATED, module.requires[0].flags);
+        assert_eq!(RequiresFlags::STATIC_PHASE, module.requires[2].flags);
+        assert_eq!(
+            Exports { package: Cow::Borrowed("com/example/app"), flags: ExportsFlags::empty(), to: vec![Cow::Borrowed("java.base"), Cow::Borrowed("java.logging")] },
+            module.exports[1]
+        );
+        assert!(module.opens.is_empty());
+        assert_eq!(vec!["com/example/spi/Plugin"], module.uses);
+        assert_eq!(vec![Cow::Borrowed("com/example/app/Main")], module.provides[0].with);
+        assert_eq!(vec!["com/example/app", "com/example/spi"], *descriptor.packages);
+        assert_eq!(Some("com/example/app/Main"), descriptor.main_class.as_deref());
+    }
+
+    #[test]
+    fn it_renders_module_info_source() {
+        let cf = read_class_file(MODULE_INFO).unwrap();
+        assert_eq!(
+            "open module com.example.app@1.0 {\n\
+             \x20   requires java.base;\n\
+             \x20   requires transitive java.logging;\n\
+             \x20   requires static java.sql;\n\
+             \x20   exports com.example.spi;\n\
+             \x20   exports com.example.app to java.base, java.logging;\n\
+             \x20   uses com.example.spi.Plugin;\n\
+             \x20   provides com.example.spi.Plugin with com.example.app.Main;\n\
+             }",
+            cf.module_descriptor().unwrap().unwrap().to_string()
+        );
+
+        let lazy = read_class_file_lazy(MODULE_INFO).unwrap();
+        assert_eq!(cf.module_descriptor().unwrap().unwrap().to_string(), lazy.module_descriptor().unwrap().unwrap().to_string());
+    }
+}
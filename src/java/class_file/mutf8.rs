@@ -0,0 +1,153 @@
+//! decoder and encoder for the "modified UTF-8" encoding used by `CONSTANT_Utf8` entries (JVMS 4.4.7).
+//!
+//! it differs from standard UTF-8 in two ways:
+//!  - `\0` is encoded as the two bytes `0xC0 0x80`, so a raw `0x00` byte never appears
+//!  - supplementary characters are encoded as a surrogate pair, each half as a 3-byte sequence
+//!    (like CESU-8). 4-byte sequences never appear.
+
+use std::borrow::Cow;
+use std::char;
+use std::str::from_utf8;
+
+#[derive(Debug, Fail, Eq, PartialEq)]
+pub enum ModifiedUtf8Error {
+    #[fail(display = "invalid byte 0x{:02x} at offset {} in modified utf-8 string", byte, offset)]
+    InvalidByte { byte: u8, offset: usize },
+    #[fail(display = "modified utf-8 string ends in the middle of a sequence at offset {}", offset)]
+    Truncated { offset: usize },
+}
+
+/// decodes modified utf-8 bytes into a rust string.
+///
+/// most strings are plain ASCII or also valid standard UTF-8, those are borrowed without copying.
+/// unpaired surrogates cannot be represented in a rust string and are replaced by U+FFFD, the
+/// original bytes are still around in the constant pool if they are needed.
+pub fn decode(bytes: &[u8]) -> Result<Cow<'_, str>, ModifiedUtf8Error> {
+    if !bytes.iter().any(|&b| b == 0 || b >= 0xf0) {
+        // standard UTF-8 rejects overlong nulls and encoded surrogates, so anything that
+        // passes here means the same thing in both encodings
+        if let Ok(value) = from_utf8(bytes) {
+            return Ok(Cow::Borrowed(value));
+        }
+    }
+
+    decode_slow(bytes).map(Cow::Owned)
+}
+
+/// encodes a rust string as modified utf-8, the inverse of `decode`.
+///
+/// strings without `\0` and supplementary characters are the same in both encodings and are
+/// borrowed.
+pub fn encode(value: &str) -> Cow<'_, [u8]> {
+    if !value.chars().any(|c| c == '\0' || c > '\u{ffff}') {
+        return Cow::Borrowed(value.as_bytes());
+    }
+
+    let mut bytes = Vec::with_capacity(value.len() + 2);
+    let mut units = [0u16; 2];
+    for c in value.chars() {
+        if c == '\0' {
+            bytes.extend_from_slice(&[0xc0, 0x80]);
+            continue;
+        }
+        if c <= '\u{ffff}' {
+            let mut buffer = [0u8; 4];
+            bytes.extend_from_slice(c.encode_utf8(&mut buffer).as_bytes());
+            continue;
+        }
+
+        for unit in c.encode_utf16(&mut units).iter() {
+            bytes.extend_from_slice(&[
+                0xe0 | (unit >> 12) as u8,
+                0x80 | ((unit >> 6) & 0x3f) as u8,
+                0x80 | (unit & 0x3f) as u8,
+            ]);
+        }
+    }
+
+    Cow::Owned(bytes)
+}
+
+fn decode_slow(bytes: &[u8]) -> Result<String, ModifiedUtf8Error> {
+    let mut value = String::with_capacity(bytes.len());
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let (unit, size) = read_unit(bytes, offset)?;
+        offset += size;
+
+        if (0xd800..=0xdbff).contains(&unit) && offset < bytes.len() {
+            if let Ok((low, low_size)) = read_unit(bytes, offset) {
+                if (0xdc00..=0xdfff).contains(&low) {
+                    let code = 0x10000 + ((unit - 0xd800) << 10) + (low - 0xdc00);
+                    value.push(char::from_u32(code).unwrap_or(char::REPLACEMENT_CHARACTER));
+                    offset += low_size;
+                    continue;
+                }
+            }
+        }
+
+        value.push(char::from_u32(unit).unwrap_or(char::REPLACEMENT_CHARACTER));
+    }
+
+    Ok(value)
+}
+
+/// reads a single UTF-16 code unit starting at `offset`, returns the unit and the number of bytes used
+fn read_unit(bytes: &[u8], offset: usize) -> Result<(u32, usize), ModifiedUtf8Error> {
+    let first = bytes[offset];
+    let continuation = |index: usize| -> Result<u32, ModifiedUtf8Error> {
+        match bytes.get(offset + index) {
+            Some(&b) if b & 0xc0 == 0x80 => Ok(u32::from(b & 0x3f)),
+            Some(&b) => Err(ModifiedUtf8Error::InvalidByte { byte: b, offset: offset + index }),
+            None => Err(ModifiedUtf8Error::Truncated { offset })
+        }
+    };
+
+    match first {
+        0x01..=0x7f => Ok((u32::from(first), 1)),
+        0xc0..=0xdf => Ok(((u32::from(first & 0x1f) << 6) | continuation(1)?, 2)),
+        0xe0..=0xef => Ok(((u32::from(first & 0x0f) << 12) | (continuation(1)? << 6) | continuation(2)?, 3)),
+        _ => Err(ModifiedUtf8Error::InvalidByte { byte: first, offset })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn it_borrows_plain_strings() {
+        match decode(b"java/lang/Object") {
+            Ok(Cow::Borrowed("java/lang/Object")) => (),
+            other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn it_decodes_nulls_and_surrogate_pairs() {
+        assert_eq!("a\0b", decode(&[0x61, 0xc0, 0x80, 0x62]).unwrap());
+        // U+1F600 as a surrogate pair: D83D DE00
+        assert_eq!("\u{1F600}", decode(&[0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80]).unwrap());
+        assert_eq!("\u{FFFD}x", decode(&[0xed, 0xa0, 0xbd, 0x78]).unwrap());
+        assert_eq!("x\u{FFFD}", decode(&[0x78, 0xed, 0xa0, 0xbd]).unwrap());
+    }
+
+    #[test]
+    fn it_encodes_nulls_and_supplementary_characters() {
+        match encode("java/lang/Object") {
+            Cow::Borrowed(b"java/lang/Object") => (),
+            other => panic!("unexpected {:?}", other)
+        }
+        assert_eq!(vec![0x61, 0xc0, 0x80, 0x62], encode("a\0b").into_owned());
+        assert_eq!(vec![0xed, 0xa0, 0xbd, 0xed, 0xb8, 0x80], encode("\u{1F600}").into_owned());
+        assert_eq!("x\0\u{1F600}\u{e9}", decode(&encode("x\0\u{1F600}\u{e9}")).unwrap());
+    }
+
+    #[test]
+    fn it_rejects_malformed_sequences() {
+        assert_eq!(Err(ModifiedUtf8Error::InvalidByte { byte: 0, offset: 1 }), decode(&[0x61, 0x00]));
+        assert_eq!(Err(ModifiedUtf8Error::Truncated { offset: 0 }), decode(&[0xe2, 0x82]));
+        assert_eq!(Err(ModifiedUtf8Error::InvalidByte { byte: 0x41, offset: 1 }), decode(&[0xc3, 0x41]));
+        assert_eq!(Err(ModifiedUtf8Error::InvalidByte { byte: 0xf0, offset: 0 }), decode(&[0xf0, 0x9f, 0x98, 0x80]));
+    }
+}
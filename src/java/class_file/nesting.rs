@@ -0,0 +1,116 @@
+//! how a class is nested in others: the `InnerClasses`, `EnclosingMethod`, `NestHost` and
+//! `NestMembers` attributes (JVMS 4.7.6, 4.7.7, 4.7.28 and 4.7.29).
+
+use java::class_file::{owned, InnerClassFlags};
+use std::borrow::Cow;
+
+/// an entry of the `InnerClasses` attribute.
+///
+/// a class lists every nested class it declares or refers to, and itself if it is nested.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct InnerClass<'a> {
+    pub inner_class: Cow<'a, str>,
+    /// the declaring class of a member class, `None` for local and anonymous classes
+    pub outer_class: Option<Cow<'a, str>>,
+    /// the simple name, `None` for anonymous classes
+    pub inner_name: Option<Cow<'a, str>>,
+    pub flags: InnerClassFlags,
+}
+
+impl<'a> InnerClass<'a> {
+    pub fn into_owned(self) -> InnerClass<'static> {
+        InnerClass {
+            inner_class: owned(self.inner_class),
+            outer_class: self.outer_class.map(owned),
+            inner_name: self.inner_name.map(owned),
+            flags: self.flags,
+        }
+    }
+
+    pub fn is_anonymous(&self) -> bool {
+        self.inner_name.is_none()
+    }
+
+    pub fn is_local(&self) -> bool {
+        self.outer_class.is_none() && self.inner_name.is_some()
+    }
+}
+
+/// the `EnclosingMethod` attribute of a local or anonymous class
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EnclosingMethod<'a> {
+    pub class_name: Cow<'a, str>,
+    /// `(name, descriptor)` of the method, `None` if the class is declared in an initializer
+    pub method: Option<(Cow<'a, str>, Cow<'a, str>)>,
+}
+
+impl<'a> EnclosingMethod<'a> {
+    pub fn into_owned(self) -> EnclosingMethod<'static> {
+        EnclosingMethod {
+            class_name: owned(self.class_name),
+            method: self.method.map(|(name, descriptor)| (owned(name), owned(descriptor))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use java::class_file::{read_class_file, read_class_file_lazy, ClassQuery};
+    use java::samples::{NESTING, NESTING_ANONYMOUS, NESTING_LOCAL, NESTING_STATIC_MEMBER};
+
+    #[test]
+    fn it_describes_top_level_classes() {
+        let cf = read_class_file(NESTING).unwrap();
+        assert_eq!(Some("Nesting.java"), cf.source_file().unwrap().as_deref());
+        assert_eq!("Nesting", cf.nest_host().unwrap());
+        assert_eq!(vec!["Nesting$StaticMember", "Nesting$Member", "Nesting$1Local", "Nesting$1"], *cf.nest_members().unwrap());
+        assert_eq!(4, cf.inner_classes().unwrap().len());
+        assert_eq!(None, cf.inner_class_entry().unwrap());
+        assert_eq!(None, cf.outer_class().unwrap());
+        assert_eq!("Nesting", cf.simple_name().unwrap());
+    }
+
+    #[test]
+    fn it_describes_member_classes() {
+        let cf = read_class_file(NESTING_STATIC_MEMBER).unwrap();
+        assert_eq!("Nesting", cf.nest_host().unwrap());
+        assert_eq!(Some("Nesting"), cf.outer_class().unwrap().as_deref());
+        assert_eq!("StaticMember", cf.simple_name().unwrap());
+        assert_eq!("static final", cf.inner_class_entry().unwrap().unwrap().flags.to_string());
+        assert!(!cf.is_anonymous().unwrap() && !cf.is_local().unwrap());
+    }
+
+    #[test]
+    fn it_describes_local_and_anonymous_classes() {
+        let anonymous = read_class_file(NESTING_ANONYMOUS).unwrap();
+        assert!(anonymous.is_anonymous().unwrap());
+        assert_eq!("", anonymous.simple_name().unwrap());
+        assert_eq!(Some("Nesting"), anonymous.outer_class().unwrap().as_deref());
+        assert_eq!(
+            Some(Cow::Borrowed(&EnclosingMethod {
+                class_name: Cow::Borrowed("Nesting"),
+                method: Some((Cow::Borrowed("anonymous"), Cow::Borrowed("()Ljava/lang/Runnable;"))),
+            })),
+            anonymous.enclosing_method().unwrap()
+        );
+
+        let local = read_class_file(NESTING_LOCAL).unwrap();
+        assert!(local.is_local().unwrap());
+        assert_eq!("Local", local.simple_name().unwrap());
+        assert_eq!("Nesting", local.nest_host().unwrap());
+    }
+
+    #[test]
+    fn lazy_class_files_describe_their_nesting() {
+        let lazy = read_class_file_lazy(NESTING).unwrap();
+        assert_eq!(Some("Nesting.java"), lazy.source_file().unwrap().as_deref());
+        assert_eq!(4, lazy.nest_members().unwrap().len());
+        assert_eq!("Nesting", lazy.simple_name().unwrap());
+
+        let lazy = read_class_file_lazy(NESTING_ANONYMOUS).unwrap();
+        assert!(lazy.is_anonymous().unwrap());
+        assert_eq!(Some("Nesting"), lazy.outer_class().unwrap().as_deref());
+        assert_eq!(read_class_file(NESTING_ANONYMOUS).unwrap().enclosing_method().unwrap(), lazy.enclosing_method().unwrap());
+    }
+}
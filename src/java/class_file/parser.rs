@@ -1,31 +1,77 @@
 use nom::*;
 
 use super::*;
+use super::error::{ClassFileError, ErrorReason, Structure};
+use super::annotation::*;
+use super::module::*;
+use super::nesting::*;
+use super::signature::*;
+use super::stack_map::{StackMapFrame, VerificationType};
 
+use std::cell::RefCell;
 use std::string::String;
 use std::str::from_utf8;
 
+/// the largest number of dimensions an array type can have (JVMS 4.4.1)
+const MAX_DIMENSIONS: usize = 255;
+
+/// nom error codes that `Context` turns into their own `ErrorReason`
+const TOO_MANY_DIMENSIONS: u32 = 1;
+const NESTED_TOO_DEEP: u32 = 2;
+
 named!(
-    parse_type<&[u8], ValueType>,
-    dbg_dmp!(switch!(take!(1),
-        b"L" => do_parse!( tn: map_res!(take_until!(";"), from_utf8) >> (ValueType::Object(String::from(tn)))) |
+    element_type<&[u8], ValueType>,
+    switch!(take!(1),
+        b"L" => do_parse!(
+            tn: verify!(map_res!(take_until!(";"), from_utf8), |name: &str| !name.is_empty()) >>
+            tag!(";") >>
+            (ValueType::Object(String::from(tn)))
+        ) |
+        b"B" => value!(ValueType::Byte) |
+        b"C" => value!(ValueType::Char) |
+        b"D" => value!(ValueType::Double) |
+        b"F" => value!(ValueType::Float) |
         b"I" => value!(ValueType::Integer) |
-        b"V" => value!(ValueType::Void) |
-        b"[" => do_parse!( ele: parse_type >> (ValueType::Array(Box::new(ele)))) |
-        _ => value!(ValueType::Void)
-    ))
+        b"J" => value!(ValueType::Long) |
+        b"S" => value!(ValueType::Short) |
+        b"Z" => value!(ValueType::Boolean)
+    )
+);
+
+/// a field type. the dimensions of an array are counted instead of parsed recursively, so a long
+/// run of `[` cannot exhaust the stack.
+fn parse_type(input: &[u8]) -> IResult<&[u8], ValueType> {
+    let dimensions = input.iter().take_while(|c| **c == b'[').count();
+    if dimensions > MAX_DIMENSIONS {
+        return Err(Err::Failure(error_position!(&input[MAX_DIMENSIONS..], ErrorKind::Custom(TOO_MANY_DIMENSIONS))));
+    }
+
+    let (rem, element) = element_type(&input[dimensions..])?;
+    Ok((rem, (0..dimensions).fold(element, |array, _| ValueType::Array(Box::new(array)))))
+}
+
+named!(
+    return_type<&[u8], ValueType>,
+    alt!(
+        value!(ValueType::Void, tag!("V")) |
+        parse_type
+    )
 );
 
 pub fn param_list(input: &[u8]) -> IResult<&[u8], Vec<ValueType>> {
-    if input[0] != b'(' {
-        return Err(Err::Incomplete(Needed::Size(2)));
+    match input.first() {
+        Some(b'(') => (),
+        Some(_) => return Err(Err::Error(error_position!(input, ErrorKind::Char))),
+        None => return Err(Err::Incomplete(Needed::Size(1)))
     }
 
     let mut input = &input[1..];
     let mut vec = Vec::new();
     loop {
-        if input[0] == b')' {
-            return Ok((&input[1..], vec));
+        match input.first() {
+            Some(b')') => return Ok((&input[1..], vec)),
+            Some(_) => (),
+            None => return Err(Err::Incomplete(Needed::Size(1)))
         }
 
         match parse_type(input) {
@@ -40,10 +86,162 @@ pub fn param_list(input: &[u8]) -> IResult<&[u8], Vec<ValueType>> {
 
 named!(
     pub method_desc<&[u8], (Vec<ValueType>, ValueType)>,
-    dbg_dmp!(tuple!(
+    tuple!(
         param_list,
-        parse_type
-    ))
+        return_type
+    )
+);
+
+/// identifiers in signatures end at any of these (JVMS 4.7.9.1)
+fn is_signature_delimiter(c: u8) -> bool {
+    c == b'.' || c == b';' || c == b'[' || c == b'/' || c == b'<' || c == b'>' || c == b':'
+}
+
+named!(
+    identifier<&[u8], String>,
+    map!(map_res!(take_till1!(is_signature_delimiter), from_utf8), String::from)
+);
+
+named!(
+    qualified_identifier<&[u8], String>,
+    map!(
+        verify!(
+            map_res!(take_till1!(|c| c == b'<' || c == b'.' || c == b';'), from_utf8),
+            |name: &str| name.split('/').all(|part| !part.is_empty() && !part.bytes().any(is_signature_delimiter))
+        ),
+        String::from
+    )
+);
+
+named!(
+    base_type<&[u8], ValueType>,
+    switch!(take!(1),
+        b"B" => value!(ValueType::Byte) |
+        b"C" => value!(ValueType::Char) |
+        b"D" => value!(ValueType::Double) |
+        b"F" => value!(ValueType::Float) |
+        b"I" => value!(ValueType::Integer) |
+        b"J" => value!(ValueType::Long) |
+        b"S" => value!(ValueType::Short) |
+        b"Z" => value!(ValueType::Boolean)
+    )
+);
+
+/// type arguments and annotation values can be nested this deep, deeper ones are rejected
+/// instead of exhausting the stack
+const MAX_NESTING: usize = 128;
+
+fn nested_too_deep(input: &[u8]) -> Err<&[u8]> {
+    Err::Failure(error_position!(input, ErrorKind::Custom(NESTED_TOO_DEEP)))
+}
+
+/// `depth` is the number of type argument lists around the argument
+fn type_argument(input: &[u8], depth: usize) -> IResult<&[u8], TypeArgument> {
+    alt!(input,
+        value!(TypeArgument::Any, tag!("*")) |
+        preceded!(tag!("+"), map!(call!(reference_type_signature, depth), TypeArgument::Extends)) |
+        preceded!(tag!("-"), map!(call!(reference_type_signature, depth), TypeArgument::Super)) |
+        map!(call!(reference_type_signature, depth), TypeArgument::Exact)
+    )
+}
+
+fn type_arguments(input: &[u8], depth: usize) -> IResult<&[u8], Vec<TypeArgument>> {
+    let (rem, _) = tag!(input, "<")?;
+    if depth >= MAX_NESTING {
+        return Err(nested_too_deep(input));
+    }
+
+    // many1! would turn a failure of the first argument into an error of its own
+    let mut rem = rem;
+    let mut arguments = Vec::new();
+    loop {
+        let (next, argument) = type_argument(rem, depth + 1)?;
+        arguments.push(argument);
+        rem = next;
+        if let Ok((next, _)) = tag!(rem, ">") {
+            return Ok((next, arguments));
+        }
+    }
+}
+
+fn inner_class_type_signature(input: &[u8], depth: usize) -> IResult<&[u8], SimpleClassTypeSignature> {
+    do_parse!(input,
+        tag!(".") >>
+        name: identifier >>
+        type_arguments: opt!(call!(type_arguments, depth)) >>
+        ( SimpleClassTypeSignature { name, type_arguments: type_arguments.unwrap_or_default() } )
+    )
+}
+
+fn class_type_signature(input: &[u8], depth: usize) -> IResult<&[u8], ClassTypeSignature> {
+    do_parse!(input,
+        tag!("L") >>
+        name: qualified_identifier >>
+        type_arguments: opt!(call!(type_arguments, depth)) >>
+        inner: many0!(call!(inner_class_type_signature, depth)) >>
+        tag!(";") >>
+        ( ClassTypeSignature { outer: SimpleClassTypeSignature { name, type_arguments: type_arguments.unwrap_or_default() }, inner } )
+    )
+}
+
+named!(
+    type_variable_signature<&[u8], TypeSignature>,
+    map!(delimited!(tag!("T"), identifier, tag!(";")), TypeSignature::TypeVariable)
+);
+
+/// like in `parse_type`, the dimensions of an array are counted instead of parsed recursively
+fn reference_type_signature(input: &[u8], depth: usize) -> IResult<&[u8], TypeSignature> {
+    let dimensions = input.iter().take_while(|c| **c == b'[').count();
+    if dimensions > MAX_DIMENSIONS {
+        return Err(Err::Failure(error_position!(&input[MAX_DIMENSIONS..], ErrorKind::Custom(TOO_MANY_DIMENSIONS))));
+    }
+    if dimensions > 0 {
+        let (rem, element) = alt!(&input[dimensions..], call!(reference_type_signature, depth) | map!(base_type, TypeSignature::Base))?;
+        return Ok((rem, (0..dimensions).fold(element, |array, _| TypeSignature::Array(Box::new(array)))));
+    }
+
+    switch!(input, peek!(take!(1)),
+        b"L" => map!(call!(class_type_signature, depth), TypeSignature::Class) |
+        b"T" => call!(type_variable_signature)
+    )
+}
+
+named!(
+    java_type_signature<&[u8], TypeSignature>,
+    alt!(call!(reference_type_signature, 0) | map!(base_type, TypeSignature::Base))
+);
+
+named!(
+    type_parameter<&[u8], TypeParameter>,
+    do_parse!(
+        name: identifier >>
+        tag!(":") >>
+        class_bound: opt!(call!(reference_type_signature, 0)) >>
+        interface_bounds: many0!(preceded!(tag!(":"), call!(reference_type_signature, 0))) >>
+        ( TypeParameter { name, class_bound, interface_bounds } )
+    )
+);
+
+named!(
+    type_parameters<&[u8], Vec<TypeParameter>>,
+    delimited!(tag!("<"), many1!(type_parameter), tag!(">"))
+);
+
+named!(
+    method_signature<&[u8], (Vec<TypeParameter>, Vec<TypeSignature>, TypeSignature)>,
+    do_parse!(
+        type_parameters: opt!(type_parameters) >>
+        tag!("(") >>
+        parameters: many0!(java_type_signature) >>
+        tag!(")") >>
+        return_type: alt!(value!(TypeSignature::Base(ValueType::Void), tag!("V")) | java_type_signature) >>
+        ( (type_parameters.unwrap_or_default(), parameters, return_type) )
+    )
+);
+
+named!(
+    throws_signature<&[u8], TypeSignature>,
+    preceded!(tag!("^"), alt!(map!(call!(class_type_signature, 0), TypeSignature::Class) | type_variable_signature))
 );
 
 named!(
@@ -72,7 +270,7 @@ named!(
 );
 named!(
     const_float<ConstantType>,
-    do_parse!(value: float >> ( ConstantType::Float { value } ))
+    do_parse!(value: be_f32 >> ( ConstantType::Float { value } ))
 );
 named!(
     const_long<ConstantType>,
@@ -80,7 +278,7 @@ named!(
 );
 named!(
     const_double<ConstantType>,
-    do_parse!(value: double >> ( ConstantType::Double { value } ))
+    do_parse!(value: be_f64 >> ( ConstantType::Double { value } ))
 );
 named!(
     const_name_and_type<ConstantType>,
@@ -88,7 +286,7 @@ named!(
 );
 named!(
     const_utf8<ConstantType>,
-    do_parse!(bytes: length_data!(be_u16) >> ( ConstantType::Utf8 { value: from_utf8(bytes).unwrap() } ) )
+    do_parse!(bytes: length_data!(be_u16) >> value: expr_res!(mutf8::decode(bytes)) >> ( ConstantType::Utf8 { value, bytes: Cow::Borrowed(bytes) } ) )
 );
 named!(
     const_method_handle<ConstantType>,
@@ -98,6 +296,10 @@ named!(
     const_method_type<ConstantType>,
     do_parse!(descriptor_index: be_u16 >> ( ConstantType::MethodType { descriptor_index } ) )
 );
+named!(
+    const_dynamic<ConstantType>,
+    do_parse!(bootstrap_method_attr_index: be_u16 >> name_and_type_index: be_u16 >> ( ConstantType::Dynamic { bootstrap_method_attr_index, name_and_type_index } ) )
+);
 named!(
     const_invoke_dynamic<ConstantType>,
     do_parse!(bootstrap_method_attr_index: be_u16 >> name_and_type_index: be_u16 >> ( ConstantType::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index  } )  )
@@ -113,26 +315,170 @@ named!(
 
 named!(
     constant<&[u8], ConstantType>,
-    dbg_dmp!(switch!(be_u8,
-        1 => dbg_dmp!(call!(const_utf8 )) |
-        3 => dbg_dmp!(call!(const_integer )) |
-        4 => dbg_dmp!(call!(const_float )) |
-        5 => dbg_dmp!(call!(const_long )) |
-        6 => dbg_dmp!(call!(const_double )) |
-        7 => dbg_dmp!(call!(const_class )) |
-        8 => dbg_dmp!(call!(const_string )) |
-        9 => dbg_dmp!(call!(const_fieldref )) |
-        10 => dbg_dmp!(call!(const_methodref )) |
-        11 => dbg_dmp!(call!(const_interface_methodref )) |
-        12 => dbg_dmp!(call!(const_name_and_type )) |
-        15 => dbg_dmp!(call!(const_method_handle )) |
-        16 => dbg_dmp!(call!(const_method_type )) |
-        18 => dbg_dmp!(call!(const_invoke_dynamic )) |
-        19 => dbg_dmp!(call!(const_module )) |
-        20 => dbg_dmp!(call!(const_package))
-    ))
+    switch!(be_u8,
+        1 => call!(const_utf8) |
+        3 => call!(const_integer) |
+        4 => call!(const_float) |
+        5 => call!(const_long) |
+        6 => call!(const_double) |
+        7 => call!(const_class) |
+        8 => call!(const_string) |
+        9 => call!(const_fieldref) |
+        10 => call!(const_methodref) |
+        11 => call!(const_interface_methodref) |
+        12 => call!(const_name_and_type) |
+        15 => call!(const_method_handle) |
+        16 => call!(const_method_type) |
+        17 => call!(const_dynamic) |
+        18 => call!(const_invoke_dynamic) |
+        19 => call!(const_module) |
+        20 => call!(const_package)
+    )
 );
 
+const MAGIC: [u8; 4] = [0xCA, 0xFE, 0xBA, 0xBE];
+
+/// keeps track of the part of the class file that is currently parsed,
+/// so errors can point to the broken structure
+struct Context<'i> {
+    input: &'i [u8],
+    path: Vec<Structure>,
+}
+
+impl<'i> Context<'i> {
+    fn new(input: &'i [u8]) -> Context<'i> {
+        Context { input, path: Vec::new() }
+    }
+
+    fn enter(&mut self, structure: Structure) {
+        self.path.push(structure);
+    }
+
+    fn leave(&mut self) {
+        self.path.pop();
+    }
+
+    /// `at` has to be a slice of the input this context was created for
+    fn offset(&self, at: &[u8]) -> usize {
+        at.as_ptr() as usize - self.input.as_ptr() as usize
+    }
+
+    fn error(&self, at: &[u8], reason: ErrorReason) -> ClassFileError {
+        ClassFileError { offset: self.offset(at), structure: self.path.clone(), reason }
+    }
+
+    /// converts the result of a nom parser that was called on `at`
+    fn run<T>(&self, at: &'i [u8], result: IResult<&'i [u8], T>) -> Result<(&'i [u8], T), ClassFileError> {
+        result.map_err(|err| self.nom_error(at, err))
+    }
+
+    fn nom_error(&self, at: &'i [u8], err: Err<&'i [u8]>) -> ClassFileError {
+        match err {
+            Err::Incomplete(_) => self.error(&at[at.len()..], ErrorReason::UnexpectedEnd),
+            Err::Error(context) | Err::Failure(context) => {
+                let (position, kind) = match context {
+                    ::nom::Context::Code(position, kind) => (position, kind),
+                    ::nom::Context::List(mut list) => list.remove(0),
+                };
+                match kind {
+                    ErrorKind::Custom(TOO_MANY_DIMENSIONS) => self.error(position, ErrorReason::TooManyDimensions { max: MAX_DIMENSIONS }),
+                    ErrorKind::Custom(NESTED_TOO_DEEP) => self.error(position, ErrorReason::NestedTooDeep { max: MAX_NESTING }),
+                    kind => self.error(position, ErrorReason::Malformed { kind: format!("{:?}", kind) })
+                }
+            }
+        }
+    }
+
+    /// fails unless `remaining`, the unparsed rest of an attribute, is empty
+    fn expect_end(&self, body: &[u8], remaining: &[u8]) -> Result<(), ClassFileError> {
+        if remaining.is_empty() {
+            Ok(())
+        } else {
+            Err(self.error(remaining, ErrorReason::AttributeLength { declared: body.len(), consumed: body.len() - remaining.len() }))
+        }
+    }
+}
+
+/// the constant pool as the attribute parsers see it. remembers the index of every
+/// constant they resolve, in order, so the writer can point to the same entries again
+struct Constants<'c, 'i: 'c> {
+    pool: &'c ConstantPool<'i>,
+    references: RefCell<Vec<u16>>,
+}
+
+impl<'c, 'i: 'c> Constants<'c, 'i> {
+    fn new(pool: &'c ConstantPool<'i>) -> Constants<'c, 'i> {
+        Constants { pool, references: RefCell::new(Vec::new()) }
+    }
+
+    fn resolved<T>(&self, index: u16, value: T) -> T {
+        self.references.borrow_mut().push(index);
+        value
+    }
+
+    fn get(&self, index: u16) -> Result<&'c ConstantType<'i>, ConstantPoolError> {
+        self.pool.get(index).map(|value| self.resolved(index, value))
+    }
+
+    fn utf8(&self, index: u16) -> Result<&'c str, ConstantPoolError> {
+        self.pool.utf8(index).map(|value| self.resolved(index, value))
+    }
+
+    fn utf8_cow(&self, index: u16) -> Result<&'c Cow<'i, str>, ConstantPoolError> {
+        self.pool.utf8_cow(index).map(|value| self.resolved(index, value))
+    }
+
+    fn class_name_cow(&self, index: u16) -> Result<&'c Cow<'i, str>, ConstantPoolError> {
+        self.pool.class_name_cow(index).map(|value| self.resolved(index, value))
+    }
+
+    fn module_name_cow(&self, index: u16) -> Result<&'c Cow<'i, str>, ConstantPoolError> {
+        self.pool.module_name_cow(index).map(|value| self.resolved(index, value))
+    }
+
+    fn package_name_cow(&self, index: u16) -> Result<&'c Cow<'i, str>, ConstantPoolError> {
+        self.pool.package_name_cow(index).map(|value| self.resolved(index, value))
+    }
+}
+
+/// reads the constant pool count and `count - 1` constant pool slots.
+/// `Long` and `Double` entries take up two of them.
+fn constant_pool<'i>(ctx: &mut Context<'i>, input: &'i [u8]) -> Result<(&'i [u8], ConstantPool<'i>), ClassFileError> {
+    let (mut input, count) = ctx.run(input, be_u16(input))?;
+    let mut pool = ConstantPool::new();
+    while pool.len() + 1 < usize::from(count) {
+        ctx.enter(Structure::Constant((pool.len() + 1) as u16));
+        let (rem, constant) = match constant(input) {
+            Ok(res) => res,
+            Err(err) => return Err(constant_error(ctx, input, err))
+        };
+        ctx.leave();
+
+        pool.push(constant);
+        input = rem;
+    }
+
+    Ok((input, pool))
+}
+
+/// the nom errors of `constant` do not tell us much, figure out what actually went wrong
+fn constant_error<'i>(ctx: &Context<'i>, input: &'i [u8], err: Err<&'i [u8]>) -> ClassFileError {
+    match input.first() {
+        Some(&tag) if !(tag == 1 || (3..=12).contains(&tag) || (15..=20).contains(&tag)) => {
+            ctx.error(input, ErrorReason::UnknownConstantTag { tag })
+        }
+        Some(1) => {
+            if let Ok((_, bytes)) = length_data!(&input[1..], be_u16) {
+                if let Err(utf8_err) = mutf8::decode(bytes) {
+                    return ctx.error(bytes, ErrorReason::InvalidUtf8(utf8_err));
+                }
+            }
+            ctx.nom_error(input, err)
+        }
+        _ => ctx.nom_error(input, err)
+    }
+}
+
 named!(
     exception_table<(u16, u16, u16, u16)>,
     do_parse!(
@@ -147,7 +493,6 @@ named!(
 named!(
     line_number_table<Attribute>,
     do_parse!(
-        length: be_u32 >>
         line_numbers: length_count!(
             be_u16,
             do_parse!(
@@ -162,144 +507,919 @@ named!(
     )
 );
 
-fn select_attribute<'t, 'a>(input: &'t [u8], name: &str, constants: &'a Vec<ConstantType<'a>>) -> IResult<&'t [u8], Attribute<'t>> {
-    match name {
-        "LineNumberTable" => {
-            match line_number_table(input) {
-                Ok((rem, line_numbers)) => {
-                    Ok((&rem, line_numbers))
-                }
-                Err(err) => return Err(err)
-            }
+named!(
+    verification_type_info<(u8, u16)>,
+    switch!(be_u8,
+        7 => do_parse!( index: be_u16 >> ( (7, index) ) ) |
+        8 => do_parse!( offset: be_u16 >> ( (8, offset) ) ) |
+        tag => value!( (tag, 0) )
+    )
+);
+
+/// a stack map frame with unresolved verification types
+struct RawFrame {
+    frame_type: u8,
+    offset_delta: u16,
+    locals: Vec<(u8, u16)>,
+    stack: Vec<(u8, u16)>,
+}
+
+fn stack_map_frame(input: &[u8]) -> IResult<&[u8], RawFrame> {
+    let (input, frame_type) = be_u8(input)?;
+    match frame_type {
+        0..=63 => Ok((input, RawFrame { frame_type, offset_delta: u16::from(frame_type), locals: vec![], stack: vec![] })),
+        64..=127 => do_parse!(input,
+            stack: verification_type_info >>
+            ( RawFrame { frame_type, offset_delta: u16::from(frame_type - 64), locals: vec![], stack: vec![stack] } )
+        ),
+        247 => do_parse!(input,
+            offset_delta: be_u16 >>
+            stack: verification_type_info >>
+            ( RawFrame { frame_type, offset_delta, locals: vec![], stack: vec![stack] } )
+        ),
+        248..=251 => do_parse!(input,
+            offset_delta: be_u16 >>
+            ( RawFrame { frame_type, offset_delta, locals: vec![], stack: vec![] } )
+        ),
+        252..=254 => do_parse!(input,
+            offset_delta: be_u16 >>
+            locals: count!( verification_type_info, usize::from(frame_type - 251) ) >>
+            ( RawFrame { frame_type, offset_delta, locals, stack: vec![] } )
+        ),
+        255 => do_parse!(input,
+            offset_delta: be_u16 >>
+            locals: length_count!( be_u16, verification_type_info ) >>
+            stack: length_count!( be_u16, verification_type_info ) >>
+            ( RawFrame { frame_type, offset_delta, locals, stack } )
+        ),
+        _ => Err(Err::Error(error_position!(input, ErrorKind::Switch)))
+    }
+}
+
+fn verification_type<'i>(ctx: &Context<'i>, at: &'i [u8], (tag, data): (u8, u16), constants: &Constants<'_, 'i>) -> Result<VerificationType<'i>, ClassFileError> {
+    Ok(match tag {
+        0 => VerificationType::Top,
+        1 => VerificationType::Integer,
+        2 => VerificationType::Float,
+        3 => VerificationType::Double,
+        4 => VerificationType::Long,
+        5 => VerificationType::Null,
+        6 => VerificationType::UninitializedThis,
+        7 => {
+            let name = constants.class_name_cow(data).map_err(|err| ctx.error(at, ErrorReason::InvalidConstant(err)))?;
+            VerificationType::Object(name.clone())
         }
-        "Code" => {
-            match do_parse!( input,
-                    be_u32 >>
-                    max_stack: be_u16 >>
-                    max_locals: be_u16 >>
-                    code: length_data!( be_u32 ) >>
-                    exception_table: length_count!( be_u16, exception_table ) >>
-                    attributes: length_count!( be_u16, call!(attribute, &constants)) >>
-                    (
-                        Attribute::CodeAttribute( CodeBlock { max_stack, max_locals, code: code.to_vec(), attributes } )
-                    )
-                ) {
-                Ok((rem, attribute)) => Ok((&rem, attribute)),
-                Err(err) => return Err(err)
-            }
+        8 => VerificationType::Uninitialized(data),
+        _ => return Err(ctx.error(at, ErrorReason::Malformed { kind: format!("verification type {}", tag) }))
+    })
+}
+
+fn stack_map_table<'i>(ctx: &Context<'i>, body: &'i [u8], constants: &Constants<'_, 'i>) -> Result<Attribute<'i>, ClassFileError> {
+    let (mut input, count) = ctx.run(body, be_u16(body))?;
+    let mut frames = Vec::with_capacity(usize::from(count));
+    // the pc of the previous frame, the first one is at its offset_delta
+    let mut pc: Option<u32> = None;
+    for idx in 0..usize::from(count) {
+        let (rem, raw) = ctx.run(input, stack_map_frame(input))?;
+        let frame_pc = pc.map_or(u32::from(raw.offset_delta), |pc| pc + u32::from(raw.offset_delta) + 1);
+        if frame_pc > u32::from(u16::MAX) {
+            return Err(ctx.error(input, ErrorReason::FrameOffset { frame: idx }));
+        }
+        pc = Some(frame_pc);
+        let mut locals = Vec::with_capacity(raw.locals.len());
+        for info in raw.locals {
+            locals.push(verification_type(ctx, input, info, constants)?);
+        }
+        let mut stack = Vec::with_capacity(raw.stack.len());
+        for info in raw.stack {
+            stack.push(verification_type(ctx, input, info, constants)?);
         }
-        _ => {
-            let nm = String::from(name);
-            match be_u32(input) {
-                Ok((rem, length)) => {
-                    Ok((&rem[(length as usize)..], Attribute::GenericAttribute { name: nm, info: &rem[0..(length as usize)] }))
+
+        let offset_delta = raw.offset_delta;
+        frames.push(match raw.frame_type {
+            0..=63 => StackMapFrame::Same { offset_delta },
+            64..=127 => StackMapFrame::SameLocals1StackItem { offset_delta, stack: stack.remove(0) },
+            247 => StackMapFrame::SameLocals1StackItemExtended { offset_delta, stack: stack.remove(0) },
+            248..=250 => StackMapFrame::Chop { offset_delta, count: 251 - raw.frame_type },
+            251 => StackMapFrame::SameExtended { offset_delta },
+            252..=254 => StackMapFrame::Append { offset_delta, locals },
+            _ => StackMapFrame::Full { offset_delta, locals, stack },
+        });
+        input = rem;
+    }
+    ctx.expect_end(body, input)?;
+
+    Ok(Attribute::StackMapTable(frames))
+}
+
+named!(
+    local_variable_table<Vec<(u16, u16, u16, u16, u16)>>,
+    length_count!(
+        be_u16,
+        do_parse!(
+            start_pc: be_u16 >>
+            length: be_u16 >>
+            name_index: be_u16 >>
+            descriptor_index: be_u16 >>
+            index: be_u16 >>
+            (start_pc, length, name_index, descriptor_index, index)
+        )
+    )
+);
+
+/// parses both the `LocalVariableTable` and the `LocalVariableTypeTable`, they only differ
+/// in the meaning of the descriptor
+fn local_variables<'i>(ctx: &Context<'i>, body: &'i [u8], constants: &Constants<'_, 'i>) -> Result<Vec<LocalVariableInfo<'i>>, ClassFileError> {
+    let (rem, raw_variables) = ctx.run(body, local_variable_table(body))?;
+    ctx.expect_end(body, rem)?;
+
+    let mut variables = Vec::with_capacity(raw_variables.len());
+    for (idx, (start_pc, length, name_index, descriptor_index, index)) in raw_variables.into_iter().enumerate() {
+        let entry = &body[2 + idx * 10..];
+        let name = constants.utf8_cow(name_index).map_err(|err| ctx.error(&entry[4..], ErrorReason::InvalidConstant(err)))?;
+        let descriptor = constants.utf8_cow(descriptor_index).map_err(|err| ctx.error(&entry[6..], ErrorReason::InvalidConstant(err)))?;
+        variables.push(LocalVariableInfo { start_pc, length, name: name.clone(), descriptor: descriptor.clone(), index });
+    }
+
+    Ok(variables)
+}
+
+fn constant_value<'i>(ctx: &Context<'i>, body: &'i [u8], constants: &Constants<'_, 'i>) -> Result<Attribute<'i>, ClassFileError> {
+    let (rem, index) = ctx.run(body, be_u16(body))?;
+    ctx.expect_end(body, rem)?;
+
+    let value = match constants.get(index) {
+        Ok(ConstantType::Integer { value }) => ConstantValue::Integer(*value),
+        Ok(ConstantType::Float { value }) => ConstantValue::Float(*value),
+        Ok(ConstantType::Long { value }) => ConstantValue::Long(*value),
+        Ok(ConstantType::Double { value }) => ConstantValue::Double(*value),
+        Ok(ConstantType::String { string_index }) => {
+            let value = constants.pool.utf8_cow(*string_index).map_err(|err| ctx.error(body, ErrorReason::InvalidConstant(err)))?;
+            ConstantValue::String(value.clone())
+        }
+        Ok(other) => {
+            let err = ConstantPoolError::WrongType { index, expected: "Integer, Float, Long, Double or String", found: other.name() };
+            return Err(ctx.error(body, ErrorReason::InvalidConstant(err)));
+        }
+        Err(err) => return Err(ctx.error(body, ErrorReason::InvalidConstant(err)))
+    };
+
+    Ok(Attribute::ConstantValue(value))
+}
+
+/// `depth` is the number of arrays and annotations around the annotation, 0 for the annotations of an attribute
+fn annotation<'i>(ctx: &Context<'i>, input: &'i [u8], constants: &Constants<'_, 'i>, depth: usize) -> Result<(&'i [u8], Annotation<'i>), ClassFileError> {
+    let (mut rem, (type_index, count)) = ctx.run(input, tuple!(input, be_u16, be_u16))?;
+    let type_name = constants.utf8_cow(type_index).map_err(|err| ctx.error(input, ErrorReason::InvalidConstant(err)))?;
+
+    let mut elements = Vec::with_capacity(usize::from(count));
+    for _ in 0..count {
+        let (next, name_index) = ctx.run(rem, be_u16(rem))?;
+        let name = constants.utf8_cow(name_index).map_err(|err| ctx.error(rem, ErrorReason::InvalidConstant(err)))?;
+        let (next, value) = element_value(ctx, next, constants, depth)?;
+        elements.push((name.clone(), value));
+        rem = next;
+    }
+
+    Ok((rem, Annotation { type_name: type_name.clone(), elements }))
+}
+
+fn element_value<'i>(ctx: &Context<'i>, input: &'i [u8], constants: &Constants<'_, 'i>, depth: usize) -> Result<(&'i [u8], ElementValue<'i>), ClassFileError> {
+    let (rem, tag) = ctx.run(input, be_u8(input))?;
+    let invalid_constant = |at: &'i [u8], err: ConstantPoolError| ctx.error(at, ErrorReason::InvalidConstant(err));
+    if (tag == b'@' || tag == b'[') && depth >= MAX_NESTING {
+        return Err(ctx.error(input, ErrorReason::NestedTooDeep { max: MAX_NESTING }));
+    }
+
+    match tag {
+        b'B' | b'C' | b'D' | b'F' | b'I' | b'J' | b'S' | b'Z' | b's' => {
+            let (next, index) = ctx.run(rem, be_u16(rem))?;
+            let value = match (tag, constants.get(index).map_err(|err| invalid_constant(rem, err))?) {
+                (b'B', ConstantType::Integer { value }) => ElementValue::Byte(*value as i8),
+                (b'C', ConstantType::Integer { value }) => ElementValue::Char(*value as u16),
+                (b'I', ConstantType::Integer { value }) => ElementValue::Integer(*value),
+                (b'S', ConstantType::Integer { value }) => ElementValue::Short(*value as i16),
+                (b'Z', ConstantType::Integer { value }) => ElementValue::Boolean(*value != 0),
+                (b'D', ConstantType::Double { value }) => ElementValue::Double(*value),
+                (b'F', ConstantType::Float { value }) => ElementValue::Float(*value),
+                (b'J', ConstantType::Long { value }) => ElementValue::Long(*value),
+                (b's', ConstantType::Utf8 { value, .. }) => ElementValue::String(value.clone()),
+                (_, other) => {
+                    let expected = match tag {
+                        b'D' => "Double",
+                        b'F' => "Float",
+                        b'J' => "Long",
+                        b's' => "Utf8",
+                        _ => "Integer"
+                    };
+                    return Err(invalid_constant(rem, ConstantPoolError::WrongType { index, expected, found: other.name() }));
                 }
-                Err(err) => return Err(err)
+            };
+            Ok((next, value))
+        }
+        b'e' => {
+            let (next, (type_index, const_index)) = ctx.run(rem, tuple!(rem, be_u16, be_u16))?;
+            let type_name = constants.utf8_cow(type_index).map_err(|err| invalid_constant(rem, err))?;
+            let const_name = constants.utf8_cow(const_index).map_err(|err| invalid_constant(&rem[2..], err))?;
+            Ok((next, ElementValue::Enum { type_name: type_name.clone(), const_name: const_name.clone() }))
+        }
+        b'c' => {
+            let (next, index) = ctx.run(rem, be_u16(rem))?;
+            let class = constants.utf8_cow(index).map_err(|err| invalid_constant(rem, err))?;
+            Ok((next, ElementValue::Class(class.clone())))
+        }
+        b'@' => annotation(ctx, rem, constants, depth + 1).map(|(next, value)| (next, ElementValue::Annotation(value))),
+        b'[' => {
+            let (mut next, count) = ctx.run(rem, be_u16(rem))?;
+            let mut values = Vec::with_capacity(usize::from(count));
+            for _ in 0..count {
+                let (after, value) = element_value(ctx, next, constants, depth + 1)?;
+                values.push(value);
+                next = after;
             }
+            Ok((next, ElementValue::Array(values)))
         }
+        _ => Err(ctx.error(input, ErrorReason::Malformed { kind: format!("element value tag {:?}", tag as char) }))
     }
 }
 
-fn attribute<'t, 'a>(input: &'t [u8], constants: &'a Vec<ConstantType<'a>>) -> IResult<&'t [u8], Attribute<'t>> {
-    let idx_res = be_u16(input);
-    match idx_res {
-        Ok((remaining, index)) => {
-            match constants.get(index as usize - 1) {
-                Some(ConstantType::Utf8 { value: ref name }) => {
-                    select_attribute(remaining, name, constants)
-                }
-                _ => {
-                    Err(Err::Error(error_position!(remaining, ErrorKind::Custom(1))))
-                }
+/// the body of `Runtime(In)VisibleAnnotations`
+fn annotations<'i>(ctx: &Context<'i>, body: &'i [u8], constants: &Constants<'_, 'i>) -> Result<Vec<Annotation<'i>>, ClassFileError> {
+    let (mut rem, count) = ctx.run(body, be_u16(body))?;
+    let mut annotations = Vec::with_capacity(usize::from(count));
+    for _ in 0..count {
+        let (next, value) = annotation(ctx, rem, constants, 0)?;
+        annotations.push(value);
+        rem = next;
+    }
+
+    ctx.expect_end(body, rem)?;
+    Ok(annotations)
+}
+
+/// the body of `Runtime(In)VisibleParameterAnnotations`
+fn parameter_annotations<'i>(ctx: &Context<'i>, body: &'i [u8], constants: &Constants<'_, 'i>) -> Result<Vec<Vec<Annotation<'i>>>, ClassFileError> {
+    let (mut rem, parameters) = ctx.run(body, be_u8(body))?;
+    let mut result = Vec::with_capacity(usize::from(parameters));
+    for _ in 0..parameters {
+        let (mut next, count) = ctx.run(rem, be_u16(rem))?;
+        let mut annotations = Vec::with_capacity(usize::from(count));
+        for _ in 0..count {
+            let (after, value) = annotation(ctx, next, constants, 0)?;
+            annotations.push(value);
+            next = after;
+        }
+        result.push(annotations);
+        rem = next;
+    }
+
+    ctx.expect_end(body, rem)?;
+    Ok(result)
+}
+
+fn type_annotation_target(input: &[u8], target_type: u8) -> IResult<&[u8], TypeAnnotationTarget> {
+    match target_type {
+        0x00 | 0x01 => map!(input, be_u8, |index| TypeAnnotationTarget::TypeParameter { target_type, index }),
+        0x10 => map!(input, be_u16, |index| TypeAnnotationTarget::SuperType { index }),
+        0x11 | 0x12 => map!(input, tuple!(be_u8, be_u8), |(type_parameter_index, bound_index)| {
+            TypeAnnotationTarget::TypeParameterBound { target_type, type_parameter_index, bound_index }
+        }),
+        0x13..=0x15 => Ok((input, TypeAnnotationTarget::Empty { target_type })),
+        0x16 => map!(input, be_u8, |index| TypeAnnotationTarget::FormalParameter { index }),
+        0x17 => map!(input, be_u16, |index| TypeAnnotationTarget::Throws { index }),
+        0x40 | 0x41 => map!(input,
+            length_count!(be_u16, do_parse!(
+                start_pc: be_u16 >>
+                length: be_u16 >>
+                index: be_u16 >>
+                ( LocalVariableTarget { start_pc, length, index } )
+            )),
+            |table| TypeAnnotationTarget::LocalVariable { target_type, table }
+        ),
+        0x42 => map!(input, be_u16, |exception_table_index| TypeAnnotationTarget::Catch { exception_table_index }),
+        0x43..=0x46 => map!(input, be_u16, |offset| TypeAnnotationTarget::Offset { target_type, offset }),
+        0x47..=0x4b => map!(input, tuple!(be_u16, be_u8), |(offset, type_argument_index)| {
+            TypeAnnotationTarget::TypeArgument { target_type, offset, type_argument_index }
+        }),
+        _ => Err(Err::Error(error_position!(input, ErrorKind::Switch)))
+    }
+}
+
+named!(
+    type_path<Vec<(u8, u8)>>,
+    length_count!(be_u8, tuple!(be_u8, be_u8))
+);
+
+/// the body of `Runtime(In)VisibleTypeAnnotations`
+fn type_annotations<'i>(ctx: &Context<'i>, body: &'i [u8], constants: &Constants<'_, 'i>) -> Result<Vec<TypeAnnotation<'i>>, ClassFileError> {
+    let (mut rem, count) = ctx.run(body, be_u16(body))?;
+    let mut annotations = Vec::with_capacity(usize::from(count));
+    for _ in 0..count {
+        let (next, target_type) = ctx.run(rem, be_u8(rem))?;
+        let (next, target) = type_annotation_target(next, target_type)
+            .map_err(|_| ctx.error(rem, ErrorReason::Malformed { kind: format!("type annotation target 0x{:02x}", target_type) }))?;
+
+        let (next, raw_path) = ctx.run(next, type_path(next))?;
+        let mut path = Vec::with_capacity(raw_path.len());
+        for (idx, (kind, argument_index)) in raw_path.into_iter().enumerate() {
+            path.push(match kind {
+                0 => TypePathEntry::Array,
+                1 => TypePathEntry::Nested,
+                2 => TypePathEntry::WildcardBound,
+                3 => TypePathEntry::TypeArgument(argument_index),
+                _ => return Err(ctx.error(&next[1 + idx * 2..], ErrorReason::Malformed { kind: format!("type path kind {}", kind) }))
+            });
+        }
+
+        let (next, annotation) = annotation(ctx, next, constants, 0)?;
+        annotations.push(TypeAnnotation { target, path, annotation });
+        rem = next;
+    }
+
+    ctx.expect_end(body, rem)?;
+    Ok(annotations)
+}
+
+/// parses the `BootstrapMethods` attribute, every entry has to point to a `MethodHandle`
+fn bootstrap_methods<'i>(ctx: &Context<'i>, body: &'i [u8], constants: &Constants<'_, 'i>) -> Result<Attribute<'i>, ClassFileError> {
+    let (mut rem, count) = ctx.run(body, be_u16(body))?;
+    let mut methods = Vec::with_capacity(usize::from(count));
+    for _ in 0..count {
+        let (next, (method_ref, arguments)) = ctx.run(rem, tuple!(rem, be_u16, length_count!(be_u16, be_u16)))?;
+        constants.pool.method_handle(method_ref).map_err(|err| ctx.error(rem, ErrorReason::InvalidConstant(err)))?;
+        methods.push(BootstrapMethod { method_ref, arguments });
+        rem = next;
+    }
+
+    ctx.expect_end(body, rem)?;
+    Ok(Attribute::BootstrapMethods(methods))
+}
+
+named!(
+    index_list<Vec<u16>>,
+    length_count!(be_u16, be_u16)
+);
+
+/// reads a list of constant pool indexes and resolves each with `lookup`
+fn resolve_list<'i, 'c, F>(ctx: &Context<'i>, input: &'i [u8], lookup: F) -> Result<(&'i [u8], Vec<Cow<'i, str>>), ClassFileError>
+    where F: Fn(u16) -> Result<&'c Cow<'i, str>, ConstantPoolError>, 'i: 'c {
+    let (rem, indexes) = ctx.run(input, index_list(input))?;
+    let mut values = Vec::with_capacity(indexes.len());
+    for (idx, index) in indexes.into_iter().enumerate() {
+        let value = lookup(index).map_err(|err| ctx.error(&input[2 + idx * 2..], ErrorReason::InvalidConstant(err)))?;
+        values.push(value.clone());
+    }
+
+    Ok((rem, values))
+}
+
+/// the `exports` and `opens` tables of the `Module` attribute
+fn module_exports<'i>(ctx: &Context<'i>, input: &'i [u8], constants: &Constants<'_, 'i>) -> Result<(&'i [u8], Vec<Exports<'i>>), ClassFileError> {
+    let (mut rem, count) = ctx.run(input, be_u16(input))?;
+    let mut exports = Vec::with_capacity(usize::from(count));
+    for _ in 0..count {
+        let (next, (package_index, flags)) = ctx.run(rem, tuple!(rem, be_u16, be_u16))?;
+        let package = constants.package_name_cow(package_index).map_err(|err| ctx.error(rem, ErrorReason::InvalidConstant(err)))?;
+        let (next, to) = resolve_list(ctx, next, |index| constants.module_name_cow(index))?;
+        exports.push(Exports { package: package.clone(), flags: ExportsFlags::from_bits(flags), to });
+        rem = next;
+    }
+
+    Ok((rem, exports))
+}
+
+fn module_attribute<'i>(ctx: &Context<'i>, body: &'i [u8], constants: &Constants<'_, 'i>) -> Result<Attribute<'i>, ClassFileError> {
+    let invalid_constant = |at: &'i [u8], err: ConstantPoolError| ctx.error(at, ErrorReason::InvalidConstant(err));
+    let version = |at: &'i [u8], index: u16| -> Result<Option<Cow<'i, str>>, ClassFileError> {
+        if index == 0 {
+            Ok(None)
+        } else {
+            constants.utf8_cow(index).map(|value| Some(value.clone())).map_err(|err| invalid_constant(at, err))
+        }
+    };
+
+    let (rem, (name_index, flags, version_index)) = ctx.run(body, tuple!(body, be_u16, be_u16, be_u16))?;
+    let name = constants.module_name_cow(name_index).map_err(|err| invalid_constant(body, err))?;
+    let module_version = version(&body[4..], version_index)?;
+
+    let (mut rem, count) = ctx.run(rem, be_u16(rem))?;
+    let mut requires = Vec::with_capacity(usize::from(count));
+    for _ in 0..count {
+        let (next, (module_index, flags, version_index)) = ctx.run(rem, tuple!(rem, be_u16, be_u16, be_u16))?;
+        let module = constants.module_name_cow(module_index).map_err(|err| invalid_constant(rem, err))?;
+        requires.push(Requires { module: module.clone(), flags: RequiresFlags::from_bits(flags), version: version(&rem[4..], version_index)? });
+        rem = next;
+    }
+
+    let (rem, exports) = module_exports(ctx, rem, constants)?;
+    let (rem, opens) = module_exports(ctx, rem, constants)?;
+    let (rem, uses) = resolve_list(ctx, rem, |index| constants.class_name_cow(index))?;
+
+    let (mut rem, count) = ctx.run(rem, be_u16(rem))?;
+    let mut provides = Vec::with_capacity(usize::from(count));
+    for _ in 0..count {
+        let (next, service_index) = ctx.run(rem, be_u16(rem))?;
+        let service = constants.class_name_cow(service_index).map_err(|err| invalid_constant(rem, err))?;
+        let (next, with) = resolve_list(ctx, next, |index| constants.class_name_cow(index))?;
+        provides.push(Provides { service: service.clone(), with });
+        rem = next;
+    }
+
+    ctx.expect_end(body, rem)?;
+    Ok(Attribute::Module(Module {
+        name: name.clone(),
+        flags: ModuleFlags::from_bits(flags),
+        version: module_version,
+        requires,
+        exports,
+        opens,
+        uses,
+        provides,
+    }))
+}
+
+named!(
+    inner_classes_table<Vec<(u16, u16, u16, u16)>>,
+    length_count!(be_u16, tuple!(be_u16, be_u16, be_u16, be_u16))
+);
+
+fn inner_classes<'i>(ctx: &Context<'i>, body: &'i [u8], constants: &Constants<'_, 'i>) -> Result<Attribute<'i>, ClassFileError> {
+    let (rem, entries) = ctx.run(body, inner_classes_table(body))?;
+    ctx.expect_end(body, rem)?;
+
+    let mut classes = Vec::with_capacity(entries.len());
+    for (idx, (inner_index, outer_index, name_index, flags)) in entries.into_iter().enumerate() {
+        let entry = &body[2 + idx * 8..];
+        let invalid_constant = |at: &'i [u8], err: ConstantPoolError| ctx.error(at, ErrorReason::InvalidConstant(err));
+
+        let inner_class = constants.class_name_cow(inner_index).map_err(|err| invalid_constant(entry, err))?;
+        let outer_class = match outer_index {
+            0 => None,
+            index => Some(constants.class_name_cow(index).map_err(|err| invalid_constant(&entry[2..], err))?.clone())
+        };
+        let inner_name = match name_index {
+            0 => None,
+            index => Some(constants.utf8_cow(index).map_err(|err| invalid_constant(&entry[4..], err))?.clone())
+        };
+
+        classes.push(InnerClass { inner_class: inner_class.clone(), outer_class, inner_name, flags: InnerClassFlags::from_bits(flags) });
+    }
+
+    Ok(Attribute::InnerClasses(classes))
+}
+
+fn enclosing_method<'i>(ctx: &Context<'i>, body: &'i [u8], constants: &Constants<'_, 'i>) -> Result<Attribute<'i>, ClassFileError> {
+    let (rem, (class_index, method_index)) = ctx.run(body, tuple!(body, be_u16, be_u16))?;
+    ctx.expect_end(body, rem)?;
+
+    let class_name = constants.class_name_cow(class_index).map_err(|err| ctx.error(body, ErrorReason::InvalidConstant(err)))?;
+    let method = match method_index {
+        0 => None,
+        index => match constants.get(index) {
+            Ok(ConstantType::NameAndType { name_index, descriptor_index }) => {
+                let name = constants.pool.utf8_cow(*name_index).map_err(|err| ctx.error(&body[2..], ErrorReason::InvalidConstant(err)))?;
+                let descriptor = constants.pool.utf8_cow(*descriptor_index).map_err(|err| ctx.error(&body[2..], ErrorReason::InvalidConstant(err)))?;
+                Some((name.clone(), descriptor.clone()))
             }
+            Ok(other) => {
+                let err = ConstantPoolError::WrongType { index, expected: "NameAndType", found: other.name() };
+                return Err(ctx.error(&body[2..], ErrorReason::InvalidConstant(err)));
+            }
+            Err(err) => return Err(ctx.error(&body[2..], ErrorReason::InvalidConstant(err)))
         }
-        Err(err) => return Err(err)
+    };
+
+    Ok(Attribute::EnclosingMethod(EnclosingMethod { class_name: class_name.clone(), method }))
+}
+
+fn record<'i>(ctx: &mut Context<'i>, body: &'i [u8], constants: &Constants<'_, 'i>) -> Result<Attribute<'i>, ClassFileError> {
+    let (mut rem, count) = ctx.run(body, be_u16(body))?;
+    let mut components = Vec::with_capacity(usize::from(count));
+    for idx in 0..count {
+        ctx.enter(Structure::RecordComponent(idx));
+        let (next, (name_index, descriptor_index)) = ctx.run(rem, tuple!(rem, be_u16, be_u16))?;
+        let name = constants.utf8_cow(name_index).map_err(|err| ctx.error(rem, ErrorReason::InvalidConstant(err)))?;
+        let descriptor = constants.utf8_cow(descriptor_index).map_err(|err| ctx.error(&rem[2..], ErrorReason::InvalidConstant(err)))?;
+        let (next, attributes) = attributes(ctx, next, constants)?;
+        ctx.leave();
+
+        components.push(RecordComponent { name: name.clone(), descriptor: descriptor.clone(), attributes });
+        rem = next;
     }
+
+    ctx.expect_end(body, rem)?;
+    Ok(Attribute::Record(components))
+}
+
+fn code_attribute<'i>(ctx: &mut Context<'i>, body: &'i [u8], constants: &Constants<'_, 'i>) -> Result<Attribute<'i>, ClassFileError> {
+    let (table, (max_stack, max_locals, code)) = ctx.run(body, do_parse!(body,
+        max_stack: be_u16 >>
+        max_locals: be_u16 >>
+        code: length_data!( be_u32 ) >>
+        ( (max_stack, max_locals, code) )
+    ))?;
+    let (rem, raw_handlers) = ctx.run(table, length_count!(table, be_u16, exception_table))?;
+
+    let mut exception_handlers = Vec::with_capacity(raw_handlers.len());
+    for (idx, (start_pc, end_pc, handler_pc, catch_type)) in raw_handlers.into_iter().enumerate() {
+        let catch_type = if catch_type == 0 {
+            None
+        } else {
+            let name = constants.class_name_cow(catch_type)
+                .map_err(|err| ctx.error(&table[2 + idx * 8 + 6..], ErrorReason::InvalidConstant(err)))?;
+            Some(name.clone())
+        };
+        exception_handlers.push(ExceptionHandler { start_pc, end_pc, handler_pc, catch_type });
+    }
+
+    let (rem, attributes) = attributes(ctx, rem, constants)?;
+    ctx.expect_end(body, rem)?;
+
+    Ok(Attribute::CodeAttribute(CodeBlock { max_stack, max_locals, code: Cow::Borrowed(code), exception_handlers, attributes }))
 }
 
-named_args!(
-    field<'a>(constants: &'a Vec<ConstantType<'this_is_probably_unique_i_hope_please>>)<Field<'this_is_probably_unique_i_hope_please>>,
+/// parses the body of an attribute, which is exactly the `attribute_length` bytes following the header
+fn select_attribute<'i>(ctx: &mut Context<'i>, body: &'i [u8], name: &str, constants: &Constants<'_, 'i>) -> Result<Attribute<'i>, ClassFileError> {
+    match name {
+        "LineNumberTable" => {
+            let (rem, line_numbers) = ctx.run(body, line_number_table(body))?;
+            ctx.expect_end(body, rem)?;
+            Ok(line_numbers)
+        }
+        "Code" => code_attribute(ctx, body, constants),
+        "ConstantValue" => constant_value(ctx, body, constants),
+        "StackMapTable" => stack_map_table(ctx, body, constants),
+        "LocalVariableTable" => local_variables(ctx, body, constants).map(Attribute::LocalVariableTable),
+        "LocalVariableTypeTable" => local_variables(ctx, body, constants).map(Attribute::LocalVariableTypeTable),
+        "RuntimeVisibleAnnotations" => annotations(ctx, body, constants).map(Attribute::RuntimeVisibleAnnotations),
+        "RuntimeInvisibleAnnotations" => annotations(ctx, body, constants).map(Attribute::RuntimeInvisibleAnnotations),
+        "RuntimeVisibleParameterAnnotations" =>
+            parameter_annotations(ctx, body, constants).map(Attribute::RuntimeVisibleParameterAnnotations),
+        "RuntimeInvisibleParameterAnnotations" =>
+            parameter_annotations(ctx, body, constants).map(Attribute::RuntimeInvisibleParameterAnnotations),
+        "RuntimeVisibleTypeAnnotations" => type_annotations(ctx, body, constants).map(Attribute::RuntimeVisibleTypeAnnotations),
+        "RuntimeInvisibleTypeAnnotations" => type_annotations(ctx, body, constants).map(Attribute::RuntimeInvisibleTypeAnnotations),
+        "AnnotationDefault" => {
+            let (rem, value) = element_value(ctx, body, constants, 0)?;
+            ctx.expect_end(body, rem)?;
+            Ok(Attribute::AnnotationDefault(value))
+        }
+        "BootstrapMethods" => bootstrap_methods(ctx, body, constants),
+        "Module" => module_attribute(ctx, body, constants),
+        "InnerClasses" => inner_classes(ctx, body, constants),
+        "Record" => record(ctx, body, constants),
+        "PermittedSubclasses" => {
+            let (rem, classes) = resolve_list(ctx, body, |index| constants.class_name_cow(index))?;
+            ctx.expect_end(body, rem)?;
+            Ok(Attribute::PermittedSubclasses(classes))
+        }
+        "EnclosingMethod" => enclosing_method(ctx, body, constants),
+        "NestHost" => {
+            let (rem, index) = ctx.run(body, be_u16(body))?;
+            ctx.expect_end(body, rem)?;
+            let host = constants.class_name_cow(index).map_err(|err| ctx.error(body, ErrorReason::InvalidConstant(err)))?;
+            Ok(Attribute::NestHost(host.clone()))
+        }
+        "NestMembers" => {
+            let (rem, members) = resolve_list(ctx, body, |index| constants.class_name_cow(index))?;
+            ctx.expect_end(body, rem)?;
+            Ok(Attribute::NestMembers(members))
+        }
+        "SourceFile" => {
+            let (rem, index) = ctx.run(body, be_u16(body))?;
+            ctx.expect_end(body, rem)?;
+            let name = constants.utf8_cow(index).map_err(|err| ctx.error(body, ErrorReason::InvalidConstant(err)))?;
+            Ok(Attribute::SourceFile(name.clone()))
+        }
+        "SourceDebugExtension" => Ok(Attribute::SourceDebugExtension(Cow::Borrowed(body))),
+        "ModulePackages" => {
+            let (rem, packages) = resolve_list(ctx, body, |index| constants.package_name_cow(index))?;
+            ctx.expect_end(body, rem)?;
+            Ok(Attribute::ModulePackages(packages))
+        }
+        "ModuleMainClass" => {
+            let (rem, index) = ctx.run(body, be_u16(body))?;
+            ctx.expect_end(body, rem)?;
+            let main_class = constants.class_name_cow(index).map_err(|err| ctx.error(body, ErrorReason::InvalidConstant(err)))?;
+            Ok(Attribute::ModuleMainClass(main_class.clone()))
+        }
+        "Signature" => {
+            let (rem, index) = ctx.run(body, be_u16(body))?;
+            ctx.expect_end(body, rem)?;
+            let signature = constants.utf8_cow(index).map_err(|err| ctx.error(body, ErrorReason::InvalidConstant(err)))?;
+            Ok(Attribute::Signature(signature.clone()))
+        }
+        _ => Ok(Attribute::GenericAttribute { name: String::from(name), info: Cow::Borrowed(body) })
+    }
+}
+
+fn attribute<'i>(ctx: &mut Context<'i>, input: &'i [u8], constants: &Constants<'_, 'i>) -> Result<(&'i [u8], Attribute<'i>), ClassFileError> {
+    let (rem, name_index) = ctx.run(input, be_u16(input))?;
+    let name = constants.utf8(name_index).map_err(|err| ctx.error(input, ErrorReason::InvalidConstant(err)))?;
+
+    ctx.enter(Structure::Attribute(String::from(name)));
+    let (rem, body) = ctx.run(rem, length_data!(rem, be_u32))?;
+    let attribute = select_attribute(ctx, body, name, constants)?;
+    ctx.leave();
+
+    Ok((rem, attribute))
+}
+
+fn attributes<'i>(ctx: &mut Context<'i>, input: &'i [u8], constants: &Constants<'_, 'i>) -> Result<(&'i [u8], Vec<Attribute<'i>>), ClassFileError> {
+    let (mut input, count) = ctx.run(input, be_u16(input))?;
+    let mut attributes = Vec::with_capacity(usize::from(count));
+    for _ in 0..count {
+        let (rem, attribute) = attribute(ctx, input, constants)?;
+        attributes.push(attribute);
+        input = rem;
+    }
+
+    Ok((input, attributes))
+}
+
+named!(
+    member_header<(u16, u16, u16)>,
     do_parse!(
         access_flags:     be_u16 >>
         name_index:       be_u16 >>
         descriptor_index: be_u16 >>
-        attributes_count: be_u16 >>
-        attributes:       count!( call!(attribute, constants), attributes_count as usize ) >>
-        ( Field { access_flags, name_index, descriptor_index, attributes } )
+        ( (access_flags, name_index, descriptor_index) )
     )
 );
 
+fn field<'i>(ctx: &mut Context<'i>, input: &'i [u8], constants: &Constants<'_, 'i>) -> Result<(&'i [u8], Field<'i>), ClassFileError> {
+    let (rem, (access_flags, name_index, descriptor_index)) = ctx.run(input, member_header(input))?;
+    let name = constants.utf8_cow(name_index).map_err(|err| ctx.error(&input[2..], ErrorReason::InvalidConstant(err)))?;
+    let descriptor = constants.utf8_cow(descriptor_index).map_err(|err| ctx.error(&input[4..], ErrorReason::InvalidConstant(err)))?;
+    let (rem, attributes) = attributes(ctx, rem, constants)?;
+
+    Ok((rem, Field { access_flags, name: name.clone(), descriptor: descriptor.clone(), attributes }))
+}
+
+fn method<'i>(ctx: &mut Context<'i>, input: &'i [u8], constants: &Constants<'_, 'i>) -> Result<(&'i [u8], Method<'i>), ClassFileError> {
+    let (rem, (access_flags, name_index, descriptor_index)) = ctx.run(input, member_header(input))?;
+    let name = constants.utf8_cow(name_index).map_err(|err| ctx.error(&input[2..], ErrorReason::InvalidConstant(err)))?;
+    let descriptor = constants.utf8_cow(descriptor_index).map_err(|err| ctx.error(&input[4..], ErrorReason::InvalidConstant(err)))?;
+    let (rem, attributes) = attributes(ctx, rem, constants)?;
+
+    Ok((rem, Method { access_flags, name: name.clone(), descriptor: descriptor.clone(), attributes }))
+}
+
+fn field_table<'i>(ctx: &mut Context<'i>, input: &'i [u8], constants: &Constants<'_, 'i>) -> Result<(&'i [u8], Vec<Field<'i>>), ClassFileError> {
+    let (mut input, count) = ctx.run(input, be_u16(input))?;
+    let mut fields = Vec::with_capacity(usize::from(count));
+    for idx in 0..count {
+        ctx.enter(Structure::Field(idx));
+        let (rem, field) = field(ctx, input, constants)?;
+        ctx.leave();
+        fields.push(field);
+        input = rem;
+    }
+
+    Ok((input, fields))
+}
+
+fn method_table<'i>(ctx: &mut Context<'i>, input: &'i [u8], constants: &Constants<'_, 'i>) -> Result<(&'i [u8], Vec<Method<'i>>), ClassFileError> {
+    let (mut input, count) = ctx.run(input, be_u16(input))?;
+    let mut methods = Vec::with_capacity(usize::from(count));
+    for idx in 0..count {
+        ctx.enter(Structure::Method(idx));
+        let (rem, method) = method(ctx, input, constants)?;
+        ctx.leave();
+        methods.push(method);
+        input = rem;
+    }
+
+    Ok((input, methods))
+}
+
+named!(skip_attribute<()>, do_parse!( be_u16 >> length_data!(be_u32) >> ( () ) ));
+
+named!(skip_member<()>, do_parse!( member_header >> length_count!(be_u16, skip_attribute) >> ( () ) ));
+
+/// splits off a table of `count` entries without decoding them
+fn split_table<'i, F>(ctx: &Context<'i>, input: &'i [u8], entry: F) -> Result<(&'i [u8], &'i [u8]), ClassFileError>
+    where F: Fn(&'i [u8]) -> IResult<&'i [u8], ()> {
+    let (rem, _) = ctx.run(input, length_count!(input, be_u16, entry))?;
+    Ok((rem, &input[..input.len() - rem.len()]))
+}
 
-named_args!(
-    method<'a>(constants: &'a Vec<ConstantType<'this_is_probably_unique_i_hope_please>>)<Method<'this_is_probably_unique_i_hope_please>>,
+named!(
+    class_header<(u16, u16, u16)>,
     do_parse!(
-        access_flags:     be_u16 >>
-        name_index:       be_u16 >>
-        descriptor_index: be_u16 >>
-        attributes_count: be_u16 >>
-        attributes:       count!( call!(attribute, constants), attributes_count as usize ) >>
-        ( Method {
-            access_flags,
-            name: match constants.get(usize::from(name_index - 1)).unwrap() {
-                ConstantType::Utf8 { value: str } => str,
-                _ => panic!("wrong constant type")
-            },
-            descriptor: match constants.get(usize::from(descriptor_index - 1)).unwrap() {
-                ConstantType::Utf8 { value: str } => str,
-                _ => panic!("wrong constant type")
-            },
-            attributes
-          }
-        )
+        access_flags: be_u16 >>
+        this_index:   be_u16 >>
+        super_index:  be_u16 >>
+        ( (access_flags, this_index, super_index) )
     )
 );
 
-named!(
-    pub read_class_file<ClassFile>,
-    dbg!(do_parse!(
-        tag!(&[0xCAu8, 0xFEu8, 0xBAu8, 0xBEu8][..]) >>
-        minor:              be_u16    >>
-        major:              be_u16    >>
-        constants_length:   be_u16    >>
-        constants:          count!( constant, constants_length as usize - 1) >>
-        access_flags:       be_u16    >>
-        this_index:         be_u16    >>
-        super_index:        be_u16    >>
-        interfaces_count:   be_u16    >>
-        interfaces:         count!( be_u16, interfaces_count as usize ) >>
-        fields_count:       be_u16    >>
-        fields:             count!( call!(field, &constants), fields_count as usize ) >>
-        methods_count:      be_u16    >>
-        methods:            count!( call!(method, &constants), methods_count as usize ) >>
-        attributes_count:   be_u16    >>
-        attributes:         count!( call!(attribute, &constants), attributes_count as usize ) >>
-        ( ClassFile { version: (major, minor), constants, access_flags, this_index, super_index, interfaces, fields, methods, attributes } )
-    ))
-);
+/// the version, the constant pool, access flags, this and super class and the interfaces
+type Header<'i> = ((u16, u16), ConstantPool<'i>, (u16, u16, u16), Vec<u16>);
+
+fn header<'i>(ctx: &mut Context<'i>, input: &'i [u8]) -> Result<(&'i [u8], Header<'i>), ClassFileError> {
+    ctx.enter(Structure::Header);
+    if input.len() >= MAGIC.len() && input[..MAGIC.len()] != MAGIC {
+        return Err(ctx.error(input, ErrorReason::InvalidMagic));
+    }
+    let (rem, (minor, major)) = ctx.run(input, do_parse!(input,
+        tag!(&MAGIC[..]) >>
+        minor: be_u16 >>
+        major: be_u16 >>
+        ( (minor, major) )
+    ))?;
+    ctx.leave();
+
+    let (rem, constants) = constant_pool(ctx, rem)?;
+
+    ctx.enter(Structure::Header);
+    let (rem, (access_flags, this_index, super_index)) = ctx.run(rem, class_header(rem))?;
+    ctx.leave();
+
+    ctx.enter(Structure::Interfaces);
+    let (rem, interfaces) = ctx.run(rem, length_count!(rem, be_u16, be_u16))?;
+    ctx.leave();
+
+    Ok((rem, ((major, minor), constants, (access_flags, this_index, super_index), interfaces)))
+}
+
+pub fn read_class_file(input: &[u8]) -> Result<ClassFile<'_>, ClassFileError> {
+    let mut ctx = Context::new(input);
+    let (rem, (version, constants, (access_flags, this_index, super_index), interfaces)) = header(&mut ctx, input)?;
+    let resolved = Constants::new(&constants);
+    let (rem, fields) = field_table(&mut ctx, rem, &resolved)?;
+    let (rem, methods) = method_table(&mut ctx, rem, &resolved)?;
+    let (rem, attributes) = attributes(&mut ctx, rem, &resolved)?;
+    if !rem.is_empty() {
+        return Err(ctx.error(rem, ErrorReason::TrailingBytes { count: rem.len() }));
+    }
+
+    let references = resolved.references.into_inner();
+    Ok(ClassFile { version, constants, references, access_flags, this_index, super_index, interfaces, fields, methods, attributes })
+}
+
+/// decodes the header and the constant pool. the field, method and attribute tables are only
+/// checked to be complete, they are decoded when they are queried.
+pub fn read_class_file_lazy(input: &[u8]) -> Result<LazyClassFile<'_>, ClassFileError> {
+    let mut ctx = Context::new(input);
+    let (rem, (version, constants, (access_flags, this_index, super_index), interfaces)) = header(&mut ctx, input)?;
+    let (rem, field_table) = split_table(&ctx, rem, skip_member)?;
+    let (rem, method_table) = split_table(&ctx, rem, skip_member)?;
+    let (rem, attribute_table) = split_table(&ctx, rem, skip_attribute)?;
+    if !rem.is_empty() {
+        return Err(ctx.error(rem, ErrorReason::TrailingBytes { count: rem.len() }));
+    }
+
+    Ok(LazyClassFile {
+        version, constants, access_flags, this_index, super_index, interfaces,
+        input, field_table, method_table, attribute_table,
+    })
+}
+
+/// the fields, methods and attributes of a class file, and the constant pool references in them
+pub type Tables<'i> = (Vec<Field<'i>>, Vec<Method<'i>>, Vec<Attribute<'i>>, Vec<u16>);
+
+/// decodes the field, method and attribute tables split off by `read_class_file_lazy`
+pub fn read_tables<'i>(input: &'i [u8], (field_bytes, method_bytes, attribute_bytes): (&'i [u8], &'i [u8], &'i [u8]), constants: &ConstantPool<'i>) -> Result<Tables<'i>, ClassFileError> {
+    let mut ctx = Context::new(input);
+    let resolved = Constants::new(constants);
+    let (_, fields) = field_table(&mut ctx, field_bytes, &resolved)?;
+    let (_, methods) = method_table(&mut ctx, method_bytes, &resolved)?;
+    let (_, attributes) = attributes(&mut ctx, attribute_bytes, &resolved)?;
+
+    Ok((fields, methods, attributes, resolved.references.into_inner()))
+}
+
+/// decodes a field table split off by `read_class_file_lazy`
+pub fn read_fields<'i>(input: &'i [u8], table: &'i [u8], constants: &ConstantPool<'i>) -> Result<Vec<Field<'i>>, ClassFileError> {
+    field_table(&mut Context::new(input), table, &Constants::new(constants)).map(|(_, fields)| fields)
+}
+
+pub fn read_methods<'i>(input: &'i [u8], table: &'i [u8], constants: &ConstantPool<'i>) -> Result<Vec<Method<'i>>, ClassFileError> {
+    method_table(&mut Context::new(input), table, &Constants::new(constants)).map(|(_, methods)| methods)
+}
+
+pub fn read_attributes<'i>(input: &'i [u8], table: &'i [u8], constants: &ConstantPool<'i>) -> Result<Vec<Attribute<'i>>, ClassFileError> {
+    attributes(&mut Context::new(input), table, &Constants::new(constants)).map(|(_, attributes)| attributes)
+}
+
+/// decodes only the method with the given name and descriptor, the others are skipped
+pub fn read_method<'i>(input: &'i [u8], table: &'i [u8], constants: &ConstantPool<'i>, name: &str, descriptor: &str) -> Result<Option<Method<'i>>, ClassFileError> {
+    let mut ctx = Context::new(input);
+    let (mut rem, count) = ctx.run(table, be_u16(table))?;
+    for idx in 0..count {
+        let (_, (_, name_index, descriptor_index)) = ctx.run(rem, member_header(rem))?;
+        if constants.utf8(name_index).ok() == Some(name) && constants.utf8(descriptor_index).ok() == Some(descriptor) {
+            ctx.enter(Structure::Method(idx));
+            let (_, method) = method(&mut ctx, rem, &Constants::new(constants))?;
+            return Ok(Some(method));
+        }
+
+        let (next, _) = ctx.run(rem, skip_member(rem))?;
+        rem = next;
+    }
+
+    Ok(None)
+}
+
+/// parses a field descriptor like `[Ljava/lang/String;`
+pub fn read_field_descriptor(descriptor: &str) -> Result<ValueType, ClassFileError> {
+    let input = descriptor.as_bytes();
+    let ctx = Context { input, path: vec![Structure::Descriptor(String::from(descriptor))] };
+    let (rem, value_type) = ctx.run(input, parse_type(input))?;
+    if !rem.is_empty() {
+        return Err(ctx.error(rem, ErrorReason::TrailingBytes { count: rem.len() }));
+    }
+
+    Ok(value_type)
+}
+
+/// parses a method descriptor like `(ILjava/lang/String;)V`
+pub fn read_method_descriptor(descriptor: &str) -> Result<(Vec<ValueType>, ValueType), ClassFileError> {
+    let input = descriptor.as_bytes();
+    let ctx = Context { input, path: vec![Structure::Descriptor(String::from(descriptor))] };
+    let (rem, desc) = ctx.run(input, method_desc(input))?;
+    if !rem.is_empty() {
+        return Err(ctx.error(rem, ErrorReason::TrailingBytes { count: rem.len() }));
+    }
+
+    Ok(desc)
+}
+
+/// parses a class signature like `<T:Ljava/lang/Object;>Ljava/lang/Object;Ljava/lang/Comparable<TT;>;`
+pub fn read_class_signature(signature: &str) -> Result<ClassSignature, ClassFileError> {
+    let input = signature.as_bytes();
+    let ctx = Context { input, path: vec![Structure::Signature(String::from(signature))] };
+    let (mut rem, (type_parameters, superclass)) = ctx.run(input, do_parse!(input,
+        type_parameters: opt!(type_parameters) >>
+        superclass: call!(class_type_signature, 0) >>
+        ( (type_parameters.unwrap_or_default(), superclass) )
+    ))?;
+
+    // the interfaces run until the end of the input, so many0! would ask for more
+    let mut interfaces = Vec::new();
+    while !rem.is_empty() {
+        let (next, interface) = ctx.run(rem, class_type_signature(rem, 0))?;
+        interfaces.push(interface);
+        rem = next;
+    }
+
+    Ok(ClassSignature { type_parameters, superclass, interfaces })
+}
+
+/// parses a method signature like `<T:Ljava/lang/Object;>(TT;)V^Ljava/io/IOException;`
+pub fn read_method_signature(signature: &str) -> Result<MethodSignature, ClassFileError> {
+    let input = signature.as_bytes();
+    let ctx = Context { input, path: vec![Structure::Signature(String::from(signature))] };
+    let (mut rem, (type_parameters, parameters, return_type)) = ctx.run(input, method_signature(input))?;
+
+    let mut throws = Vec::new();
+    while !rem.is_empty() {
+        let (next, exception) = ctx.run(rem, throws_signature(rem))?;
+        throws.push(exception);
+        rem = next;
+    }
+
+    Ok(MethodSignature { type_parameters, parameters, return_type, throws })
+}
+
+/// parses a field signature like `Ljava/util/List<Ljava/lang/String;>;`
+pub fn read_field_signature(signature: &str) -> Result<TypeSignature, ClassFileError> {
+    let input = signature.as_bytes();
+    let ctx = Context { input, path: vec![Structure::Signature(String::from(signature))] };
+    let (rem, field_type) = ctx.run(input, reference_type_signature(input, 0))?;
+    if !rem.is_empty() {
+        return Err(ctx.error(rem, ErrorReason::TrailingBytes { count: rem.len() }));
+    }
+
+    Ok(field_type)
+}
 
 
 #[cfg(test)]
 mod test {
-    use super::read_class_file;
+    use super::{read_class_file, select_attribute, Constants, Context};
+    use java::class_file::{Attribute, ConstantPool};
     use java::class_file::ClassFile;
+    use java::samples::{self, INTERFACES, LAMBDAS, LOCAL_VARIABLES, SHAPES, SHAPES_POLYGON, TRY_CATCH, WIDE_CONSTANTS};
+    use java::class_file::{ClassAccess, ConstantPoolError, ConstantType, ConstantValue, ExceptionHandler, FieldAccess, ReferenceKind};
+    use std::collections::HashSet;
+    use java::class_file::error::{ErrorReason, Structure};
+    use std::borrow::Cow;
 
-    const CLASSFILE: &'static [u8] = include_bytes!("../../../sample/HelloWorld.class");
-    const DEMOCLASS: &'static [u8] = include_bytes!("../../../sample/DemoClass.class");
+    const CLASSFILE: &[u8] = samples::HELLO_WORLD;
 
 
     fn get_cf<'a>() -> ClassFile<'a> {
-        read_class_file(CLASSFILE).unwrap().1
+        read_class_file(CLASSFILE).unwrap()
     }
 
     #[test]
     fn it_can_read_the_complete_class_file() {
-        let cf = read_class_file(CLASSFILE).unwrap();
+        let cf = read_class_file(CLASSFILE);
         match cf {
-            ([], _) => (),
-            _ => {
-                println!("{:?}", cf);
-                panic!("cannot read class file")
-            }
+            Ok(_) => (),
+            Err(err) => panic!("cannot read class file: {}", err)
         };
     }
 
@@ -317,7 +1437,249 @@ mod test {
 
     #[test]
     fn it_gets_the_class_name_correct() {
-        assert_eq!("HelloWorld", get_cf().get_class_name())
+        assert_eq!("HelloWorld", get_cf().get_class_name().unwrap())
+    }
+
+    #[test]
+    fn it_resolves_the_class_hierarchy() {
+        let cf = read_class_file(INTERFACES).unwrap();
+        assert_eq!(Some("java/lang/Object"), cf.super_class_name().unwrap());
+        assert_eq!(vec!["java/lang/Runnable", "java/io/Serializable"], cf.interface_names().unwrap());
+        assert_eq!(
+            vec![ClassAccess::Super, ClassAccess::Abstract].into_iter().collect::<HashSet<ClassAccess>>(),
+            cf.get_access()
+        );
+
+        let mut object = get_cf();
+        object.super_index = 0;
+        assert_eq!(None, object.super_class_name().unwrap());
+        assert!(object.interface_names().unwrap().is_empty());
+    }
+
+    #[test]
+    fn it_resolves_fields() {
+        let cf = get_cf();
+        let text = &cf.fields[0];
+        assert_eq!("text", text.name);
+        assert_eq!(ValueType::Object(String::from("java/lang/String")), text.get_type().unwrap());
+        assert_eq!(
+            vec![FieldAccess::Private, FieldAccess::Static, FieldAccess::Final].into_iter().collect::<HashSet<FieldAccess>>(),
+            text.get_access()
+        );
+        assert_eq!(Some(&ConstantValue::String(Cow::Borrowed("Hello World!"))), text.constant_value());
+    }
+
+    #[test]
+    fn it_keeps_indexes_after_long_and_double_constants() {
+        let cf = read_class_file(WIDE_CONSTANTS).unwrap();
+        assert_eq!("WideConstants", cf.get_class_name().unwrap());
+        match cf.get_constant(7) {
+            Ok(ConstantType::Long { value: 1234567890123 }) => (),
+            other => panic!("unexpected {:?}", other)
+        }
+        assert!(cf.get_constant(8).is_err());
+        match cf.get_constant(21) {
+            Ok(ConstantType::String { string_index }) =>
+                assert_eq!("after the wide constants", cf.constants.utf8(*string_index).unwrap()),
+            other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn it_reads_dynamic_constants() {
+        // a `Dynamic` constant followed by the `Integer` 7
+        let input = [0, 3, 17, 0, 1, 0, 2, 3, 0, 0, 0, 7];
+        let (rest, pool) = constant_pool(&mut Context::new(&input), &input).unwrap();
+        assert!(rest.is_empty());
+        match pool.get(1) {
+            Ok(ConstantType::Dynamic { bootstrap_method_attr_index: 1, name_and_type_index: 2 }) => (),
+            other => panic!("unexpected {:?}", other)
+        }
+        assert_eq!("Dynamic", pool.get(1).unwrap().name());
+    }
+
+    #[test]
+    fn it_keeps_the_exception_table() {
+        let cf = read_class_file(TRY_CATCH).unwrap();
+        let divide = cf.methods.iter().find(|m| m.name == "divide").unwrap().get_code().unwrap();
+        assert_eq!(
+            &[ExceptionHandler { start_pc: 0, end_pc: 3, handler_pc: 4, catch_type: Some(Cow::Borrowed("java/lang/ArithmeticException")) }],
+            divide.exception_handlers()
+        );
+
+        let cleanup = cf.methods.iter().find(|m| m.name == "cleanup").unwrap().get_code().unwrap();
+        assert_eq!(None, cleanup.exception_handlers()[0].catch_type);
+        assert_eq!(1, cleanup.handlers_at(5).count());
+        assert_eq!(0, cleanup.handlers_at(6).count());
+    }
+
+    #[test]
+    fn it_finds_local_variable_names() {
+        let cf = read_class_file(LOCAL_VARIABLES).unwrap();
+        let code = cf.methods.iter().find(|m| m.name == "testMe").unwrap().get_code().unwrap();
+
+        let summe = code.local_variable(0, 20).unwrap();
+        assert_eq!(("summe", "I"), (&*summe.name, &*summe.descriptor));
+        assert_eq!("i", code.local_variable(2, 12).unwrap().name);
+        assert_eq!(None, code.local_variable(2, 40));
+
+        assert_eq!("Ljava/util/List;", code.local_variable(1, 20).unwrap().descriptor);
+        assert_eq!(Some("Ljava/util/List<Ljava/lang/String;>;"), code.local_variable_signature(1, 20));
+        assert_eq!(None, code.local_variable_signature(0, 20));
+    }
+
+    #[test]
+    fn it_resolves_invoke_dynamic_constants() {
+        let cf = read_class_file(LAMBDAS).unwrap();
+        assert_eq!(2, cf.bootstrap_methods().unwrap().len());
+
+        let lambda = cf.invoke_dynamic(7).unwrap();
+        assert_eq!(("apply", "()Ljava/util/function/Function;"), (lambda.name, lambda.descriptor));
+        assert_eq!(ReferenceKind::InvokeStatic, lambda.bootstrap.kind);
+        assert_eq!(("java/lang/invoke/LambdaMetafactory", "metafactory"), (lambda.bootstrap.member.class_name, lambda.bootstrap.member.name));
+        assert_eq!(3, lambda.arguments.len());
+        match lambda.arguments[1] {
+            ConstantType::MethodHandle { reference_kind: 6, .. } => (),
+            other => panic!("unexpected {:?}", other)
+        }
+
+        let concat = cf.invoke_dynamic(18).unwrap();
+        assert_eq!("makeConcatWithConstants", concat.bootstrap.member.name);
+        match concat.arguments[0] {
+            ConstantType::String { string_index } => assert_eq!("\u{1} x\u{1}", cf.constants.utf8(*string_index).unwrap()),
+            other => panic!("unexpected {:?}", other)
+        }
+
+        match cf.invoke_dynamic(8) {
+            Err(ClassFileError { reason: ErrorReason::InvalidConstant(ConstantPoolError::WrongType { index: 8, expected: "InvokeDynamic", found: "NameAndType" }), .. }) => (),
+            other => panic!("unexpected {:?}", other)
+        }
+        match cf.dynamic_constant(7) {
+            Err(ClassFileError { reason: ErrorReason::InvalidConstant(ConstantPoolError::WrongType { index: 7, expected: "Dynamic", found: "InvokeDynamic" }), .. }) => (),
+            other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn it_reads_sealed_interfaces_and_records() {
+        let shapes = read_class_file(SHAPES).unwrap();
+        assert_eq!((61, 0), shapes.version);
+        assert!(shapes.is_sealed().unwrap());
+        assert!(!shapes.is_record().unwrap());
+        assert_eq!(vec!["Shapes$Circle", "Shapes$Polygon"], *shapes.permitted_subclasses().unwrap());
+
+        let polygon = read_class_file(SHAPES_POLYGON).unwrap();
+        assert!(polygon.is_record().unwrap());
+        assert!(!polygon.is_sealed().unwrap());
+        assert_eq!(Some("java/lang/Record"), polygon.super_class_name().unwrap());
+
+        let components = polygon.record_components().unwrap().unwrap();
+        assert_eq!(vec!["name", "points"], components.iter().map(|c| &*c.name).collect::<Vec<&str>>());
+        assert_eq!(ValueType::Object(String::from("java/lang/String")), components[0].get_type().unwrap());
+        assert!(components[0].annotation("LShapes$Tag;").is_some());
+        assert_eq!("java.util.List<int[]>", components[1].generic_signature().unwrap().unwrap().to_string());
+    }
+
+    #[test]
+    fn it_rejects_stack_map_frames_beyond_the_largest_offset() {
+        let body: &[u8] = &[0x00, 0x02, 251, 0xff, 0xff, 251, 0xff, 0xff];
+        let mut ctx = Context::new(body);
+        let err = select_attribute(&mut ctx, body, "StackMapTable", &Constants::new(&ConstantPool::new())).unwrap_err();
+        assert_eq!(5, err.offset);
+        match err.reason {
+            ErrorReason::FrameOffset { frame: 1 } => (),
+            other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn it_limits_the_nesting_of_annotation_values() {
+        // `depth` arrays of one element each around an empty array
+        let nested = |depth: usize| {
+            let mut body: Vec<u8> = (0..depth).flat_map(|_| b"[\x00\x01".iter().cloned()).collect();
+            body.extend_from_slice(b"[\x00\x00");
+            body
+        };
+
+        let body = nested(127);
+        let mut ctx = Context::new(&body);
+        assert!(select_attribute(&mut ctx, &body, "AnnotationDefault", &Constants::new(&ConstantPool::new())).is_ok());
+
+        let body = nested(128);
+        let mut ctx = Context::new(&body);
+        let err = select_attribute(&mut ctx, &body, "AnnotationDefault", &Constants::new(&ConstantPool::new())).unwrap_err();
+        assert_eq!(128 * 3, err.offset);
+        match err.reason {
+            ErrorReason::NestedTooDeep { max: 128 } => (),
+            other => panic!("unexpected {:?}", other)
+        }
+
+        // far too deep to parse recursively
+        let body = nested(1_000_000);
+        let mut ctx = Context::new(&body);
+        assert!(select_attribute(&mut ctx, &body, "AnnotationDefault", &Constants::new(&ConstantPool::new())).is_err());
+    }
+
+    #[test]
+    fn it_keeps_the_source_debug_extension_as_bytes() {
+        // not modified UTF-8, which some tools write anyway
+        let body: &[u8] = b"SMAP\nHello.java\n\xff\x00*E\n";
+        let mut ctx = Context::new(body);
+        match select_attribute(&mut ctx, body, "SourceDebugExtension", &Constants::new(&ConstantPool::new())) {
+            Ok(Attribute::SourceDebugExtension(value)) => assert_eq!(body, &*value),
+            other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn it_reports_truncated_class_files() {
+        let err = read_class_file(&CLASSFILE[..CLASSFILE.len() - 3]).err().unwrap();
+        assert_eq!(CLASSFILE.len() - 3, err.offset);
+        assert_eq!(vec![Structure::Attribute(String::from("SourceFile"))], err.structure);
+        match err.reason {
+            ErrorReason::UnexpectedEnd => (),
+            other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn it_reports_a_wrong_magic_number() {
+        let mut bytes = CLASSFILE.to_vec();
+        bytes[0] = 0xCB;
+        match read_class_file(&bytes).err().unwrap().reason {
+            ErrorReason::InvalidMagic => (),
+            other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn it_reports_the_broken_constant() {
+        let mut bytes = CLASSFILE.to_vec();
+        // constant #1 starts right after magic, version and the constant pool count
+        bytes[10] = 2;
+        let err = read_class_file(&bytes).err().unwrap();
+        assert_eq!(10, err.offset);
+        assert_eq!(vec![Structure::Constant(1)], err.structure);
+        match err.reason {
+            ErrorReason::UnknownConstantTag { tag: 2 } => (),
+            other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn it_reports_invalid_attribute_names() {
+        let code_offset = 0x1b2;
+        let mut bytes = CLASSFILE.to_vec();
+        // name index of the code attribute of `main`
+        bytes[code_offset] = 0;
+        bytes[code_offset + 1] = 0;
+        let err = read_class_file(&bytes).err().unwrap();
+        assert_eq!(code_offset, err.offset);
+        assert_eq!(vec![Structure::Method(1)], err.structure);
+        match err.reason {
+            ErrorReason::InvalidConstant(ConstantPoolError::InvalidIndex { index: 0 }) => (),
+            other => panic!("unexpected {:?}", other)
+        }
     }
 
 
@@ -347,4 +1709,129 @@ mod test {
             _ => assert_eq!(true, false)
         };
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_method_desc_errors() {
+        let err = read_method_descriptor("(I").err().unwrap();
+        assert_eq!(2, err.offset);
+        assert_eq!(vec![Structure::Descriptor(String::from("(I"))], err.structure);
+        match err.reason {
+            ErrorReason::UnexpectedEnd => (),
+            other => panic!("unexpected {:?}", other)
+        }
+
+        assert!(read_method_descriptor("I)V").is_err());
+        assert!(read_method_descriptor("").is_err());
+        assert!(read_method_descriptor("()VV").is_err());
+    }
+
+    #[test]
+    fn test_method_desc_all_types() {
+        let (args, ret) = read_method_descriptor("(BCDFIJLjava/lang/String;SZ[[I)J").unwrap();
+        assert_eq!(vec![
+            ValueType::Byte,
+            ValueType::Char,
+            ValueType::Double,
+            ValueType::Float,
+            ValueType::Integer,
+            ValueType::Long,
+            ValueType::Object(String::from("java/lang/String")),
+            ValueType::Short,
+            ValueType::Boolean,
+            ValueType::Array(Box::new(ValueType::Array(Box::new(ValueType::Integer)))),
+        ], args);
+        assert_eq!(ValueType::Long, ret);
+    }
+
+    #[test]
+    fn test_descriptor_rejects_unknown_types() {
+        assert!(read_method_descriptor("(X)V").is_err());
+        assert!(read_method_descriptor("(V)V").is_err());
+        assert!(read_method_descriptor("(L;)V").is_err());
+        assert!(read_method_descriptor("(Ljava/lang/String)V").is_err());
+        assert!(read_field_descriptor("V").is_err());
+        assert_eq!(ValueType::Long, read_field_descriptor("J").unwrap());
+    }
+
+    #[test]
+    fn test_descriptor_limits_array_dimensions() {
+        let deepest = format!("{}I", "[".repeat(255));
+        assert!(read_field_descriptor(&deepest).is_ok());
+
+        let err = read_field_descriptor(&format!("[{}", deepest)).unwrap_err();
+        assert_eq!(255, err.offset);
+        match err.reason {
+            ErrorReason::TooManyDimensions { max: 255 } => (),
+            other => panic!("unexpected {:?}", other)
+        }
+
+        // far too many to parse recursively
+        let descriptor = format!("({}I)V", "[".repeat(65000));
+        assert!(read_method_descriptor(&descriptor).is_err());
+    }
+
+    #[test]
+    fn test_overload_signatures() {
+        let cf = read_class_file(samples::DEMO_CLASS).unwrap();
+        let arguments = cf.methods.iter()
+            .filter(|method| method.name == "overload")
+            .map(|method| method.get_signature().unwrap().arguments.remove(0).to_java())
+            .collect::<Vec<String>>();
+        assert_eq!(vec!["byte", "short", "int", "long", "float", "double", "char", "boolean"], arguments);
+    }
+
+    #[test]
+    fn lazy_class_files_answer_the_same_queries() {
+        let lazy = super::read_class_file_lazy(INTERFACES).unwrap();
+        let eager = read_class_file(INTERFACES).unwrap();
+        assert_eq!(eager.get_class_name().unwrap(), lazy.get_class_name().unwrap());
+        assert_eq!(Some("java/lang/Object"), lazy.super_class_name().unwrap());
+        assert_eq!(vec!["java/lang/Runnable", "java/io/Serializable"], lazy.interface_names().unwrap());
+        assert_eq!(eager.get_access(), lazy.get_access());
+        assert_eq!(eager.methods().unwrap().len(), lazy.methods().unwrap().len());
+        assert_eq!(eager.attributes().unwrap().len(), lazy.attributes().unwrap().len());
+
+        let lazy = super::read_class_file_lazy(samples::DEMO_CLASS).unwrap();
+        let method = lazy.find_method("overload", "(I)V").unwrap().unwrap();
+        assert_eq!("(I)V", method.descriptor);
+        assert!(method.get_code().is_some());
+        assert!(lazy.find_method("overload", "(Ljava/lang/Object;)V").unwrap().is_none());
+
+        let cf = lazy.into_class_file().unwrap();
+        assert_eq!(samples::DEMO_CLASS, &::java::class_file::write_class_file(&cf).unwrap()[..]);
+    }
+
+    #[test]
+    fn lazy_class_files_decode_members_on_demand() {
+        let lazy = super::read_class_file_lazy(CLASSFILE).unwrap();
+        let name_index = lazy.method_table.as_ptr() as usize - CLASSFILE.as_ptr() as usize + 4;
+
+        let mut broken = CLASSFILE.to_vec();
+        broken[name_index] = 0xff;
+        broken[name_index + 1] = 0xff;
+        assert!(read_class_file(&broken).is_err());
+
+        let lazy = super::read_class_file_lazy(&broken).unwrap();
+        assert_eq!("HelloWorld", lazy.get_class_name().unwrap());
+        let err = lazy.methods().unwrap_err();
+        assert_eq!(name_index, err.offset);
+        assert_eq!(vec![Structure::Method(0)], err.structure);
+
+        assert!(super::read_class_file_lazy(&CLASSFILE[..CLASSFILE.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_owned_class_outlives_its_buffer() {
+        let buffer = LAMBDAS.to_vec();
+        let cf: ClassFile<'static> = read_class_file(&buffer).unwrap().into_owned();
+        drop(buffer);
+
+        let cf = ::std::thread::spawn(move || {
+            assert_eq!("Lambdas", cf.get_class_name().unwrap());
+            cf
+        }).join().unwrap();
+        assert_eq!(Some("Lambdas.java"), cf.source_file().unwrap().as_deref());
+        assert!(!cf.bootstrap_methods().unwrap().is_empty());
+        assert_eq!(LAMBDAS, &::java::class_file::write_class_file(&cf).unwrap()[..]);
+    }
+}
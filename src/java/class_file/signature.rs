@@ -0,0 +1,364 @@
+//! generic signatures from the `Signature` attribute (JVMS 4.7.9.1).
+//!
+//! descriptors only carry erased types, signatures keep type parameters, type arguments and
+//! the generic throws clause. `Display` renders java-like text with qualified class names,
+//! the alternate form (`{:#}`) leaves out the packages.
+
+use java::class_file::{parser, ClassFileError, ValueType};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum TypeSignature {
+    /// a primitive type, or `Void` as the result of a method
+    Base(ValueType),
+    Class(ClassTypeSignature),
+    /// a type variable, by name
+    TypeVariable(String),
+    Array(Box<TypeSignature>),
+}
+
+/// a (possibly parameterized) class type, e.g. `java.util.Map<K, V>.Entry<K, V>`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ClassTypeSignature {
+    /// the outermost class. its name is the internal name including the package, e.g. `java/util/Map`
+    pub outer: SimpleClassTypeSignature,
+    /// the nested classes, innermost last. their names are the simple names, e.g. `Entry`
+    pub inner: Vec<SimpleClassTypeSignature>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct SimpleClassTypeSignature {
+    pub name: String,
+    pub type_arguments: Vec<TypeArgument>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum TypeArgument {
+    /// `?`
+    Any,
+    Exact(TypeSignature),
+    /// `? extends T`
+    Extends(TypeSignature),
+    /// `? super T`
+    Super(TypeSignature),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TypeParameter {
+    pub name: String,
+    /// missing if the only bounds are interfaces
+    pub class_bound: Option<TypeSignature>,
+    pub interface_bounds: Vec<TypeSignature>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ClassSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub superclass: ClassTypeSignature,
+    pub interfaces: Vec<ClassTypeSignature>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MethodSignature {
+    pub type_parameters: Vec<TypeParameter>,
+    pub parameters: Vec<TypeSignature>,
+    pub return_type: TypeSignature,
+    /// class types or type variables
+    pub throws: Vec<TypeSignature>,
+}
+
+impl ClassTypeSignature {
+    /// the binary name of the innermost class, e.g. `java/util/Map$Entry`
+    pub fn binary_name(&self) -> String {
+        self.inner.iter().fold(self.outer.name.clone(), |name, inner| format!("{}${}", name, inner.name))
+    }
+
+    fn is_object(&self) -> bool {
+        self.outer.name == "java/lang/Object" && self.inner.is_empty()
+    }
+}
+
+impl ClassSignature {
+    /// renders the signature as a class declaration, e.g. `Box<T> extends Base<T> implements java.io.Serializable`
+    pub fn declaration<'s>(&'s self, name: &'s str) -> Declaration<'s, ClassSignature> {
+        Declaration { name, signature: self }
+    }
+}
+
+impl MethodSignature {
+    /// renders the signature as a method declaration, e.g. `<T> java.util.List<T> sort(java.util.List<T>)`
+    pub fn declaration<'s>(&'s self, name: &'s str) -> Declaration<'s, MethodSignature> {
+        Declaration { name, signature: self }
+    }
+}
+
+impl FromStr for TypeSignature {
+    type Err = ClassFileError;
+
+    /// parses a field signature
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        parser::read_field_signature(s)
+    }
+}
+
+impl FromStr for ClassSignature {
+    type Err = ClassFileError;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        parser::read_class_signature(s)
+    }
+}
+
+impl FromStr for MethodSignature {
+    type Err = ClassFileError;
+
+    fn from_str(s: &str) -> Result<Self, <Self as FromStr>::Err> {
+        parser::read_method_signature(s)
+    }
+}
+
+/// a class or method signature together with the name of the declared class or method
+pub struct Declaration<'s, S: 's> {
+    name: &'s str,
+    signature: &'s S,
+}
+
+/// writes `items` separated by `separator`, passing the alternate flag on
+fn write_list<T: fmt::Display>(f: &mut fmt::Formatter, items: &[T], separator: &str) -> fmt::Result {
+    for (idx, item) in items.iter().enumerate() {
+        if idx > 0 {
+            f.write_str(separator)?;
+        }
+        if f.alternate() {
+            write!(f, "{:#}", item)?;
+        } else {
+            write!(f, "{}", item)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_type_parameters(f: &mut fmt::Formatter, type_parameters: &[TypeParameter]) -> fmt::Result {
+    if type_parameters.is_empty() {
+        return Ok(());
+    }
+
+    f.write_str("<")?;
+    write_list(f, type_parameters, ", ")?;
+    f.write_str(">")
+}
+
+impl fmt::Display for TypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TypeSignature::Base(value_type) => f.write_str(&value_type.to_java()),
+            TypeSignature::Class(class) => write_list(f, &[class], ""),
+            TypeSignature::TypeVariable(name) => f.write_str(name),
+            TypeSignature::Array(element) => {
+                write_list(f, &[element], "")?;
+                f.write_str("[]")
+            }
+        }
+    }
+}
+
+impl fmt::Display for ClassTypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_list(f, &[&self.outer], "")?;
+        for inner in &self.inner {
+            f.write_str(".")?;
+            write_list(f, &[inner], "")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for SimpleClassTypeSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if f.alternate() {
+            f.write_str(self.name.rsplit('/').next().unwrap_or(&self.name))?;
+        } else {
+            f.write_str(&self.name.replace('/', "."))?;
+        }
+
+        if !self.type_arguments.is_empty() {
+            f.write_str("<")?;
+            write_list(f, &self.type_arguments, ", ")?;
+            f.write_str(">")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for TypeArgument {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (prefix, bound) = match self {
+            TypeArgument::Any => return f.write_str("?"),
+            TypeArgument::Exact(bound) => ("", bound),
+            TypeArgument::Extends(bound) => ("? extends ", bound),
+            TypeArgument::Super(bound) => ("? super ", bound),
+        };
+
+        f.write_str(prefix)?;
+        write_list(f, &[bound], "")
+    }
+}
+
+/// a lone `java.lang.Object` bound is left out, like in java source
+impl fmt::Display for TypeParameter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.name)?;
+
+        let bounds: Vec<&TypeSignature> = self.class_bound.iter().chain(self.interface_bounds.iter()).collect();
+        match bounds.as_slice() {
+            [] => Ok(()),
+            [TypeSignature::Class(class)] if class.is_object() => Ok(()),
+            bounds => {
+                f.write_str(" extends ")?;
+                write_list(f, bounds, " & ")
+            }
+        }
+    }
+}
+
+impl<'s> fmt::Display for Declaration<'s, ClassSignature> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name)?;
+        write_type_parameters(f, &self.signature.type_parameters)?;
+
+        if !self.signature.superclass.is_object() {
+            f.write_str(" extends ")?;
+            write_list(f, &[&self.signature.superclass], "")?;
+        }
+        if !self.signature.interfaces.is_empty() {
+            f.write_str(" implements ")?;
+            write_list(f, &self.signature.interfaces, ", ")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'s> fmt::Display for Declaration<'s, MethodSignature> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.signature.type_parameters.is_empty() {
+            write_type_parameters(f, &self.signature.type_parameters)?;
+            f.write_str(" ")?;
+        }
+
+        write_list(f, &[&self.signature.return_type], "")?;
+        write!(f, " {}(", self.name)?;
+        write_list(f, &self.signature.parameters, ", ")?;
+        f.write_str(")")?;
+
+        if !self.signature.throws.is_empty() {
+            f.write_str(" throws ")?;
+            write_list(f, &self.signature.throws, ", ")?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use java::class_file::error::ErrorReason;
+    use java::class_file::{read_class_file, ClassQuery};
+    use java::samples::GENERICS;
+
+    fn class(name: &str, type_arguments: Vec<TypeArgument>) -> TypeSignature {
+        TypeSignature::Class(ClassTypeSignature {
+            outer: SimpleClassTypeSignature { name: String::from(name), type_arguments },
+            inner: Vec::new(),
+        })
+    }
+
+    fn variable(name: &str) -> TypeSignature {
+        TypeSignature::TypeVariable(String::from(name))
+    }
+
+    #[test]
+    fn it_parses_method_signatures() {
+        let signature = MethodSignature::from_str("<T::Ljava/lang/Comparable<TT;>;>(Ljava/util/List<TT;>;)Ljava/util/List<TT;>;").unwrap();
+        assert_eq!(
+            vec![TypeParameter {
+                name: String::from("T"),
+                class_bound: None,
+                interface_bounds: vec![class("java/lang/Comparable", vec![TypeArgument::Exact(variable("T"))])],
+            }],
+            signature.type_parameters
+        );
+        assert_eq!(vec![class("java/util/List", vec![TypeArgument::Exact(variable("T"))])], signature.parameters);
+        assert_eq!("<T extends Comparable<T>> List<T> sort(List<T>)", format!("{:#}", signature.declaration("sort")));
+        assert_eq!(
+            "<T extends java.lang.Comparable<T>> java.util.List<T> sort(java.util.List<T>)",
+            signature.declaration("sort").to_string()
+        );
+    }
+
+    #[test]
+    fn it_parses_wildcards_inner_classes_and_arrays() {
+        let signature = TypeSignature::from_str("[Ljava/util/Map<+Ljava/lang/Number;*>.Entry<-TK;[I>;").unwrap();
+        assert_eq!("java.util.Map<? extends java.lang.Number, ?>.Entry<? super K, int[]>[]", signature.to_string());
+        match signature {
+            TypeSignature::Array(ref element) => match **element {
+                TypeSignature::Class(ref class) => assert_eq!("java/util/Map$Entry", class.binary_name()),
+                ref other => panic!("unexpected {:?}", other)
+            },
+            ref other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn it_rejects_malformed_signatures() {
+        // a field signature is a reference type (JVMS 4.7.9.1)
+        assert!(TypeSignature::from_str("I").is_err());
+        assert!(TypeSignature::from_str("[I").is_ok());
+        assert!(TypeSignature::from_str("Ljava/util/List<>;").is_err());
+        assert!(TypeSignature::from_str("TT").is_err());
+        assert!(MethodSignature::from_str("()V^").is_err());
+        assert!(ClassSignature::from_str("<T:>Ljava/lang/Object;;").is_err());
+    }
+
+    #[test]
+    fn it_limits_the_nesting_of_type_arguments() {
+        let nested = |depth: usize| format!("{}Ljava/lang/Object;{}", "Ljava/util/List<".repeat(depth), ">;".repeat(depth));
+        assert!(TypeSignature::from_str(&nested(128)).is_ok());
+
+        let err = TypeSignature::from_str(&nested(129)).unwrap_err();
+        match err.reason {
+            ErrorReason::NestedTooDeep { max: 128 } => (),
+            ref other => panic!("unexpected {:?}", other)
+        }
+        // far too deep to parse recursively
+        assert!(TypeSignature::from_str(&nested(3600)).is_err());
+        assert!(MethodSignature::from_str(&format!("({})V", nested(3600))).is_err());
+    }
+
+    #[test]
+    fn it_reads_signature_attributes() {
+        let cf = read_class_file(GENERICS).unwrap();
+        let signature = cf.generic_signature().unwrap().unwrap();
+        assert_eq!(
+            "Generics<K extends java.lang.Comparable<K>, V> extends java.util.AbstractMap<K, V> implements java.io.Serializable",
+            signature.declaration("Generics").to_string()
+        );
+
+        let field = cf.fields.iter().find(|f| f.name == "listeners").unwrap();
+        assert_eq!("List<? super Map$Entry<K, V>>[]", format!("{:#}", field.generic_signature().unwrap().unwrap()));
+
+        let method = cf.methods.iter().find(|m| m.name == "copy").unwrap();
+        assert_eq!(
+            "<E extends java.lang.Exception> void copy(java.util.Collection<? extends V>, int) throws E, java.io.IOException",
+            method.generic_signature().unwrap().unwrap().declaration("copy").to_string()
+        );
+
+        let plain = cf.methods.iter().find(|m| m.name == "size").unwrap();
+        assert!(plain.generic_signature().unwrap().is_none());
+    }
+}
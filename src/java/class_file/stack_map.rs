@@ -0,0 +1,272 @@
+//! the `StackMapTable` attribute (JVMS 4.7.4).
+//!
+//! every frame is stored relative to the one before it. `expand` turns that into the absolute
+//! type state at each frame's pc.
+
+use java::class_file::error::{ErrorReason, Structure};
+use java::class_file::{owned, ClassFileError, Method, MethodFlags, ValueType};
+use std::borrow::Cow;
+use std::fmt;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum VerificationType<'a> {
+    Top,
+    Integer,
+    Float,
+    Double,
+    Long,
+    Null,
+    UninitializedThis,
+    /// an initialized reference, by class name. arrays use their descriptor, e.g. `[I`
+    Object(Cow<'a, str>),
+    /// the result of the `new` instruction at the given offset, before the constructor ran
+    Uninitialized(u16),
+}
+
+impl<'a> VerificationType<'a> {
+    /// the type a parameter of the given type has in the initial frame of a method
+    pub fn from_value_type(value_type: &ValueType) -> Option<VerificationType<'a>> {
+        match value_type {
+            ValueType::Void => None,
+            ValueType::Boolean | ValueType::Byte | ValueType::Char | ValueType::Short | ValueType::Integer =>
+                Some(VerificationType::Integer),
+            ValueType::Float => Some(VerificationType::Float),
+            ValueType::Long => Some(VerificationType::Long),
+            ValueType::Double => Some(VerificationType::Double),
+            ValueType::Object(name) => Some(VerificationType::Object(Cow::Owned(name.clone()))),
+            ValueType::Array(_) => Some(VerificationType::Object(Cow::Owned(value_type.to_descriptor()))),
+        }
+    }
+
+    /// `Long` and `Double` occupy two local variable slots
+    pub fn size(&self) -> usize {
+        match self {
+            VerificationType::Long | VerificationType::Double => 2,
+            _ => 1
+        }
+    }
+
+    pub fn into_owned(self) -> VerificationType<'static> {
+        match self {
+            VerificationType::Top => VerificationType::Top,
+            VerificationType::Integer => VerificationType::Integer,
+            VerificationType::Float => VerificationType::Float,
+            VerificationType::Double => VerificationType::Double,
+            VerificationType::Long => VerificationType::Long,
+            VerificationType::Null => VerificationType::Null,
+            VerificationType::UninitializedThis => VerificationType::UninitializedThis,
+            VerificationType::Object(name) => VerificationType::Object(owned(name)),
+            VerificationType::Uninitialized(offset) => VerificationType::Uninitialized(offset),
+        }
+    }
+}
+
+/// the names the verifier uses, e.g. `int`, `uninitialized(12)` or `java/lang/String`
+impl<'a> fmt::Display for VerificationType<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VerificationType::Top => write!(f, "top"),
+            VerificationType::Integer => write!(f, "int"),
+            VerificationType::Float => write!(f, "float"),
+            VerificationType::Double => write!(f, "double"),
+            VerificationType::Long => write!(f, "long"),
+            VerificationType::Null => write!(f, "null"),
+            VerificationType::UninitializedThis => write!(f, "uninitializedThis"),
+            VerificationType::Object(name) => write!(f, "{}", name),
+            VerificationType::Uninitialized(offset) => write!(f, "uninitialized({})", offset),
+        }
+    }
+}
+
+/// a single entry of the `StackMapTable`, as it is stored in the class file
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum StackMapFrame<'a> {
+    /// `same_frame`, frame types 0-63
+    Same { offset_delta: u16 },
+    /// `same_locals_1_stack_item_frame`, frame types 64-127
+    SameLocals1StackItem { offset_delta: u16, stack: VerificationType<'a> },
+    /// `same_locals_1_stack_item_frame_extended`, frame type 247
+    SameLocals1StackItemExtended { offset_delta: u16, stack: VerificationType<'a> },
+    /// `chop_frame`, frame types 248-250. the last `count` locals are removed
+    Chop { offset_delta: u16, count: u8 },
+    /// `same_frame_extended`, frame type 251
+    SameExtended { offset_delta: u16 },
+    /// `append_frame`, frame types 252-254
+    Append { offset_delta: u16, locals: Vec<VerificationType<'a>> },
+    /// `full_frame`, frame type 255
+    Full { offset_delta: u16, locals: Vec<VerificationType<'a>>, stack: Vec<VerificationType<'a>> },
+}
+
+impl<'a> StackMapFrame<'a> {
+    pub fn offset_delta(&self) -> u16 {
+        match self {
+            StackMapFrame::Same { offset_delta } |
+            StackMapFrame::SameLocals1StackItem { offset_delta, .. } |
+            StackMapFrame::SameLocals1StackItemExtended { offset_delta, .. } |
+            StackMapFrame::Chop { offset_delta, .. } |
+            StackMapFrame::SameExtended { offset_delta } |
+            StackMapFrame::Append { offset_delta, .. } |
+            StackMapFrame::Full { offset_delta, .. } => *offset_delta
+        }
+    }
+
+    pub fn into_owned(self) -> StackMapFrame<'static> {
+        let owned_types = |types: Vec<VerificationType>| types.into_iter().map(VerificationType::into_owned).collect();
+        match self {
+            StackMapFrame::Same { offset_delta } => StackMapFrame::Same { offset_delta },
+            StackMapFrame::SameLocals1StackItem { offset_delta, stack } =>
+                StackMapFrame::SameLocals1StackItem { offset_delta, stack: stack.into_owned() },
+            StackMapFrame::SameLocals1StackItemExtended { offset_delta, stack } =>
+                StackMapFrame::SameLocals1StackItemExtended { offset_delta, stack: stack.into_owned() },
+            StackMapFrame::Chop { offset_delta, count } => StackMapFrame::Chop { offset_delta, count },
+            StackMapFrame::SameExtended { offset_delta } => StackMapFrame::SameExtended { offset_delta },
+            StackMapFrame::Append { offset_delta, locals } => StackMapFrame::Append { offset_delta, locals: owned_types(locals) },
+            StackMapFrame::Full { offset_delta, locals, stack } =>
+                StackMapFrame::Full { offset_delta, locals: owned_types(locals), stack: owned_types(stack) },
+        }
+    }
+}
+
+/// the type state at the start of the instruction at `pc`.
+///
+/// like in the class file, `locals` has one entry per variable, so a `Long` or `Double`
+/// stands for two local variable slots.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct FrameState<'a> {
+    pub pc: u16,
+    pub locals: Vec<VerificationType<'a>>,
+    pub stack: Vec<VerificationType<'a>>,
+}
+
+/// the locals of the implicit first frame of `method`, derived from its descriptor
+pub fn initial_locals<'a>(class_name: &str, method: &Method) -> Result<Vec<VerificationType<'a>>, ClassFileError> {
+    let mut locals = Vec::new();
+    if !method.access().contains(MethodFlags::STATIC) {
+        if method.name == "<init>" && class_name != "java/lang/Object" {
+            locals.push(VerificationType::UninitializedThis);
+        } else {
+            locals.push(VerificationType::Object(Cow::Owned(String::from(class_name))));
+        }
+    }
+
+    let signature = method.get_signature()?;
+    locals.extend(signature.arguments.iter().filter_map(VerificationType::from_value_type));
+
+    Ok(locals)
+}
+
+/// turns the relative frames into absolute type states, starting from `initial_locals`.
+/// the implicit first frame at pc 0 is not part of the result. fails if a frame's pc does not
+/// fit into a `u16`, no code is that long.
+pub fn expand<'a>(frames: &[StackMapFrame<'a>], initial_locals: Vec<VerificationType<'a>>) -> Result<Vec<FrameState<'a>>, ClassFileError> {
+    let mut states: Vec<FrameState<'a>> = Vec::with_capacity(frames.len());
+    let mut locals = initial_locals;
+
+    for (idx, frame) in frames.iter().enumerate() {
+        let pc = match states.last() {
+            Some(previous) => previous.pc.checked_add(frame.offset_delta()).and_then(|pc| pc.checked_add(1)),
+            None => Some(frame.offset_delta())
+        };
+        let pc = pc.ok_or_else(|| ClassFileError {
+            offset: 0,
+            structure: vec![Structure::Attribute(String::from("StackMapTable"))],
+            reason: ErrorReason::FrameOffset { frame: idx },
+        })?;
+
+        let stack = match frame {
+            StackMapFrame::Same { .. } | StackMapFrame::SameExtended { .. } => Vec::new(),
+            StackMapFrame::SameLocals1StackItem { stack, .. } |
+            StackMapFrame::SameLocals1StackItemExtended { stack, .. } => vec![stack.clone()],
+            StackMapFrame::Chop { count, .. } => {
+                let remaining = locals.len().saturating_sub(usize::from(*count));
+                locals.truncate(remaining);
+                Vec::new()
+            }
+            StackMapFrame::Append { locals: appended, .. } => {
+                locals.extend(appended.iter().cloned());
+                Vec::new()
+            }
+            StackMapFrame::Full { locals: full_locals, stack, .. } => {
+                locals = full_locals.clone();
+                stack.clone()
+            }
+        };
+
+        states.push(FrameState { pc, locals: locals.clone(), stack });
+    }
+
+    Ok(states)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use java::class_file::read_class_file;
+
+    use java::samples::{STACK_FRAMES, TRY_CATCH};
+
+    fn object(name: &str) -> VerificationType<'_> {
+        VerificationType::Object(Cow::Borrowed(name))
+    }
+
+    #[test]
+    fn it_decodes_all_frame_kinds() {
+        let cf = read_class_file(STACK_FRAMES).unwrap();
+        let pick = cf.methods.iter().find(|m| m.name == "pick").unwrap();
+        let frames = pick.get_code().unwrap().stack_map_table();
+
+        assert_eq!(StackMapFrame::Same { offset_delta: 13 }, frames[0]);
+        assert_eq!(StackMapFrame::Append { offset_delta: 2, locals: vec![object("java/lang/String")] }, frames[1]);
+        match frames[2] {
+            StackMapFrame::Full { offset_delta: 19, ref locals, ref stack } => {
+                assert_eq!(object("StackFrames"), locals[0]);
+                assert_eq!(vec![VerificationType::Integer, VerificationType::Integer], *stack);
+            }
+            ref other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn it_expands_frames_to_absolute_states() {
+        let cf = read_class_file(STACK_FRAMES).unwrap();
+        let sum = cf.methods.iter().find(|m| m.name == "sum").unwrap();
+        let frames = sum.get_code().unwrap().stack_map_table();
+        assert_eq!(StackMapFrame::Chop { offset_delta: 20, count: 1 }, frames[1]);
+
+        let states = expand(frames, vec![object("[I"), VerificationType::Long]).unwrap();
+        assert_eq!(vec![5, 26, 47], states.iter().map(|state| state.pc).collect::<Vec<u16>>());
+        assert_eq!(
+            vec![object("[I"), VerificationType::Long, VerificationType::Long, VerificationType::Integer],
+            states[0].locals
+        );
+        assert_eq!(vec![object("[I"), VerificationType::Long, VerificationType::Long], states[1].locals);
+        assert_eq!(states[1].locals, states[2].locals);
+        assert_eq!(states, sum.stack_map_states("StackFrames").unwrap());
+    }
+
+    #[test]
+    fn it_derives_the_initial_locals_from_the_method() {
+        let cf = read_class_file(TRY_CATCH).unwrap();
+        let divide = cf.methods.iter().find(|m| m.name == "divide").unwrap();
+        let states = divide.stack_map_states("TryCatch").unwrap();
+
+        assert_eq!(
+            vec![FrameState {
+                pc: 4,
+                locals: vec![VerificationType::Integer, VerificationType::Integer],
+                stack: vec![object("java/lang/ArithmeticException")],
+            }],
+            states
+        );
+    }
+
+    #[test]
+    fn it_rejects_frames_beyond_the_largest_offset() {
+        let frames = vec![StackMapFrame::SameExtended { offset_delta: 0xffff }, StackMapFrame::SameExtended { offset_delta: 0xffff }];
+        let err = expand(&frames, Vec::new()).unwrap_err();
+        match err.reason {
+            ErrorReason::FrameOffset { frame: 1 } => (),
+            ref other => panic!("unexpected {:?}", other)
+        }
+    }
+}
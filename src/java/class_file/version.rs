@@ -0,0 +1,266 @@
+//! class file versions (JVMS 4.1) and the features that depend on them.
+//!
+//! a `VersionPolicy` decides which versions the loader accepts, and checks that a class only
+//! uses constants and instructions that exist in its version.
+
+use java::class_file::{Attribute, ClassFile, ConstantType, Method};
+use java::instructions::{Instruction, Wide};
+
+/// the oldest version rjvm accepts, JDK 1.0.2 and 1.1
+pub const MIN_MAJOR: u16 = 45;
+/// the newest version rjvm knows about, java 17
+pub const MAX_MAJOR: u16 = 61;
+/// the minor version of a class file that uses the preview features of its java release
+pub const PREVIEW_MINOR: u16 = 0xffff;
+
+/// java 12 restricted the minor version to 0 or `PREVIEW_MINOR`
+const PREVIEW_SINCE: u16 = 56;
+/// type-checking verification, which needs stack map frames
+pub const STACK_MAP_TABLE_SINCE: u16 = 50;
+/// `invokedynamic` and its constants. `jsr` and `ret` were removed in the same version.
+const INVOKE_DYNAMIC_SINCE: u16 = 51;
+const MODULES_SINCE: u16 = 53;
+/// `Dynamic` constants, loaded with `ldc`
+const DYNAMIC_SINCE: u16 = 55;
+
+#[derive(Debug, Fail)]
+pub enum VersionError {
+    #[fail(display = "class file version {}.{} is not supported, only {} to {}", major, minor, min, max)]
+    Unsupported { major: u16, minor: u16, min: u16, max: u16 },
+    #[fail(display = "class file version {}.{} has an invalid minor version", major, minor)]
+    InvalidMinor { major: u16, minor: u16 },
+    #[fail(display = "class file version {} uses preview features, which are not enabled for it", major)]
+    PreviewNotEnabled { major: u16 },
+    #[fail(display = "{} needs class file version {}, found {}", feature, since, major)]
+    FeatureUnavailable { feature: String, since: u16, major: u16 },
+    #[fail(display = "{} is not allowed from class file version {} on, found {}", feature, until, major)]
+    FeatureRemoved { feature: String, until: u16, major: u16 },
+    #[fail(display = "method {}{} branches but has no stack map frames, which version {} requires", name, descriptor, major)]
+    MissingStackMapTable { name: String, descriptor: String, major: u16 },
+}
+
+/// which class file versions the loader accepts
+#[derive(Debug, Clone)]
+pub struct VersionPolicy {
+    pub min_major: u16,
+    pub max_major: u16,
+    /// accept preview class files of `max_major`. preview features of older releases are never accepted.
+    pub preview: bool,
+    /// check that the constants and instructions exist in the version of the class
+    pub check_features: bool,
+}
+
+impl Default for VersionPolicy {
+    fn default() -> VersionPolicy {
+        VersionPolicy { min_major: MIN_MAJOR, max_major: MAX_MAJOR, preview: false, check_features: true }
+    }
+}
+
+impl VersionPolicy {
+    /// checks the version, then the features the class uses
+    pub fn check(&self, class: &ClassFile) -> Result<(), VersionError> {
+        self.check_version(class.version)?;
+        if self.check_features {
+            if let Some(err) = feature_errors(class).into_iter().next() {
+                return Err(err);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// checks a `(major, minor)` version against the supported range
+    pub fn check_version(&self, version: (u16, u16)) -> Result<(), VersionError> {
+        let (major, minor) = version;
+        if major < self.min_major || major > self.max_major {
+            return Err(VersionError::Unsupported { major, minor, min: self.min_major, max: self.max_major });
+        }
+
+        if major >= PREVIEW_SINCE {
+            match minor {
+                0 => (),
+                PREVIEW_MINOR if self.preview && major == self.max_major => (),
+                PREVIEW_MINOR => return Err(VersionError::PreviewNotEnabled { major }),
+                _ => return Err(VersionError::InvalidMinor { major, minor })
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// true for class files of java 12 and newer that use preview features
+pub fn is_preview(version: (u16, u16)) -> bool {
+    version.0 >= PREVIEW_SINCE && version.1 == PREVIEW_MINOR
+}
+
+/// every constant, instruction and missing stack map table that is wrong for the version of the class
+pub fn feature_errors(class: &ClassFile) -> Vec<VersionError> {
+    let major = class.version.0;
+    let mut errors = Vec::new();
+
+    for (index, constant) in class.constants.iter() {
+        let since = match constant {
+            ConstantType::MethodHandle { .. } | ConstantType::MethodType { .. } | ConstantType::InvokeDynamic { .. } => INVOKE_DYNAMIC_SINCE,
+            ConstantType::Module { .. } | ConstantType::Package { .. } => MODULES_SINCE,
+            ConstantType::Dynamic { .. } => DYNAMIC_SINCE,
+            _ => continue
+        };
+        if major < since {
+            errors.push(VersionError::FeatureUnavailable { feature: format!("{} constant #{}", constant.name(), index), since, major });
+        }
+    }
+
+    for method in &class.methods {
+        method_errors(method, major, &mut errors);
+    }
+
+    errors
+}
+
+fn method_errors(method: &Method, major: u16, errors: &mut Vec<VersionError>) {
+    let code = match method.get_code() {
+        Some(code) => code,
+        None => return
+    };
+
+    // broken bytecode is left to the verifier, only the exception table counts then
+    let instructions = code.instructions().unwrap_or_default();
+    let feature = |instruction: &str| format!("{} in {}{}", instruction, method.name, method.descriptor);
+
+    if major < INVOKE_DYNAMIC_SINCE && instructions.iter().any(|instruction| matches!(instruction, Instruction::InvokeDynamic(_))) {
+        errors.push(VersionError::FeatureUnavailable { feature: feature("invokedynamic"), since: INVOKE_DYNAMIC_SINCE, major });
+    }
+
+    if major >= INVOKE_DYNAMIC_SINCE && instructions.iter().any(|instruction| matches!(instruction, Instruction::JSR(_) | Instruction::JSRW(_) | Instruction::Ret(_) | Instruction::Wide(Wide::Ret(_)))) {
+        errors.push(VersionError::FeatureRemoved { feature: feature("jsr/ret"), until: INVOKE_DYNAMIC_SINCE, major });
+    }
+
+    let has_frames = code.attributes.iter().any(|attr| match attr {
+        Attribute::StackMapTable(frames) => !frames.is_empty(),
+        _ => false
+    });
+    if major >= STACK_MAP_TABLE_SINCE && !has_frames && (!code.exception_handlers.is_empty() || instructions.iter().any(branches)) {
+        errors.push(VersionError::MissingStackMapTable {
+            name: method.name.to_string(),
+            descriptor: method.descriptor.to_string(),
+            major,
+        });
+    }
+}
+
+/// instructions that continue somewhere else than the next instruction, so their target needs a frame
+fn branches(instruction: &Instruction) -> bool {
+    matches!(instruction,
+        Instruction::Ifeq(_) | Instruction::Ifne(_) | Instruction::Iflt(_) |
+        Instruction::Ifge(_) | Instruction::Ifgt(_) | Instruction::Ifle(_) |
+        Instruction::IfICmpEQ(_) | Instruction::IfICmpNE(_) | Instruction::IfICmpLT(_) |
+        Instruction::IfICmpGE(_) | Instruction::IfICmpGT(_) | Instruction::IfICmpLE(_) |
+        Instruction::IfACmpEQ(_) | Instruction::IfACmpNE(_) | Instruction::IfNull(_) | Instruction::IfNonNull(_) |
+        Instruction::Goto(_) | Instruction::GotoW(_) | Instruction::JSR(_) | Instruction::JSRW(_) |
+        Instruction::TableSwitch(_) | Instruction::LookupSwitch(_))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use java::class_file::read_class_file;
+    use java::samples::{LAMBDAS, MODULE_INFO, SIMPLE_MATH_WITH_LOOP};
+
+    #[test]
+    fn it_checks_the_supported_range() {
+        let policy = VersionPolicy::default();
+        assert!(policy.check(&read_class_file(LAMBDAS).unwrap()).is_ok());
+        assert!(policy.check_version((45, 3)).is_ok());
+        assert!(policy.check_version((49, 7)).is_ok());
+
+        match policy.check_version((62, 0)) {
+            Err(VersionError::Unsupported { major: 62, min: 45, max: 61, .. }) => (),
+            other => panic!("unexpected {:?}", other)
+        }
+        assert!(policy.check_version((44, 0)).is_err());
+
+        let narrow = VersionPolicy { min_major: 52, max_major: 55, ..VersionPolicy::default() };
+        assert!(narrow.check_version((51, 0)).is_err());
+        assert!(narrow.check_version((55, 0)).is_ok());
+    }
+
+    #[test]
+    fn it_only_accepts_preview_features_of_the_latest_version() {
+        assert!(is_preview((61, PREVIEW_MINOR)));
+        assert!(!is_preview((55, PREVIEW_MINOR)));
+
+        let policy = VersionPolicy::default();
+        match policy.check_version((61, PREVIEW_MINOR)) {
+            Err(VersionError::PreviewNotEnabled { major: 61 }) => (),
+            other => panic!("unexpected {:?}", other)
+        }
+
+        let preview = VersionPolicy { preview: true, ..VersionPolicy::default() };
+        assert!(preview.check_version((61, PREVIEW_MINOR)).is_ok());
+        assert!(preview.check_version((60, PREVIEW_MINOR)).is_err());
+        match preview.check_version((60, 3)) {
+            Err(VersionError::InvalidMinor { major: 60, minor: 3 }) => (),
+            other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn it_flags_features_newer_than_the_class() {
+        let mut lambdas = read_class_file(LAMBDAS).unwrap();
+        assert!(feature_errors(&lambdas).is_empty());
+
+        lambdas.version = (49, 0);
+        let errors = feature_errors(&lambdas);
+        assert!(errors.iter().any(|err| match err {
+            VersionError::FeatureUnavailable { feature, since: 51, .. } => feature.starts_with("InvokeDynamic constant"),
+            _ => false
+        }));
+        assert!(errors.iter().any(|err| match err {
+            VersionError::FeatureUnavailable { feature, .. } => feature.starts_with("invokedynamic in "),
+            _ => false
+        }));
+        assert!(VersionPolicy::default().check(&lambdas).is_err());
+        assert!(VersionPolicy { check_features: false, ..VersionPolicy::default() }.check(&lambdas).is_ok());
+
+        let mut module = read_class_file(MODULE_INFO).unwrap();
+        module.version = (52, 0);
+        assert!(feature_errors(&module).iter().any(|err| match err {
+            VersionError::FeatureUnavailable { feature, since: 53, .. } => feature.starts_with("Module constant"),
+            _ => false
+        }));
+
+        let mut lambdas = read_class_file(LAMBDAS).unwrap();
+        lambdas.constants.push(ConstantType::Dynamic { bootstrap_method_attr_index: 0, name_and_type_index: 7 });
+        lambdas.version = (54, 0);
+        assert!(feature_errors(&lambdas).iter().any(|err| match err {
+            VersionError::FeatureUnavailable { feature, since: 55, .. } => feature.starts_with("Dynamic constant"),
+            _ => false
+        }));
+        lambdas.version = (55, 0);
+        assert!(feature_errors(&lambdas).is_empty());
+    }
+
+    #[test]
+    fn it_requires_stack_maps_for_branches() {
+        let mut class = read_class_file(SIMPLE_MATH_WITH_LOOP).unwrap();
+        for method in class.methods.iter_mut() {
+            for attribute in method.attributes.iter_mut() {
+                if let Attribute::CodeAttribute(code) = attribute {
+                    code.attributes.retain(|attr| !matches!(attr, Attribute::StackMapTable(_)));
+                }
+            }
+        }
+
+        let methods: Vec<String> = feature_errors(&class).into_iter()
+            .map(|err| match err {
+                VersionError::MissingStackMapTable { name, .. } => name,
+                other => panic!("unexpected {:?}", other)
+            })
+            .collect();
+        assert_eq!(vec!["testMe", "main"], methods);
+
+        class.version = (49, 0);
+        assert!(feature_errors(&class).is_empty());
+    }
+}
@@ -0,0 +1,758 @@
+//! serializes a `ClassFile` back into the class file format.
+//!
+//! the parsed structures keep resolved names and values instead of constant pool indexes, so the
+//! writer looks them up in the class's own constant pool again. it points every use to the entry
+//! the parser resolved it from (`ClassFile::references`), which keeps `read -> write` lossless
+//! even when a value is in the pool more than once. values without a recorded entry, like the
+//! ones of an added attribute, use the first matching entry.
+use java::class_file::annotation::{Annotation, ElementValue, TypeAnnotation, TypeAnnotationTarget, TypePathEntry};
+use java::class_file::module::{Exports, Module};
+use java::class_file::stack_map::{StackMapFrame, VerificationType};
+use java::class_file::{Attribute, ClassFile, CodeBlock, ConstantPool, ConstantType, ConstantValue, LocalVariableInfo};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::collections::HashMap;
+
+#[derive(Debug, Fail)]
+pub enum WriteError {
+    #[fail(display = "there is no {} constant for {:?} in the constant pool", kind, value)]
+    MissingConstant { kind: &'static str, value: String },
+    #[fail(display = "too many {} for the class file format", what)]
+    TooLarge { what: &'static str },
+}
+
+const MAGIC: u32 = 0xCAFE_BABE;
+
+/// the bytes of the class file, in the layout of JVMS 4.1
+pub fn write_class_file(class: &ClassFile) -> Result<Vec<u8>, WriteError> {
+    let constants = ConstantIndex::new(class);
+    let mut out = Vec::new();
+
+    put_u32(&mut out, MAGIC);
+    put_u16(&mut out, class.version.1);
+    put_u16(&mut out, class.version.0);
+
+    put_u16(&mut out, count(class.constants.len() + 1, "constants")?);
+    for (_, constant) in class.constants.iter() {
+        write_constant(&mut out, constant);
+    }
+
+    put_u16(&mut out, class.access_flags);
+    put_u16(&mut out, class.this_index);
+    put_u16(&mut out, class.super_index);
+    put_u16(&mut out, count(class.interfaces.len(), "interfaces")?);
+    for interface in &class.interfaces {
+        put_u16(&mut out, *interface);
+    }
+
+    put_u16(&mut out, count(class.fields.len(), "fields")?);
+    for field in &class.fields {
+        put_u16(&mut out, field.access_flags);
+        put_u16(&mut out, constants.utf8(&field.name)?);
+        put_u16(&mut out, constants.utf8(&field.descriptor)?);
+        write_attributes(&mut out, &constants, &field.attributes)?;
+    }
+
+    put_u16(&mut out, count(class.methods.len(), "methods")?);
+    for method in &class.methods {
+        put_u16(&mut out, method.access_flags);
+        put_u16(&mut out, constants.utf8(&method.name)?);
+        put_u16(&mut out, constants.utf8(&method.descriptor)?);
+        write_attributes(&mut out, &constants, &method.attributes)?;
+    }
+
+    write_attributes(&mut out, &constants, &class.attributes)?;
+
+    Ok(out)
+}
+
+fn put_u8(out: &mut Vec<u8>, value: u8) {
+    out.push(value);
+}
+
+fn put_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&[(value >> 8) as u8, value as u8]);
+}
+
+fn put_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&[(value >> 24) as u8, (value >> 16) as u8, (value >> 8) as u8, value as u8]);
+}
+
+fn put_u64(out: &mut Vec<u8>, value: u64) {
+    put_u32(out, (value >> 32) as u32);
+    put_u32(out, value as u32);
+}
+
+/// a table length or count, which has to fit in a `u2`
+fn count(len: usize, what: &'static str) -> Result<u16, WriteError> {
+    if len > usize::from(u16::MAX) {
+        Err(WriteError::TooLarge { what })
+    } else {
+        Ok(len as u16)
+    }
+}
+
+fn write_constant(out: &mut Vec<u8>, constant: &ConstantType) {
+    match constant {
+        ConstantType::Utf8 { bytes, .. } => {
+            put_u8(out, 1);
+            put_u16(out, bytes.len() as u16);
+            out.extend_from_slice(bytes);
+        }
+        ConstantType::Integer { value } => {
+            put_u8(out, 3);
+            put_u32(out, *value as u32);
+        }
+        ConstantType::Float { value } => {
+            put_u8(out, 4);
+            put_u32(out, value.to_bits());
+        }
+        ConstantType::Long { value } => {
+            put_u8(out, 5);
+            put_u64(out, *value as u64);
+        }
+        ConstantType::Double { value } => {
+            put_u8(out, 6);
+            put_u64(out, value.to_bits());
+        }
+        ConstantType::Class { name_index } => {
+            put_u8(out, 7);
+            put_u16(out, *name_index);
+        }
+        ConstantType::String { string_index } => {
+            put_u8(out, 8);
+            put_u16(out, *string_index);
+        }
+        ConstantType::FieldRef { class_index, name_and_type_index } |
+        ConstantType::MethodRef { class_index, name_and_type_index } |
+        ConstantType::InterfaceMethodRef { class_index, name_and_type_index } => {
+            put_u8(out, match constant {
+                ConstantType::FieldRef { .. } => 9,
+                ConstantType::MethodRef { .. } => 10,
+                _ => 11
+            });
+            put_u16(out, *class_index);
+            put_u16(out, *name_and_type_index);
+        }
+        ConstantType::NameAndType { name_index, descriptor_index } => {
+            put_u8(out, 12);
+            put_u16(out, *name_index);
+            put_u16(out, *descriptor_index);
+        }
+        ConstantType::MethodHandle { reference_kind, reference_index } => {
+            put_u8(out, 15);
+            put_u8(out, *reference_kind);
+            put_u16(out, *reference_index);
+        }
+        ConstantType::MethodType { descriptor_index } => {
+            put_u8(out, 16);
+            put_u16(out, *descriptor_index);
+        }
+        ConstantType::Dynamic { bootstrap_method_attr_index, name_and_type_index } => {
+            put_u8(out, 17);
+            put_u16(out, *bootstrap_method_attr_index);
+            put_u16(out, *name_and_type_index);
+        }
+        ConstantType::InvokeDynamic { bootstrap_method_attr_index, name_and_type_index } => {
+            put_u8(out, 18);
+            put_u16(out, *bootstrap_method_attr_index);
+            put_u16(out, *name_and_type_index);
+        }
+        ConstantType::Module { name_index } => {
+            put_u8(out, 19);
+            put_u16(out, *name_index);
+        }
+        ConstantType::Package { name_index } => {
+            put_u8(out, 20);
+            put_u16(out, *name_index);
+        }
+    }
+}
+
+/// a resolved constant, as the parsed structures refer to it
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Key<'c> {
+    Utf8(&'c str),
+    Class(&'c str),
+    String(&'c str),
+    Module(&'c str),
+    Package(&'c str),
+    NameAndType(&'c str, &'c str),
+    Integer(i32),
+    Long(i64),
+    /// by their bits, so `NaN`s can be found too
+    Float(u32),
+    Double(u64),
+}
+
+impl<'c> Key<'c> {
+    /// `None` for entries the parsed structures cannot refer to, or that do not resolve
+    fn of(pool: &'c ConstantPool, index: u16, constant: &'c ConstantType) -> Option<Key<'c>> {
+        match constant {
+            ConstantType::Utf8 { value, .. } => Some(Key::Utf8(value)),
+            ConstantType::Integer { value } => Some(Key::Integer(*value)),
+            ConstantType::Float { value } => Some(Key::Float(value.to_bits())),
+            ConstantType::Long { value } => Some(Key::Long(*value)),
+            ConstantType::Double { value } => Some(Key::Double(value.to_bits())),
+            ConstantType::Class { name_index } => pool.utf8(*name_index).ok().map(Key::Class),
+            ConstantType::String { string_index } => pool.utf8(*string_index).ok().map(Key::String),
+            ConstantType::Module { name_index } => pool.utf8(*name_index).ok().map(Key::Module),
+            ConstantType::Package { name_index } => pool.utf8(*name_index).ok().map(Key::Package),
+            ConstantType::NameAndType { .. } => pool.name_and_type(index).ok()
+                .map(|(name, descriptor)| Key::NameAndType(name, descriptor)),
+            _ => None
+        }
+    }
+}
+
+/// the entries of one value in the constant pool
+struct Entries {
+    first: u16,
+    /// the entries the parser resolved this value from, in the order it did
+    recorded: Vec<u16>,
+    next: Cell<usize>,
+}
+
+/// reverse lookups from resolved values to their index in the constant pool
+struct ConstantIndex<'c> {
+    entries: HashMap<Key<'c>, Entries>,
+}
+
+impl<'c> ConstantIndex<'c> {
+    fn new(class: &'c ClassFile) -> ConstantIndex<'c> {
+        let mut entries = HashMap::new();
+        let mut keys = HashMap::new();
+        for (index, constant) in class.constants.iter() {
+            if let Some(key) = Key::of(&class.constants, index, constant) {
+                entries.entry(key.clone()).or_insert(Entries { first: index, recorded: Vec::new(), next: Cell::new(0) });
+                keys.insert(index, key);
+            }
+        }
+
+        for index in &class.references {
+            if let Some(entries) = keys.get(index).and_then(|key| entries.get_mut(key)) {
+                entries.recorded.push(*index);
+            }
+        }
+
+        ConstantIndex { entries }
+    }
+
+    /// the entry the parser resolved the next use of `key` from, or the first entry for values
+    /// that were added or are used more often than before
+    fn find<V: ToString>(&self, key: Key, kind: &'static str, value: V) -> Result<u16, WriteError> {
+        let entries = self.entries.get(&key).ok_or_else(|| WriteError::MissingConstant { kind, value: value.to_string() })?;
+        let next = entries.next.get();
+        entries.next.set(next + 1);
+        Ok(entries.recorded.get(next).cloned().unwrap_or(entries.first))
+    }
+
+    fn utf8(&self, value: &str) -> Result<u16, WriteError> {
+        self.find(Key::Utf8(value), "Utf8", value)
+    }
+
+    fn class(&self, name: &str) -> Result<u16, WriteError> {
+        self.find(Key::Class(name), "Class", name)
+    }
+
+    /// like `class`, but `None` is written as index 0
+    fn optional_class(&self, name: &Option<Cow<str>>) -> Result<u16, WriteError> {
+        match name {
+            Some(name) => self.class(name),
+            None => Ok(0)
+        }
+    }
+
+    fn optional_utf8(&self, value: &Option<Cow<str>>) -> Result<u16, WriteError> {
+        match value {
+            Some(value) => self.utf8(value),
+            None => Ok(0)
+        }
+    }
+
+    fn string(&self, value: &str) -> Result<u16, WriteError> {
+        self.find(Key::String(value), "String", value)
+    }
+
+    fn module(&self, name: &str) -> Result<u16, WriteError> {
+        self.find(Key::Module(name), "Module", name)
+    }
+
+    fn package(&self, name: &str) -> Result<u16, WriteError> {
+        self.find(Key::Package(name), "Package", name)
+    }
+
+    fn name_and_type(&self, name: &str, descriptor: &str) -> Result<u16, WriteError> {
+        self.find(Key::NameAndType(name, descriptor), "NameAndType", format!("{}:{}", name, descriptor))
+    }
+
+    fn integer(&self, value: i32) -> Result<u16, WriteError> {
+        self.find(Key::Integer(value), "Integer", value)
+    }
+
+    fn long(&self, value: i64) -> Result<u16, WriteError> {
+        self.find(Key::Long(value), "Long", value)
+    }
+
+    fn float(&self, value: f32) -> Result<u16, WriteError> {
+        self.find(Key::Float(value.to_bits()), "Float", value)
+    }
+
+    fn double(&self, value: f64) -> Result<u16, WriteError> {
+        self.find(Key::Double(value.to_bits()), "Double", value)
+    }
+
+    /// writes a `u2` count followed by the index of every class
+    fn put_classes(&self, out: &mut Vec<u8>, names: &[Cow<str>], what: &'static str) -> Result<(), WriteError> {
+        put_u16(out, count(names.len(), what)?);
+        for name in names {
+            put_u16(out, self.class(name)?);
+        }
+
+        Ok(())
+    }
+}
+
+fn write_attributes(out: &mut Vec<u8>, constants: &ConstantIndex, attributes: &[Attribute]) -> Result<(), WriteError> {
+    put_u16(out, count(attributes.len(), "attributes")?);
+    for attribute in attributes {
+        write_attribute(out, constants, attribute)?;
+    }
+
+    Ok(())
+}
+
+fn write_attribute(out: &mut Vec<u8>, constants: &ConstantIndex, attribute: &Attribute) -> Result<(), WriteError> {
+    // the name before the body, in the order the parser resolved them
+    put_u16(out, constants.utf8(attribute.name())?);
+    let mut body = Vec::new();
+    match attribute {
+        Attribute::LineNumberTable(lines) => {
+            put_u16(&mut body, count(lines.len(), "line numbers")?);
+            for (start, line) in lines {
+                put_u16(&mut body, *start);
+                put_u16(&mut body, *line);
+            }
+        }
+        Attribute::StackMapTable(frames) => {
+            put_u16(&mut body, count(frames.len(), "stack map frames")?);
+            for frame in frames {
+                write_frame(&mut body, constants, frame)?;
+            }
+        }
+        Attribute::ConstantValue(value) => put_u16(&mut body, match value {
+            ConstantValue::Integer(value) => constants.integer(*value)?,
+            ConstantValue::Float(value) => constants.float(*value)?,
+            ConstantValue::Long(value) => constants.long(*value)?,
+            ConstantValue::Double(value) => constants.double(*value)?,
+            ConstantValue::String(value) => constants.string(value)?,
+        }),
+        Attribute::LocalVariableTable(variables) | Attribute::LocalVariableTypeTable(variables) =>
+            write_local_variables(&mut body, constants, variables)?,
+        Attribute::CodeAttribute(code) => write_code(&mut body, constants, code)?,
+        Attribute::BootstrapMethods(methods) => {
+            put_u16(&mut body, count(methods.len(), "bootstrap methods")?);
+            for method in methods {
+                put_u16(&mut body, method.method_ref);
+                put_u16(&mut body, count(method.arguments.len(), "bootstrap arguments")?);
+                for argument in &method.arguments {
+                    put_u16(&mut body, *argument);
+                }
+            }
+        }
+        Attribute::InnerClasses(classes) => {
+            put_u16(&mut body, count(classes.len(), "inner classes")?);
+            for inner in classes {
+                put_u16(&mut body, constants.class(&inner.inner_class)?);
+                put_u16(&mut body, constants.optional_class(&inner.outer_class)?);
+                put_u16(&mut body, constants.optional_utf8(&inner.inner_name)?);
+                put_u16(&mut body, inner.flags.bits());
+            }
+        }
+        Attribute::EnclosingMethod(enclosing) => {
+            put_u16(&mut body, constants.class(&enclosing.class_name)?);
+            put_u16(&mut body, match enclosing.method {
+                Some((ref name, ref descriptor)) => constants.name_and_type(name, descriptor)?,
+                None => 0
+            });
+        }
+        Attribute::NestHost(name) | Attribute::ModuleMainClass(name) => put_u16(&mut body, constants.class(name)?),
+        Attribute::NestMembers(names) => constants.put_classes(&mut body, names, "nest members")?,
+        Attribute::PermittedSubclasses(names) => constants.put_classes(&mut body, names, "permitted subclasses")?,
+        Attribute::SourceFile(name) | Attribute::Signature(name) => put_u16(&mut body, constants.utf8(name)?),
+        Attribute::SourceDebugExtension(value) => body.extend_from_slice(value),
+        Attribute::Record(components) => {
+            put_u16(&mut body, count(components.len(), "record components")?);
+            for component in components {
+                put_u16(&mut body, constants.utf8(&component.name)?);
+                put_u16(&mut body, constants.utf8(&component.descriptor)?);
+                write_attributes(&mut body, constants, &component.attributes)?;
+            }
+        }
+        Attribute::Module(module) => write_module(&mut body, constants, module)?,
+        Attribute::ModulePackages(packages) => {
+            put_u16(&mut body, count(packages.len(), "packages")?);
+            for package in packages {
+                put_u16(&mut body, constants.package(package)?);
+            }
+        }
+        Attribute::RuntimeVisibleAnnotations(annotations) | Attribute::RuntimeInvisibleAnnotations(annotations) =>
+            write_annotations(&mut body, constants, annotations)?,
+        Attribute::RuntimeVisibleParameterAnnotations(parameters) |
+        Attribute::RuntimeInvisibleParameterAnnotations(parameters) => {
+            if parameters.len() > usize::from(u8::MAX) {
+                return Err(WriteError::TooLarge { what: "annotated parameters" });
+            }
+            put_u8(&mut body, parameters.len() as u8);
+            for annotations in parameters {
+                write_annotations(&mut body, constants, annotations)?;
+            }
+        }
+        Attribute::RuntimeVisibleTypeAnnotations(annotations) | Attribute::RuntimeInvisibleTypeAnnotations(annotations) => {
+            put_u16(&mut body, count(annotations.len(), "type annotations")?);
+            for annotation in annotations {
+                write_type_annotation(&mut body, constants, annotation)?;
+            }
+        }
+        Attribute::AnnotationDefault(value) => write_element_value(&mut body, constants, value)?,
+        Attribute::GenericAttribute { info, .. } => body.extend_from_slice(info),
+    }
+
+    put_u32(out, body.len() as u32);
+    out.extend_from_slice(&body);
+    Ok(())
+}
+
+fn write_code(out: &mut Vec<u8>, constants: &ConstantIndex, code: &CodeBlock) -> Result<(), WriteError> {
+    put_u16(out, code.max_stack);
+    put_u16(out, code.max_locals);
+    put_u32(out, code.code.len() as u32);
+    out.extend_from_slice(&code.code);
+
+    put_u16(out, count(code.exception_handlers.len(), "exception handlers")?);
+    for handler in &code.exception_handlers {
+        put_u16(out, handler.start_pc);
+        put_u16(out, handler.end_pc);
+        put_u16(out, handler.handler_pc);
+        put_u16(out, constants.optional_class(&handler.catch_type)?);
+    }
+
+    write_attributes(out, constants, &code.attributes)
+}
+
+fn write_local_variables(out: &mut Vec<u8>, constants: &ConstantIndex, variables: &[LocalVariableInfo]) -> Result<(), WriteError> {
+    put_u16(out, count(variables.len(), "local variables")?);
+    for variable in variables {
+        put_u16(out, variable.start_pc);
+        put_u16(out, variable.length);
+        put_u16(out, constants.utf8(&variable.name)?);
+        put_u16(out, constants.utf8(&variable.descriptor)?);
+        put_u16(out, variable.index);
+    }
+
+    Ok(())
+}
+
+/// uses the compact frame types where the offset allows it
+fn write_frame(out: &mut Vec<u8>, constants: &ConstantIndex, frame: &StackMapFrame) -> Result<(), WriteError> {
+    match frame {
+        StackMapFrame::Same { offset_delta } if *offset_delta < 64 => put_u8(out, *offset_delta as u8),
+        StackMapFrame::Same { offset_delta } | StackMapFrame::SameExtended { offset_delta } => {
+            put_u8(out, 251);
+            put_u16(out, *offset_delta);
+        }
+        StackMapFrame::SameLocals1StackItem { offset_delta, stack } if *offset_delta < 64 => {
+            put_u8(out, 64 + *offset_delta as u8);
+            write_verification_type(out, constants, stack)?;
+        }
+        StackMapFrame::SameLocals1StackItem { offset_delta, stack } |
+        StackMapFrame::SameLocals1StackItemExtended { offset_delta, stack } => {
+            put_u8(out, 247);
+            put_u16(out, *offset_delta);
+            write_verification_type(out, constants, stack)?;
+        }
+        StackMapFrame::Chop { offset_delta, count } => {
+            put_u8(out, 251 - count);
+            put_u16(out, *offset_delta);
+        }
+        StackMapFrame::Append { offset_delta, locals } => {
+            put_u8(out, 251 + locals.len() as u8);
+            put_u16(out, *offset_delta);
+            for local in locals {
+                write_verification_type(out, constants, local)?;
+            }
+        }
+        StackMapFrame::Full { offset_delta, locals, stack } => {
+            put_u8(out, 255);
+            put_u16(out, *offset_delta);
+            for types in &[locals, stack] {
+                put_u16(out, count(types.len(), "verification types")?);
+                for value_type in types.iter() {
+                    write_verification_type(out, constants, value_type)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn write_verification_type(out: &mut Vec<u8>, constants: &ConstantIndex, value_type: &VerificationType) -> Result<(), WriteError> {
+    match value_type {
+        VerificationType::Top => put_u8(out, 0),
+        VerificationType::Integer => put_u8(out, 1),
+        VerificationType::Float => put_u8(out, 2),
+        VerificationType::Double => put_u8(out, 3),
+        VerificationType::Long => put_u8(out, 4),
+        VerificationType::Null => put_u8(out, 5),
+        VerificationType::UninitializedThis => put_u8(out, 6),
+        VerificationType::Object(name) => {
+            put_u8(out, 7);
+            put_u16(out, constants.class(name)?);
+        }
+        VerificationType::Uninitialized(offset) => {
+            put_u8(out, 8);
+            put_u16(out, *offset);
+        }
+    }
+
+    Ok(())
+}
+
+fn write_annotations(out: &mut Vec<u8>, constants: &ConstantIndex, annotations: &[Annotation]) -> Result<(), WriteError> {
+    put_u16(out, count(annotations.len(), "annotations")?);
+    for annotation in annotations {
+        write_annotation(out, constants, annotation)?;
+    }
+
+    Ok(())
+}
+
+fn write_annotation(out: &mut Vec<u8>, constants: &ConstantIndex, annotation: &Annotation) -> Result<(), WriteError> {
+    put_u16(out, constants.utf8(&annotation.type_name)?);
+    put_u16(out, count(annotation.elements.len(), "annotation elements")?);
+    for (name, value) in &annotation.elements {
+        put_u16(out, constants.utf8(name)?);
+        write_element_value(out, constants, value)?;
+    }
+
+    Ok(())
+}
+
+fn write_element_value(out: &mut Vec<u8>, constants: &ConstantIndex, value: &ElementValue) -> Result<(), WriteError> {
+    let (tag, index) = match value {
+        ElementValue::Byte(value) => (b'B', constants.integer(i32::from(*value))?),
+        ElementValue::Char(value) => (b'C', constants.integer(i32::from(*value))?),
+        ElementValue::Double(value) => (b'D', constants.double(*value)?),
+        ElementValue::Float(value) => (b'F', constants.float(*value)?),
+        ElementValue::Integer(value) => (b'I', constants.integer(*value)?),
+        ElementValue::Long(value) => (b'J', constants.long(*value)?),
+        ElementValue::Short(value) => (b'S', constants.integer(i32::from(*value))?),
+        ElementValue::Boolean(value) => (b'Z', constants.integer(if *value { 1 } else { 0 })?),
+        ElementValue::String(value) => (b's', constants.utf8(value)?),
+        ElementValue::Class(name) => (b'c', constants.utf8(name)?),
+        ElementValue::Enum { type_name, const_name } => {
+            put_u8(out, b'e');
+            put_u16(out, constants.utf8(type_name)?);
+            put_u16(out, constants.utf8(const_name)?);
+            return Ok(());
+        }
+        ElementValue::Annotation(annotation) => {
+            put_u8(out, b'@');
+            return write_annotation(out, constants, annotation);
+        }
+        ElementValue::Array(values) => {
+            put_u8(out, b'[');
+            put_u16(out, count(values.len(), "array elements")?);
+            for value in values {
+                write_element_value(out, constants, value)?;
+            }
+            return Ok(());
+        }
+    };
+
+    put_u8(out, tag);
+    put_u16(out, index);
+    Ok(())
+}
+
+fn write_type_annotation(out: &mut Vec<u8>, constants: &ConstantIndex, annotation: &TypeAnnotation) -> Result<(), WriteError> {
+    match annotation.target {
+        TypeAnnotationTarget::TypeParameter { target_type, index } => {
+            put_u8(out, target_type);
+            put_u8(out, index);
+        }
+        TypeAnnotationTarget::SuperType { index } => {
+            put_u8(out, 0x10);
+            put_u16(out, index);
+        }
+        TypeAnnotationTarget::TypeParameterBound { target_type, type_parameter_index, bound_index } => {
+            put_u8(out, target_type);
+            put_u8(out, type_parameter_index);
+            put_u8(out, bound_index);
+        }
+        TypeAnnotationTarget::Empty { target_type } => put_u8(out, target_type),
+        TypeAnnotationTarget::FormalParameter { index } => {
+            put_u8(out, 0x16);
+            put_u8(out, index);
+        }
+        TypeAnnotationTarget::Throws { index } => {
+            put_u8(out, 0x17);
+            put_u16(out, index);
+        }
+        TypeAnnotationTarget::LocalVariable { target_type, ref table } => {
+            put_u8(out, target_type);
+            put_u16(out, count(table.len(), "local variable targets")?);
+            for entry in table {
+                put_u16(out, entry.start_pc);
+                put_u16(out, entry.length);
+                put_u16(out, entry.index);
+            }
+        }
+        TypeAnnotationTarget::Catch { exception_table_index } => {
+            put_u8(out, 0x42);
+            put_u16(out, exception_table_index);
+        }
+        TypeAnnotationTarget::Offset { target_type, offset } => {
+            put_u8(out, target_type);
+            put_u16(out, offset);
+        }
+        TypeAnnotationTarget::TypeArgument { target_type, offset, type_argument_index } => {
+            put_u8(out, target_type);
+            put_u16(out, offset);
+            put_u8(out, type_argument_index);
+        }
+    }
+
+    if annotation.path.len() > usize::from(u8::MAX) {
+        return Err(WriteError::TooLarge { what: "type path entries" });
+    }
+    put_u8(out, annotation.path.len() as u8);
+    for entry in &annotation.path {
+        let (kind, argument_index) = match entry {
+            TypePathEntry::Array => (0, 0),
+            TypePathEntry::Nested => (1, 0),
+            TypePathEntry::WildcardBound => (2, 0),
+            TypePathEntry::TypeArgument(index) => (3, *index),
+        };
+        put_u8(out, kind);
+        put_u8(out, argument_index);
+    }
+
+    write_annotation(out, constants, &annotation.annotation)
+}
+
+fn write_module(out: &mut Vec<u8>, constants: &ConstantIndex, module: &Module) -> Result<(), WriteError> {
+    put_u16(out, constants.module(&module.name)?);
+    put_u16(out, module.flags.bits());
+    put_u16(out, constants.optional_utf8(&module.version)?);
+
+    put_u16(out, count(module.requires.len(), "requires")?);
+    for requires in &module.requires {
+        put_u16(out, constants.module(&requires.module)?);
+        put_u16(out, requires.flags.bits());
+        put_u16(out, constants.optional_utf8(&requires.version)?);
+    }
+
+    write_exports(out, constants, &module.exports)?;
+    write_exports(out, constants, &module.opens)?;
+    constants.put_classes(out, &module.uses, "uses")?;
+
+    put_u16(out, count(module.provides.len(), "provides")?);
+    for provides in &module.provides {
+        put_u16(out, constants.class(&provides.service)?);
+        constants.put_classes(out, &provides.with, "service implementations")?;
+    }
+
+    Ok(())
+}
+
+fn write_exports(out: &mut Vec<u8>, constants: &ConstantIndex, exports: &[Exports]) -> Result<(), WriteError> {
+    put_u16(out, count(exports.len(), "exports")?);
+    for export in exports {
+        put_u16(out, constants.package(&export.package)?);
+        put_u16(out, export.flags.bits());
+        put_u16(out, count(export.to.len(), "modules")?);
+        for module in &export.to {
+            put_u16(out, constants.module(module)?);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use java::class_file::{read_class_file, ClassQuery};
+    use java::samples;
+
+    #[test]
+    fn it_writes_every_sample_back_unchanged() {
+        for (name, bytes) in samples::ALL {
+            let class = read_class_file(bytes).unwrap();
+            let written = write_class_file(&class).unwrap();
+            assert!(&written[..] == *bytes, "{} changed in the round trip", name);
+        }
+    }
+
+    #[test]
+    fn it_keeps_duplicate_constants_apart() {
+        let mut class = read_class_file(samples::HELLO_WORLD).unwrap();
+        // an unpaired surrogate decodes to the same replacement character as the real one
+        let replacement: &[u8] = &[0xef, 0xbf, 0xbd];
+        class.constants.push(ConstantType::Utf8 { value: Cow::Borrowed("\u{fffd}"), bytes: Cow::Borrowed(replacement) });
+        let first = class.constants.len() as u16;
+        let surrogate: &[u8] = &[0xed, 0xa0, 0x80];
+        class.constants.push(ConstantType::Utf8 { value: Cow::Borrowed("\u{fffd}"), bytes: Cow::Borrowed(surrogate) });
+        let second = class.constants.len() as u16;
+        class.attributes.retain(|attribute| attribute.name() != "SourceFile");
+        class.attributes.push(Attribute::SourceFile(Cow::Borrowed("\u{fffd}")));
+
+        let mut written = write_class_file(&class).unwrap();
+        let end = written.len();
+        assert_eq!(first.to_be_bytes(), written[end - 2..]);
+        written[end - 2..].copy_from_slice(&second.to_be_bytes());
+
+        let class = read_class_file(&written).unwrap();
+        assert_eq!(written, write_class_file(&class).unwrap());
+    }
+
+    #[test]
+    fn it_writes_dynamic_constants() {
+        let mut class = read_class_file(samples::HELLO_WORLD).unwrap();
+        class.constants.push(ConstantType::Dynamic { bootstrap_method_attr_index: 1, name_and_type_index: 2 });
+        let index = class.constants.len() as u16;
+
+        let written = write_class_file(&class).unwrap();
+        let class = read_class_file(&written).unwrap();
+        match class.constants.get(index) {
+            Ok(ConstantType::Dynamic { bootstrap_method_attr_index: 1, name_and_type_index: 2 }) => (),
+            other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn it_writes_the_source_debug_extension_verbatim() {
+        let mut class = read_class_file(samples::HELLO_WORLD).unwrap();
+        let name: &[u8] = b"SourceDebugExtension";
+        class.constants.push(ConstantType::Utf8 { value: Cow::Borrowed("SourceDebugExtension"), bytes: Cow::Borrowed(name) });
+        let smap: &[u8] = b"SMAP\n\xff\x00\n*E\n";
+        class.attributes.push(Attribute::SourceDebugExtension(Cow::Borrowed(smap)));
+
+        let written = write_class_file(&class).unwrap();
+        let class = read_class_file(&written).unwrap();
+        assert_eq!(Some(smap), class.source_debug_extension().unwrap().as_deref());
+        assert_eq!(None, class.source_debug_extension_text().unwrap());
+    }
+
+    #[test]
+    fn it_reports_missing_constants() {
+        let mut class = read_class_file(samples::HELLO_WORLD).unwrap();
+        class.attributes.push(Attribute::SourceFile(Cow::Borrowed("Missing.java")));
+        match write_class_file(&class) {
+            Err(WriteError::MissingConstant { kind: "Utf8", ref value }) if value == "Missing.java" => (),
+            other => panic!("unexpected {:?}", other)
+        }
+    }
+}
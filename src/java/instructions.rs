@@ -10,9 +10,10 @@ pub enum ReadInstructionError<P> {
     InvalidOpcode { opcode: u8 },
 }
 
+/// `$pc` names the pc of the instruction, which the size and the parser of an instruction can use
 macro_rules! instruction {
-    ( $( $num:pat => ($size:expr): [ $($parser:tt)* ] => $name:ident ( $($a:ident: $t:ty ),* ) ),* ) => {
-          #[derive(Debug, Copy, Clone)]
+    ( $pc:ident; $( $num:pat => ($size:expr): [ $($parser:tt)* ] => $name:ident ( $($a:ident: $t:ty ),* ) ),* ) => {
+          #[derive(Debug, Clone)]
           pub enum Instruction {
             $(
                 $name ( ( $($t),* ) )
@@ -20,9 +21,12 @@ macro_rules! instruction {
           }
 
           impl Instruction {
-                pub fn get_size(&self) -> usize {
+                /// the size in bytes, including the opcode and the operands.
+                /// the switches are padded relative to the start of the code, so their size depends on `pc`.
+                #[allow(unused_variables, unused_parens)]
+                pub fn get_size(&self, $pc: usize) -> usize {
                     match self {
-                        $(Instruction::$name(_) => $size),*
+                        $(Instruction::$name(( $($a),* )) => $size),*
                     }
                 }
 
@@ -34,7 +38,7 @@ macro_rules! instruction {
                             break;
                         }
 
-                        match Instruction::read(remaining) {
+                        match Instruction::read(remaining, input.len() - remaining.len()) {
                             Ok((rem, ins)) => {
                                 vec.push(ins);
                                 remaining = rem;
@@ -48,7 +52,7 @@ macro_rules! instruction {
                     return Result::Ok(vec);
                 }
 
-                fn read(input: &[u8]) -> IResult<&[u8], Instruction> {
+                fn read(input: &[u8], $pc: usize) -> IResult<&[u8], Instruction> {
                     match be_u8(input) {
                         $(
                             Ok((rem, $num)) => match do_parse!(rem, $($parser)* ) {
@@ -66,7 +70,7 @@ macro_rules! instruction {
 }
 
 
-instruction!(
+instruction!(pc;
     0x00 => (1): [ () ] => NOOP(),
     0x01 => (1): [ () ] => AConstNull(),
     0x02 => (1): [ () ] => IConstm1(),
@@ -199,7 +203,7 @@ instruction!(
     0x81 => (1): [ () ] => LOr(),
     0x82 => (1): [ () ] => IXor(),
     0x83 => (1): [ () ] => LXor(),
-    0x84 => (3): [ a: be_u8 >> b: be_u8 >> ( ( a, b ) ) ] => IInc( a: u8, b: u8 ),
+    0x84 => (3): [ a: be_u8 >> b: be_i8 >> ( ( a, b ) ) ] => IInc( a: u8, b: i8 ),
     0x85 => (1): [ () ] => I2L(),
     0x86 => (1): [ () ] => I2F(),
     0x87 => (1): [ () ] => I2D(),
@@ -237,8 +241,8 @@ instruction!(
     0xa7 => (3): [ a: be_i16 >> ( ( a ) ) ] => Goto( a: i16 ),
     0xa8 => (3): [ a: be_i16 >> ( ( a ) ) ] => JSR( a: i16 ),
     0xa9 => (2): [ a: be_u8  >> ( ( a ) ) ] => Ret( a: u8 ),
-    0xaa => (2): [ a: be_u8  >> ( ( a ) ) ] => TableSwitch( a: u8 ),
-    0xab => (9): [ a: be_u64 >> b: be_u64 >> ( ( a, b ) ) ] => LookupSwitch( a: u64, b: u64 ),   //TODO: this is not entirely correct, the length is not fixed
+    0xaa => (a.size(pc)): [ a: call!(TableSwitch::read, pc) >> ( ( a ) ) ] => TableSwitch( a: TableSwitch ),
+    0xab => (a.size(pc)): [ a: call!(LookupSwitch::read, pc) >> ( ( a ) ) ] => LookupSwitch( a: LookupSwitch ),
     0xac => (1): [ () ] => IReturn(),
     0xad => (1): [ () ] => LReturn(),
     0xae => (1): [ () ] => FReturn(),
@@ -252,8 +256,8 @@ instruction!(
     0xb6 => (3): [ a: be_u16 >> ( ( a ) ) ] => InvokeVirtual( a: u16 ),
     0xb7 => (3): [ a: be_u16 >> ( ( a ) ) ] => InvokeSpecial( a: u16 ),
     0xb8 => (3): [ a: be_u16 >> ( ( a ) ) ] => InvokeStatic( a: u16 ),
-    0xb9 => (5): [ a: be_u64 >> ( ( a ) ) ] => InvokeInterface( a: u64 ),
-    0xba => (5): [ a: be_u64 >> ( ( a ) ) ] => InvokeDynamic( a: u64),
+    0xb9 => (5): [ a: be_u16 >> b: be_u8 >> be_u8 >> ( ( a, b ) ) ] => InvokeInterface( a: u16, b: u8 ),
+    0xba => (5): [ a: be_u16 >> be_u16 >> ( ( a ) ) ] => InvokeDynamic( a: u16 ),
     0xbb => (3): [ a: be_u16 >> ( ( a ) ) ] => New( a: u16 ),
     0xbc => (2): [ a: be_u8 >> ( ( a ) ) ] => NewArray( a: u8 ),
     0xbd => (3): [ a: be_u16 >> ( ( a ) ) ] => AAewArray( a: u16 ),
@@ -263,13 +267,250 @@ instruction!(
     0xc1 => (3): [ a: be_u16 >> ( ( a ) ) ] => InstanceOf( a: u16 ),
     0xc2 => (1): [ () ] => MonitorEnter(),
     0xc3 => (1): [ () ] => MonitorExit(),
-    0xc4 => (1): [ a: be_u16 >> b: be_u8 >> ( ( a, b ) ) ] => Wide( a: u16, b: u8),
-    0xc5 => (1): [ a: be_u16 >> b: be_u8 >> ( ( a, b ) ) ] => MultianeWArray( a: u16, b: u8),
+    0xc4 => (a.size()): [ a: call!(Wide::read) >> ( ( a ) ) ] => Wide( a: Wide ),
+    0xc5 => (4): [ a: be_u16 >> b: be_u8 >> ( ( a, b ) ) ] => MultianeWArray( a: u16, b: u8),
     0xc6 => (3): [ a: be_u16 >> ( ( a ) ) ] => IfNull( a: u16 ),
     0xc7 => (3): [ a: be_u16 >> ( ( a ) ) ] => IfNonNull( a: u16 ),
-    0xc8 => (5): [ a: be_u64 >> ( ( a ) ) ] => GotoW( a: u64),
-    0xc9 => (5): [ a: be_u64 >> ( ( a ) ) ] => JSRW( a: u64),
+    0xc8 => (5): [ a: be_i32 >> ( ( a ) ) ] => GotoW( a: i32 ),
+    0xc9 => (5): [ a: be_i32 >> ( ( a ) ) ] => JSRW( a: i32 ),
     0xca => (1): [ () ] => Breakpoint(),
     0xfe => (1): [ () ] => ImpDep1(),
     0xff => (1): [ () ] => ImpDep2()
-);
\ No newline at end of file
+);
+
+/// the switches align their operands to a multiple of four bytes from the start of the code
+fn padding(pc: usize) -> usize {
+    3 - pc % 4
+}
+
+/// the operands of `tableswitch`, with a jump offset for every key from `low` to `high`.
+/// all offsets are relative to the pc of the instruction.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TableSwitch {
+    pub default: i32,
+    pub low: i32,
+    pub high: i32,
+    pub offsets: Vec<i32>,
+}
+
+impl TableSwitch {
+    /// reads the operands of the `tableswitch` at `pc`, after its opcode
+    fn read(input: &[u8], pc: usize) -> IResult<&[u8], TableSwitch> {
+        do_parse!(input,
+            take!(padding(pc)) >>
+            default: be_i32 >>
+            low: be_i32 >>
+            high: verify!(be_i32, |high: i32| high >= low) >>
+            offsets: count!(be_i32, (i64::from(high) - i64::from(low) + 1) as usize) >>
+            ( TableSwitch { default, low, high, offsets } )
+        )
+    }
+
+    /// the size of the instruction at `pc`, including the opcode and the padding
+    pub fn size(&self, pc: usize) -> usize {
+        1 + padding(pc) + 12 + 4 * self.offsets.len()
+    }
+
+    /// the offset to jump to for `key`
+    pub fn offset(&self, key: i32) -> i32 {
+        if key < self.low || key > self.high {
+            self.default
+        } else {
+            self.offsets[(i64::from(key) - i64::from(self.low)) as usize]
+        }
+    }
+
+    /// the default offset, then the offsets of all keys
+    pub fn branches(&self) -> Vec<i32> {
+        Some(self.default).into_iter().chain(self.offsets.iter().cloned()).collect()
+    }
+}
+
+/// the operands of `lookupswitch`, with `(key, offset)` pairs sorted by key.
+/// all offsets are relative to the pc of the instruction.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct LookupSwitch {
+    pub default: i32,
+    pub pairs: Vec<(i32, i32)>,
+}
+
+impl LookupSwitch {
+    /// reads the operands of the `lookupswitch` at `pc`, after its opcode
+    fn read(input: &[u8], pc: usize) -> IResult<&[u8], LookupSwitch> {
+        do_parse!(input,
+            take!(padding(pc)) >>
+            default: be_i32 >>
+            npairs: verify!(be_i32, |npairs: i32| npairs >= 0) >>
+            pairs: count!(tuple!(be_i32, be_i32), npairs as usize) >>
+            ( LookupSwitch { default, pairs } )
+        )
+    }
+
+    /// the size of the instruction at `pc`, including the opcode and the padding
+    pub fn size(&self, pc: usize) -> usize {
+        1 + padding(pc) + 8 + 8 * self.pairs.len()
+    }
+
+    /// the offset to jump to for `key`
+    pub fn offset(&self, key: i32) -> i32 {
+        match self.pairs.binary_search_by_key(&key, |&(key, _)| key) {
+            Ok(idx) => self.pairs[idx].1,
+            Err(_) => self.default
+        }
+    }
+
+    /// the default offset, then the offsets of all keys
+    pub fn branches(&self) -> Vec<i32> {
+        Some(self.default).into_iter().chain(self.pairs.iter().map(|&(_, offset)| offset)).collect()
+    }
+}
+
+/// the instruction after the `wide` prefix, with a 16 bit local index
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Wide {
+    ILoad(u16),
+    LLoad(u16),
+    FLoad(u16),
+    DLoad(u16),
+    ALoad(u16),
+    IStore(u16),
+    LStore(u16),
+    FStore(u16),
+    DStore(u16),
+    AStore(u16),
+    Ret(u16),
+    /// the local index and a 16 bit constant
+    IInc(u16, i16),
+}
+
+impl Wide {
+    /// reads the instruction after the `wide` opcode
+    fn read(input: &[u8]) -> IResult<&[u8], Wide> {
+        switch!(input, be_u8,
+            0x15 => map!(be_u16, Wide::ILoad) |
+            0x16 => map!(be_u16, Wide::LLoad) |
+            0x17 => map!(be_u16, Wide::FLoad) |
+            0x18 => map!(be_u16, Wide::DLoad) |
+            0x19 => map!(be_u16, Wide::ALoad) |
+            0x36 => map!(be_u16, Wide::IStore) |
+            0x37 => map!(be_u16, Wide::LStore) |
+            0x38 => map!(be_u16, Wide::FStore) |
+            0x39 => map!(be_u16, Wide::DStore) |
+            0x3a => map!(be_u16, Wide::AStore) |
+            0xa9 => map!(be_u16, Wide::Ret) |
+            0x84 => do_parse!(index: be_u16 >> value: be_i16 >> ( Wide::IInc(index, value) ))
+        )
+    }
+
+    /// the size including the `wide` opcode, 6 bytes for `iinc` and 4 for all others
+    pub fn size(&self) -> usize {
+        match self {
+            Wide::IInc(..) => 6,
+            _ => 4
+        }
+    }
+}
+
+impl Instruction {
+    /// decodes the instruction at `pc`
+    pub fn read_at(code: &[u8], pc: usize) -> Option<Instruction> {
+        let input = code.get(pc..)?;
+        Instruction::read(input, pc).ok().map(|(_, instruction)| instruction)
+    }
+
+    /// the length in bytes of the instruction at `pc`, including its operands.
+    /// `None` if the opcode is unknown or the instruction does not fit into `code`.
+    pub fn length_at(code: &[u8], pc: usize) -> Option<usize> {
+        Instruction::read_at(code, pc).map(|instruction| instruction.get_size(pc))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use java::class_file::read_class_file;
+    use java::samples;
+
+    #[test]
+    fn it_pads_switches_from_the_start_of_the_code() {
+        // the operands of a `tableswitch` at pc 1 start at pc 4
+        let code = [
+            0x00, 0xaa, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x20, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02,
+            0x00, 0x00, 0x00, 0x18, 0x00, 0x00, 0x00, 0x1c,
+        ];
+        let table = TableSwitch { default: 32, low: 1, high: 2, offsets: vec![24, 28] };
+        match Instruction::read_at(&code, 1) {
+            Some(Instruction::TableSwitch(ref read)) => assert_eq!(table, *read),
+            other => panic!("unexpected {:?}", other)
+        }
+        assert_eq!(Some(23), Instruction::length_at(&code, 1));
+        assert_eq!(vec![32, 24, 28], table.branches());
+        assert_eq!(28, table.offset(2));
+        assert_eq!(32, table.offset(3));
+
+        // at pc 3 there is no padding
+        let lookup = LookupSwitch { default: 9, pairs: vec![(-1, 5), (7, 6)] };
+        match Instruction::read_at(&[0x00, 0x00, 0x00, 0xab, 0, 0, 0, 9, 0, 0, 0, 2, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 5, 0, 0, 0, 7, 0, 0, 0, 6], 3) {
+            Some(Instruction::LookupSwitch(ref read)) => assert_eq!(lookup, *read),
+            other => panic!("unexpected {:?}", other)
+        }
+        assert_eq!(25, lookup.size(3));
+        assert_eq!(28, lookup.size(0));
+        assert_eq!(5, lookup.offset(-1));
+        assert_eq!(9, lookup.offset(0));
+
+        // the table would end after the code
+        assert!(Instruction::read_at(&code[..20], 1).is_none());
+    }
+
+    #[test]
+    fn it_decodes_every_switch_of_a_method() {
+        let class = read_class_file(samples::SWITCHES).unwrap();
+        for method in &class.methods {
+            let code = method.get_code().unwrap();
+            let instructions = Instruction::read_all(code.code()).unwrap();
+            let mut pc = 0;
+            for instruction in &instructions {
+                pc += instruction.get_size(pc);
+            }
+            assert_eq!(code.code().len(), pc, "{} does not end with its last instruction", method.name);
+        }
+
+        let dense = class.methods.iter().find(|method| method.name == "dense").unwrap();
+        match Instruction::read_all(dense.get_code().unwrap().code()).unwrap()[1] {
+            Instruction::TableSwitch(ref table) => {
+                assert_eq!((1, 4, 43), (table.low, table.high, table.default));
+                assert_eq!(vec![31, 34, 37, 40], table.offsets);
+            }
+            ref other => panic!("unexpected {:?}", other)
+        }
+
+        let sparse = class.methods.iter().find(|method| method.name == "sparse").unwrap();
+        match Instruction::read_all(sparse.get_code().unwrap().code()).unwrap()[1] {
+            Instruction::LookupSwitch(ref lookup) => {
+                assert_eq!(vec![(-100, 35), (7, 37), (1000, 39)], lookup.pairs);
+                assert_eq!(41, lookup.default);
+            }
+            ref other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn it_decodes_the_instruction_after_wide() {
+        // `iload 300`, `iinc 300 -1000` and `ret 2`
+        let code = [0xc4, 0x15, 0x01, 0x2c, 0xc4, 0x84, 0x01, 0x2c, 0xfc, 0x18, 0xc4, 0xa9, 0x00, 0x02];
+        let widened: Vec<(Wide, usize)> = Instruction::read_all(&code).unwrap().into_iter()
+            .map(|instruction| match instruction {
+                Instruction::Wide(wide) => (wide, instruction.get_size(0)),
+                other => panic!("unexpected {:?}", other)
+            })
+            .collect();
+        assert_eq!(vec![(Wide::ILoad(300), 4), (Wide::IInc(300, -1000), 6), (Wide::Ret(2), 4)], widened);
+        assert_eq!(Some(6), Instruction::length_at(&code, 4));
+
+        // only loads, stores, `iinc` and `ret` can be widened
+        assert!(Instruction::read_at(&[0xc4, 0x60, 0x00, 0x01], 0).is_none());
+        assert!(Instruction::read_at(&code[..8], 4).is_none());
+    }
+}
@@ -1,3 +1,6 @@
 pub mod class_file;
 pub mod instructions;
-pub mod runtime;
\ No newline at end of file
+pub mod runtime;
+#[cfg(test)]
+pub mod samples;
+pub mod verifier;
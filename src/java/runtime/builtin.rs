@@ -0,0 +1,63 @@
+//! the methods of the java class library the runtime provides itself, since there is no jdk to
+//! load them from. only what javac emits for `String` and `enum` switches is there: string hash
+//! codes and equality, the enum constructor and ordinals, and cloning arrays.
+
+use java::runtime::{Object, Runtime, RuntimeError, StackValue};
+use std::collections::HashMap;
+
+impl Runtime {
+    /// calls a method of a class the runtime provides on the object `receiver`
+    pub(super) fn invoke_builtin(&mut self, class: &str, receiver: usize, name: &str, descriptor: &str, arguments: Vec<StackValue>) -> Result<Option<StackValue>, RuntimeError> {
+        let object = self.objects.get_mut(receiver).ok_or(RuntimeError::NullPointer)?;
+        let result = match (class, object, name, descriptor, arguments.as_slice()) {
+            ("java/lang/Object", _, "<init>", "()V", []) => return Ok(None),
+            ("java/lang/Object", Object::Array(elements), "clone", "()Ljava/lang/Object;", []) => {
+                let elements = elements.clone();
+                self.allocate(Object::Array(elements))
+            }
+            ("java/lang/String", Object::String(value), "hashCode", "()I", []) => StackValue::Integer(i64::from(string_hash(value))),
+            ("java/lang/String", Object::String(value), "equals", "(Ljava/lang/Object;)Z", [other]) => {
+                let value = Object::String(value.clone());
+                let equal = match *other {
+                    StackValue::Reference(other) => self.objects.get(other) == Some(&value),
+                    _ => false
+                };
+                StackValue::Integer(if equal { 1 } else { 0 })
+            }
+            // every enum constant is created by its class initializer with its name and ordinal
+            ("java/lang/Enum", Object::Instance { fields, .. }, "<init>", "(Ljava/lang/String;I)V", [constant, ordinal]) => {
+                fields.insert(String::from("name"), constant.clone());
+                fields.insert(String::from("ordinal"), ordinal.clone());
+                return Ok(None);
+            }
+            ("java/lang/Enum", Object::Instance { fields, .. }, "name", "()Ljava/lang/String;", []) => enum_field(fields, "name")?,
+            ("java/lang/Enum", Object::Instance { fields, .. }, "ordinal", "()I", []) => enum_field(fields, "ordinal")?,
+            _ => return Err(RuntimeError::MethodNotFound)
+        };
+
+        Ok(Some(result))
+    }
+}
+
+/// a field `java.lang.Enum` sets in its constructor
+fn enum_field(fields: &HashMap<String, StackValue>, name: &str) -> Result<StackValue, RuntimeError> {
+    fields.get(name).cloned().ok_or(RuntimeError::FieldNotFound)
+}
+
+/// `String.hashCode`, computed over the utf-16 code units like java does
+fn string_hash(value: &str) -> i32 {
+    value.encode_utf16().fold(0i32, |hash, unit| hash.wrapping_mul(31).wrapping_add(i32::from(unit)))
+}
+
+#[cfg(test)]
+mod test {
+    use java::runtime::builtin::string_hash;
+
+    #[test]
+    fn it_hashes_strings_like_java() {
+        assert_eq!(0, string_hash(""));
+        assert_eq!(110182, string_hash("one"));
+        assert_eq!(string_hash("Aa"), string_hash("BB"));
+        assert_eq!(-1_789_667_313, string_hash("a longer string that overflows"));
+    }
+}
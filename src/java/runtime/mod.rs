@@ -1,18 +1,30 @@
 use java::class_file::Method;
-use std::collections::HashMap;
+use java::class_file::MethodDescriptor;
+use std::str::FromStr;
+use std::collections::{HashMap, HashSet};
 use java::class_file::ClassFile;
-use std::path::PathBuf;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use java::class_file::ConstantType;
+use java::class_file::ConstantPoolError;
+use java::class_file::ClassFileError;
+use java::class_file::ClassQuery;
+use java::class_file::read_class_file;
 use java::class_file::ValueType;
+use java::class_file::version::{VersionError, VersionPolicy};
+use java::verifier::{self, ClassHierarchy, ClassInfo, VerifyError};
 
 
-use java::instructions::Instruction;
+use java::instructions::{Instruction, Wide};
+
+mod builtin;
 
 /// these type of errors should not happen at all.
-/// Triggering one of these means the jvm is probably buggy since the compiler should prevent these.
-/// This is for stuff like "we tried to pop the stack but it was empty" or "i need to load an int,
-/// but theres a string on the stack"….
+/// stuff like "we tried to pop the stack but it was empty" or "i need to load an int, but theres
+/// a string on the stack" is rejected by the verifier when the class is loaded, so triggering one
+/// of these means the jvm is probably buggy.
 ///
 /// we might trigger something like this when a class file does not contain the expected methods.
 /// this is something the compiler cannot prevent since the user could just swap out the class file.
@@ -26,10 +38,54 @@ pub enum RuntimeError {
     EmptyStack,
     #[fail(display = "runtime error: method not found")]
     MethodNotFound,
+    #[fail(display = "runtime error: field not found")]
+    FieldNotFound,
+    #[fail(display = "runtime error: null pointer")]
+    NullPointer,
     #[fail(display = "runtime error: no such variable ")]
     VariableOutOfScope,
     #[fail(display = "runtime error: variable at index {} has the wrong type. expected: {}", offset, expected)]
     VariableType { expected: String, offset: usize },
+    #[fail(display = "runtime error: {}", _0)]
+    InvalidConstant(#[cause] ConstantPoolError),
+    #[fail(display = "runtime error: {}", _0)]
+    InvalidClassFile(#[cause] ClassFileError),
+    #[fail(display = "runtime error: {}", _0)]
+    Io(#[cause] io::Error),
+    #[fail(display = "runtime error: {}", _0)]
+    UnsupportedClass(#[cause] VersionError),
+    #[fail(display = "runtime error: {}", _0)]
+    VerifyError(#[cause] VerifyError),
+}
+
+impl From<ConstantPoolError> for RuntimeError {
+    fn from(err: ConstantPoolError) -> RuntimeError {
+        RuntimeError::InvalidConstant(err)
+    }
+}
+
+impl From<ClassFileError> for RuntimeError {
+    fn from(err: ClassFileError) -> RuntimeError {
+        RuntimeError::InvalidClassFile(err)
+    }
+}
+
+impl From<io::Error> for RuntimeError {
+    fn from(err: io::Error) -> RuntimeError {
+        RuntimeError::Io(err)
+    }
+}
+
+impl From<VersionError> for RuntimeError {
+    fn from(err: VersionError) -> RuntimeError {
+        RuntimeError::UnsupportedClass(err)
+    }
+}
+
+impl From<VerifyError> for RuntimeError {
+    fn from(err: VerifyError) -> RuntimeError {
+        RuntimeError::VerifyError(err)
+    }
 }
 
 #[derive(Debug)]
@@ -37,13 +93,37 @@ enum LocalVariable {
     None,
     Null,
     Integer(i64),
+    /// the index of an object on the heap
+    Reference(usize),
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 enum StackValue {
     None,
     Null,
     Integer(i64),
+    /// the index of an object on the heap
+    Reference(usize),
+}
+
+impl From<StackValue> for LocalVariable {
+    fn from(value: StackValue) -> LocalVariable {
+        match value {
+            StackValue::None => LocalVariable::None,
+            StackValue::Null => LocalVariable::Null,
+            StackValue::Integer(intvalue) => LocalVariable::Integer(intvalue),
+            StackValue::Reference(reference) => LocalVariable::Reference(reference),
+        }
+    }
+}
+
+/// an object on the heap
+#[derive(Debug, Eq, PartialEq)]
+enum Object {
+    String(String),
+    Array(Vec<StackValue>),
+    /// an object created by `new`, with the fields that were written so far by name
+    Instance { class: String, fields: HashMap<String, StackValue> },
 }
 
 #[derive(Debug)]
@@ -107,6 +187,35 @@ impl StackFrame {
     fn push_stack(&mut self, value: StackValue) {
         self.stack.push(value)
     }
+
+    fn pop_integer(&mut self) -> Result<i64, RuntimeError> {
+        match self.pop_stack() {
+            Some(StackValue::Integer(intvalue)) => Ok(intvalue),
+            Some(_) => Err(RuntimeError::StackType { expected: String::from("integer") }),
+            None => Err(RuntimeError::EmptyStack)
+        }
+    }
+
+    /// pops a reference that is not `null`
+    fn pop_reference(&mut self) -> Result<usize, RuntimeError> {
+        match self.pop_stack() {
+            Some(StackValue::Reference(reference)) => Ok(reference),
+            Some(StackValue::Null) => Err(RuntimeError::NullPointer),
+            Some(_) => Err(RuntimeError::StackType { expected: String::from("reference") }),
+            None => Err(RuntimeError::EmptyStack)
+        }
+    }
+
+    /// pops the arguments of a method with the given descriptor, the first argument comes first
+    fn pop_arguments(&mut self, descriptor: &str) -> Result<Vec<StackValue>, RuntimeError> {
+        let count = MethodDescriptor::from_str(descriptor)?.arguments.len();
+        let mut arguments = (0..count)
+            .map(|_| self.pop_stack().ok_or(RuntimeError::EmptyStack))
+            .collect::<Result<Vec<StackValue>, RuntimeError>>()?;
+        arguments.reverse();
+
+        Ok(arguments)
+    }
 }
 
 /// this might be terrible named (it is).
@@ -114,82 +223,86 @@ impl StackFrame {
 /// has been executed
 enum InstructionResult {
     Continue,
-    GotoRelative(i16),
+    GotoRelative(i32),
     GotoAbsolute(usize),
     Return(Option<StackValue>),
 }
 
-pub struct Context<'b> {
+pub struct Context {
     return_value: Option<StackValue>,
-    class: Arc<ClassFile<'b>>,
+    class: Arc<ClassFile<'static>>,
 }
 
-pub struct Runtime<'a> {
-    classes: HashMap<String, Arc<ClassFile<'a>>>,
+/// classes are stored as owned copies, so they can be loaded from anywhere at any time and
+/// shared between threads
+pub struct Runtime {
+    classes: HashMap<String, Arc<ClassFile<'static>>>,
     classpath: Vec<PathBuf>,
     main_class: String,
-    class_index_map: HashMap<String, HashMap<usize, String>>,
+    /// which class file versions `load_class` accepts
+    version_policy: VersionPolicy,
+    /// the classes whose static initializer already ran
+    initialized: HashSet<String>,
+    /// by class and field name
+    static_fields: HashMap<(String, String), StackValue>,
+    /// objects are never freed, there is no garbage collector yet
+    objects: Vec<Object>,
+    /// the string objects of the string constants, by their value
+    interned: HashMap<String, usize>,
 }
 
-impl<'a> Runtime<'a> {
-    pub fn create(main_class: ClassFile<'a>) -> Runtime<'a> {
-        let name = String::from(main_class.get_class_name());
+impl Runtime {
+    pub fn create(main_class: ClassFile) -> Result<Runtime, RuntimeError> {
+        Runtime::create_with_policy(main_class, VersionPolicy::default())
+    }
+
+    pub fn create_with_policy(main_class: ClassFile, version_policy: VersionPolicy) -> Result<Runtime, RuntimeError> {
+        let name = String::from(main_class.get_class_name()?);
         let mut rt = Runtime {
             classes: HashMap::new(),
             classpath: vec![PathBuf::from(".")],
-            class_index_map: HashMap::new(),
             main_class: name,
+            version_policy,
+            initialized: HashSet::new(),
+            static_fields: HashMap::new(),
+            objects: Vec::new(),
+            interned: HashMap::new(),
         };
 
-        rt.load_class(main_class);
+        rt.load_class(main_class)?;
 
-        return rt;
+        Ok(rt)
     }
 
-    fn build_class_index_map(class: &ClassFile<'a>) -> HashMap<usize, String> {
-        let cla_idx_map = class.constants
-            .iter()
-            .filter_map(|mref| match mref {
-                ConstantType::MethodRef { class_index: cli, .. } => Some(cli),
-                _ => None
-            })
-            .filter_map(|class_index| {
-                match class.get_constant(*class_index) {
-                    Some(ConstantType::Class { name_index: idx }) => Some((class_index, idx)),
-                    _ => None
-                }
-            })
-            .filter_map(|(class_index, name_index)| {
-                match class.get_constant(*name_index) {
-                    Some(ConstantType::Utf8 { value }) => Some((class_index, value.clone())),
-                    _ => None
-                }
-            });
-
-        let mut map = HashMap::new();
-        for (class_index, name) in cla_idx_map {
-            map.insert(usize::from(*class_index), String::from(name));
-        }
-
-        return map;
+    /// loads a class. anything it still borrows from its input buffer is copied.
+    /// fails if the version policy does not accept the class, or if one of its methods is not
+    /// type-safe.
+    pub fn load_class(&mut self, class: ClassFile) -> Result<(), RuntimeError> {
+        self.version_policy.check(&class)?;
+        verifier::verify_class(&class, self)?;
+        let class = class.into_owned();
+        let name = String::from(class.get_class_name()?);
+        self.classes.insert(name, Arc::new(class));
+        Ok(())
     }
 
-    pub fn load_class(&mut self, class: ClassFile<'a>) {
-        let map = Runtime::build_class_index_map(&class);
-        let name = String::from(class.get_class_name());
-        self.class_index_map.insert(name.clone(), map);
-        self.classes.insert(name, Arc::new(class));
+    /// reads and loads a class file from disk
+    pub fn load_class_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), RuntimeError> {
+        let buffer = fs::read(path)?;
+        let class = read_class_file(&buffer)?;
+        self.load_class(class)
     }
 
     pub fn run(&mut self) {
         let class = self.classes.get(&self.main_class).expect("no main class loaded").clone();
         let method = class.methods.iter().find(|method| method.name.eq("main"));
         if method.is_none() {
-            eprintln!("Class {} does not have a main method", class.get_class_name());
+            eprintln!("Class {} does not have a main method", self.main_class);
             return;
         }
 
-        match self.run_method(method.unwrap(), class.clone(), vec![]) {
+        let main_class = self.main_class.clone();
+        match self.initialize(&main_class).and_then(|_| self.run_method(method.unwrap(), class.clone(), vec![])) {
             Ok(ret) => println!("main return value: {:?}", ret),
             Err(err) => eprintln!("runtime error: {:?}", err)
         }
@@ -197,13 +310,134 @@ impl<'a> Runtime<'a> {
 
     #[cfg(test)]
     pub fn exec_method_on_main(&mut self, method_name: &str) -> Result<Option<StackValue>, RuntimeError> {
+        self.exec_method_on_main_with(method_name, vec![])
+    }
+
+    #[cfg(test)]
+    fn exec_method_on_main_with(&mut self, method_name: &str, arguments: Vec<LocalVariable>) -> Result<Option<StackValue>, RuntimeError> {
         let class = self.classes.get(&self.main_class).expect("no main class loaded").clone();
         let method = class.methods.iter().find(|method| method.name.eq(method_name));
         if method.is_none() {
-            return Err(RuntimeError::GenericError { message: format!("Class {} does not have a main method", class.get_class_name()) });
+            return Err(RuntimeError::GenericError { message: format!("Class {} does not have a main method", self.main_class) });
+        }
+
+        let main_class = self.main_class.clone();
+        self.initialize(&main_class)?;
+        self.run_method(method.unwrap(), class.clone(), arguments)
+    }
+
+    /// runs the static initializer of a loaded class the first time the class is used.
+    /// the class counts as initialized while its initializer runs.
+    fn initialize(&mut self, name: &str) -> Result<(), RuntimeError> {
+        let class = match self.classes.get(name) {
+            Some(class) => class.clone(),
+            None => return Ok(())
+        };
+        if !self.initialized.insert(String::from(name)) {
+            return Ok(());
+        }
+
+        match class.methods.iter().find(|method| method.name == "<clinit>") {
+            Some(method) => self.run_method(method, class.clone(), vec![]).map(|_| ()),
+            None => Ok(())
+        }
+    }
+
+    /// static fields that were not written yet have their default value
+    fn get_static(&mut self, class: &str, name: &str, descriptor: &str) -> Result<StackValue, RuntimeError> {
+        if !self.classes.contains_key(class) {
+            return Err(RuntimeError::FieldNotFound);
         }
 
-        return self.run_method(method.unwrap(), class.clone(), vec![]);
+        self.initialize(class)?;
+        match self.static_fields.get(&(String::from(class), String::from(name))) {
+            Some(value) => Ok(value.clone()),
+            None if ValueType::from_str(descriptor)?.is_reference() => Ok(StackValue::Null),
+            None => Ok(StackValue::Integer(0))
+        }
+    }
+
+    fn put_static(&mut self, class: &str, name: &str, value: StackValue) -> Result<(), RuntimeError> {
+        if !self.classes.contains_key(class) {
+            return Err(RuntimeError::FieldNotFound);
+        }
+
+        self.initialize(class)?;
+        self.static_fields.insert((String::from(class), String::from(name)), value);
+        Ok(())
+    }
+
+    fn allocate(&mut self, object: Object) -> StackValue {
+        self.objects.push(object);
+        StackValue::Reference(self.objects.len() - 1)
+    }
+
+    /// the same string object for every string constant with this value
+    fn intern(&mut self, value: &str) -> StackValue {
+        if let Some(reference) = self.interned.get(value) {
+            return StackValue::Reference(*reference);
+        }
+
+        let reference = self.allocate(Object::String(String::from(value)));
+        if let StackValue::Reference(index) = reference {
+            self.interned.insert(String::from(value), index);
+        }
+        reference
+    }
+
+    /// pushes an `Integer` or `String` constant
+    fn load_constant(&mut self, class: &ClassFile, index: u16) -> Result<StackValue, RuntimeError> {
+        match class.get_constant(index)? {
+            ConstantType::Integer { value } => Ok(StackValue::Integer(i64::from(*value))),
+            ConstantType::String { string_index } => Ok(self.intern(class.constants.utf8(*string_index)?)),
+            other => Err(RuntimeError::GenericError { message: format!("unsupported constant {}", other.name()) })
+        }
+    }
+
+    /// runs a static method of a loaded class
+    fn invoke_static(&mut self, class: &str, name: &str, descriptor: &str, arguments: Vec<StackValue>) -> Result<Option<StackValue>, RuntimeError> {
+        let loaded = self.classes.get(class).cloned().ok_or(RuntimeError::MethodNotFound)?;
+
+        self.initialize(class)?;
+        let method = loaded.methods.iter()
+            .find(|method| method.name == name && method.descriptor == descriptor)
+            .ok_or(RuntimeError::MethodNotFound)?;
+        self.run_method(method, loaded.clone(), arguments.into_iter().map(LocalVariable::from).collect())
+    }
+
+    /// runs the method of the class of `receiver`, or the one it inherits
+    fn invoke_virtual(&mut self, receiver: usize, name: &str, descriptor: &str, arguments: Vec<StackValue>) -> Result<Option<StackValue>, RuntimeError> {
+        let class = match self.objects.get(receiver) {
+            Some(Object::Instance { class, .. }) => class.clone(),
+            Some(Object::String(_)) => String::from("java/lang/String"),
+            Some(Object::Array(_)) => String::from("java/lang/Object"),
+            None => return Err(RuntimeError::NullPointer)
+        };
+
+        self.invoke_special(&class, receiver, name, descriptor, arguments)
+    }
+
+    /// runs the method of `class` on `receiver`, or the one `class` inherits. once the search
+    /// reaches a class that is not loaded, the runtime provides the method itself, see `builtin`.
+    fn invoke_special(&mut self, class: &str, receiver: usize, name: &str, descriptor: &str, arguments: Vec<StackValue>) -> Result<Option<StackValue>, RuntimeError> {
+        let mut class = String::from(class);
+        loop {
+            let loaded = match self.classes.get(&class) {
+                Some(loaded) => loaded.clone(),
+                None => return self.invoke_builtin(&class, receiver, name, descriptor, arguments)
+            };
+
+            if let Some(method) = loaded.methods.iter().find(|method| method.name == name && method.descriptor == descriptor) {
+                let mut variables = vec![LocalVariable::Reference(receiver)];
+                variables.extend(arguments.into_iter().map(LocalVariable::from));
+                return self.run_method(method, loaded.clone(), variables);
+            }
+
+            class = match loaded.super_class_name()? {
+                Some(super_class) => String::from(super_class),
+                None => return Err(RuntimeError::MethodNotFound)
+            };
+        }
     }
 
     /// stores the top stack value into the local variable at `offset` as an integer
@@ -239,7 +473,52 @@ impl<'a> Runtime<'a> {
         Ok(())
     }
 
-    fn exec(&mut self, instruction: &Instruction, mut stack_frame: &mut StackFrame, context: &mut Context<'a>) -> Result<InstructionResult, RuntimeError> {
+    /// loads a reference from local variable `offset` onto the stack
+    fn exec_aload(stack_frame: &mut StackFrame, offset: usize) -> Result<(), RuntimeError> {
+        let value = match stack_frame.get_variable(offset) {
+            Some(LocalVariable::Reference(reference)) => StackValue::Reference(*reference),
+            Some(LocalVariable::Null) => StackValue::Null,
+            Some(_) => return Err(RuntimeError::VariableType { expected: String::from("reference"), offset }),
+            None => return Err(RuntimeError::VariableOutOfScope)
+        };
+
+        stack_frame.push_stack(value);
+        Ok(())
+    }
+
+    /// stores the reference on top of the stack into local variable `offset`
+    fn exec_astore(stack_frame: &mut StackFrame, offset: usize) -> Result<(), RuntimeError> {
+        let variable = match stack_frame.pop_stack() {
+            Some(StackValue::Reference(reference)) => LocalVariable::Reference(reference),
+            Some(StackValue::Null) => LocalVariable::Null,
+            Some(_) => return Err(RuntimeError::StackType { expected: String::from("reference") }),
+            None => return Err(RuntimeError::EmptyStack)
+        };
+
+        stack_frame.set_variable(offset, variable);
+        Ok(())
+    }
+
+    /// the elements of the array `reference` points to
+    fn array_mut(&mut self, reference: usize) -> Result<&mut Vec<StackValue>, RuntimeError> {
+        match self.objects.get_mut(reference) {
+            Some(Object::Array(elements)) => Ok(elements),
+            _ => Err(RuntimeError::StackType { expected: String::from("array") })
+        }
+    }
+
+    /// adds `value` to the integer in local variable `offset`
+    fn exec_iinc(stack_frame: &mut StackFrame, offset: usize, value: i64) -> Result<(), RuntimeError> {
+        match stack_frame.get_variable_mut(offset) {
+            Some(LocalVariable::Integer(intvalue)) => *intvalue += value,
+            Some(_) => return Err(RuntimeError::VariableType { expected: String::from("integer"), offset }),
+            None => return Err(RuntimeError::VariableOutOfScope)
+        }
+
+        Ok(())
+    }
+
+    fn exec(&mut self, instruction: &Instruction, mut stack_frame: &mut StackFrame, context: &mut Context) -> Result<InstructionResult, RuntimeError> {
         // since most of the instructions just operate on the StackFrame, and the return value
         // it might be useful to move these implementations somewhere else.
         // although some instructions actually need more knownledge about the context, like the
@@ -258,12 +537,32 @@ impl<'a> Runtime<'a> {
                 stack_frame.push_stack(StackValue::Integer(i64::from(*value))),
             Instruction::SIPush(value) =>
                 stack_frame.push_stack(StackValue::Integer(i64::from(*value))),
+            Instruction::LDC(index) => {
+                let value = self.load_constant(&context.class, u16::from(*index))?;
+                stack_frame.push_stack(value);
+            }
+            Instruction::LDCW(index) => {
+                let value = self.load_constant(&context.class, *index)?;
+                stack_frame.push_stack(value);
+            }
             Instruction::ILoad(offset) => Runtime::exec_iload(&mut stack_frame, usize::from(*offset))?,
             Instruction::ILoad0(()) => Runtime::exec_iload(&mut stack_frame, 0)?,
             Instruction::ILoad1(()) => Runtime::exec_iload(&mut stack_frame, 1)?,
             Instruction::ILoad2(()) => Runtime::exec_iload(&mut stack_frame, 2)?,
             Instruction::ILoad3(()) => Runtime::exec_iload(&mut stack_frame, 3)?,
+            Instruction::ALoad(offset) => Runtime::exec_aload(stack_frame, usize::from(*offset))?,
             // 20..
+            Instruction::ALoad0(()) => Runtime::exec_aload(stack_frame, 0)?,
+            Instruction::ALoad1(()) => Runtime::exec_aload(stack_frame, 1)?,
+            Instruction::ALoad2(()) => Runtime::exec_aload(stack_frame, 2)?,
+            Instruction::ALoad3(()) => Runtime::exec_aload(stack_frame, 3)?,
+            Instruction::IALoad(()) => {
+                let index = stack_frame.pop_integer()?;
+                let array = stack_frame.pop_reference()?;
+                let value = self.array_mut(array)?.get(index as usize).cloned()
+                    .ok_or_else(|| RuntimeError::GenericError { message: format!("array index {} is out of bounds", index) })?;
+                stack_frame.push_stack(value);
+            }
             // 30..
             Instruction::IStore(offset) => Runtime::exec_istore(&mut stack_frame, usize::from(*offset))?,
             Instruction::IStore0(()) => Runtime::exec_istore(&mut stack_frame, 0)?,
@@ -273,8 +572,35 @@ impl<'a> Runtime<'a> {
             Instruction::IStore2(()) => Runtime::exec_istore(&mut stack_frame, 2)?,
 
             Instruction::IStore3(()) => Runtime::exec_istore(&mut stack_frame, 3)?,
+            Instruction::AStore(offset) => Runtime::exec_astore(stack_frame, usize::from(*offset))?,
             // 40..
+            Instruction::AStore0(()) => Runtime::exec_astore(stack_frame, 0)?,
+            Instruction::AStore1(()) => Runtime::exec_astore(stack_frame, 1)?,
+            Instruction::AStore2(()) => Runtime::exec_astore(stack_frame, 2)?,
+            Instruction::AStore3(()) => Runtime::exec_astore(stack_frame, 3)?,
+            Instruction::IAStore(()) => {
+                let value = stack_frame.pop_integer()?;
+                let index = stack_frame.pop_integer()?;
+                let array = stack_frame.pop_reference()?;
+                match self.array_mut(array)?.get_mut(index as usize) {
+                    Some(element) => *element = StackValue::Integer(value),
+                    None => return Err(RuntimeError::GenericError { message: format!("array index {} is out of bounds", index) })
+                }
+            }
             // 50..
+            Instruction::AAStore(()) => {
+                let value = stack_frame.pop_stack().ok_or(RuntimeError::EmptyStack)?;
+                let index = stack_frame.pop_integer()?;
+                let array = stack_frame.pop_reference()?;
+                match self.array_mut(array)?.get_mut(index as usize) {
+                    Some(element) => *element = value,
+                    None => return Err(RuntimeError::GenericError { message: format!("array index {} is out of bounds", index) })
+                }
+            }
+            Instruction::Dup(()) => {
+                let value = stack_frame.stack.last().cloned().ok_or(RuntimeError::EmptyStack)?;
+                stack_frame.push_stack(value);
+            }
             // 60..
             Instruction::IAdd(()) => match (stack_frame.pop_stack(), stack_frame.pop_stack()) {
                 (Some(StackValue::Integer(lh)), Some(StackValue::Integer(rh))) =>
@@ -287,11 +613,25 @@ impl<'a> Runtime<'a> {
                     return Err(RuntimeError::GenericError { message: format!("IAdd") })
             }
             // 80..
-            Instruction::IInc((offset, value)) => match stack_frame.get_variable_mut(usize::from(*offset)) {
-                Some(LocalVariable::Integer(intvalue)) => *intvalue = *intvalue + 1,
-                Some(_) => return Err(RuntimeError::VariableType { expected: format!("integer"), offset: usize::from(*offset) }),
-                None => return Err(RuntimeError::VariableOutOfScope)
-            }
+            Instruction::IInc((offset, value)) => Runtime::exec_iinc(stack_frame, usize::from(*offset), i64::from(*value))?,
+
+            Instruction::Wide(Wide::ILoad(offset)) => Runtime::exec_iload(stack_frame, usize::from(*offset))?,
+            Instruction::Wide(Wide::IStore(offset)) => Runtime::exec_istore(stack_frame, usize::from(*offset))?,
+            Instruction::Wide(Wide::IInc(offset, value)) => Runtime::exec_iinc(stack_frame, usize::from(*offset), i64::from(*value))?,
+            Instruction::Wide(Wide::ALoad(offset)) => Runtime::exec_aload(stack_frame, usize::from(*offset))?,
+            Instruction::Wide(Wide::AStore(offset)) => Runtime::exec_astore(stack_frame, usize::from(*offset))?,
+
+            // 90..
+            Instruction::Ifeq(offset) => return if stack_frame.pop_integer()? == 0 {
+                Ok(InstructionResult::GotoRelative(i32::from(*offset)))
+            } else {
+                Ok(InstructionResult::Continue)
+            },
+            Instruction::Ifne(offset) => return if stack_frame.pop_integer()? != 0 {
+                Ok(InstructionResult::GotoRelative(i32::from(*offset)))
+            } else {
+                Ok(InstructionResult::Continue)
+            },
 
             // a0..
             Instruction::IfICmpGE(instruction) => {
@@ -299,7 +639,7 @@ impl<'a> Runtime<'a> {
                     (Some(StackValue::Integer(b)), Some(StackValue::Integer(a))) => {
                         println!("if_icmp_ge {} >= {}?", a, b);
                         return if a >= b {
-                            Ok(InstructionResult::GotoRelative(*instruction))
+                            Ok(InstructionResult::GotoRelative(i32::from(*instruction)))
                         } else {
                             Ok(InstructionResult::Continue)
                         };
@@ -314,7 +654,15 @@ impl<'a> Runtime<'a> {
             }
 
             Instruction::Goto(offset) => {
-                return Ok(InstructionResult::GotoRelative(*offset));
+                return Ok(InstructionResult::GotoRelative(i32::from(*offset)));
+            }
+            Instruction::TableSwitch(table) => {
+                let key = stack_frame.pop_integer()?;
+                return Ok(InstructionResult::GotoRelative(table.offset(key as i32)));
+            }
+            Instruction::LookupSwitch(lookup) => {
+                let key = stack_frame.pop_integer()?;
+                return Ok(InstructionResult::GotoRelative(lookup.offset(key as i32)));
             }
 
             Instruction::IReturn(()) => return match stack_frame.pop_stack() {
@@ -322,87 +670,102 @@ impl<'a> Runtime<'a> {
                 Some(_) => Err(RuntimeError::StackType { expected: format!("Integer") }),
                 None => Err(RuntimeError::EmptyStack)
             },
+            Instruction::AReturn(()) => match stack_frame.pop_stack() {
+                Some(value @ StackValue::Reference(_)) | Some(value @ StackValue::Null) => return Ok(InstructionResult::Return(Some(value))),
+                Some(_) => return Err(RuntimeError::StackType { expected: String::from("reference") }),
+                None => return Err(RuntimeError::EmptyStack)
+            },
 
             // b0..
             Instruction::Return(()) => return Ok(InstructionResult::Return(None)),
-            Instruction::InvokeStatic(method_offset) => {
-                let class = &context.class;
-                match class.get_constant(*method_offset) {
-                    Some(ConstantType::MethodRef { class_index, name_and_type_index }) => {
-                        let cls_name = {
-                            let other_class = self.class_index_map.get(class.get_class_name()).unwrap().get(&(*class_index as usize));
-                            if other_class.is_none() {
-                                return Err(RuntimeError::GenericError { message: format!("class not found {}", class_index) });
-                            }
-                            other_class.unwrap().clone()
-                        };
-
-
-                        if cls_name.eq(class.get_class_name()) {
-                            let method = match class.get_method_from_nat(*name_and_type_index) {
-                                Some(m) => m,
-                                None => return Err(RuntimeError::MethodNotFound)
-                            };
-
-                            let mut args = method.get_signature().arguments.iter().map(|arg_type| {
-                                //TODO: we really should check the type here. some day.
-                                match stack_frame.pop_stack() {
-                                    Some(StackValue::Integer(intvalue)) => Ok(LocalVariable::Integer(intvalue)),
-                                    Some(StackValue::None) => Ok(LocalVariable::None), //??? None => undefined, Null => null.
-                                    Some(StackValue::Null) => Ok(LocalVariable::Null),
-                                    None => Err(RuntimeError::EmptyStack)
-                                }
-                            }).collect::<Result<Vec<LocalVariable>, RuntimeError>>()?;
-                            args.reverse();
-
-                            println!("{:?}, {:?}", method, args);
-                            let return_val = self.run_method(method, class.clone(), args);
-                            println!(" => return value: {:?}", return_val);
-                            match return_val {
-                                Ok(Some(stack_value)) => stack_frame.push_stack(stack_value),
-                                Ok(None) => (),
-                                Err(err) => return Err(err)
-                            };
-                        }
-                        //
-                    }
-                    Some(_) => {
-                        return Err(RuntimeError::GenericError {
-                            message: format!("invalid method offset {}", method_offset)
-                        });
-                    }
-                    None => {
-                        return Err(RuntimeError::GenericError {
-                            message: format!("invalid method offset {}", method_offset)
-                        });
-                    }
+            Instruction::GetStatic(index) => {
+                let field = context.class.constants.field_ref(*index)?;
+                let value = self.get_static(field.class_name, field.name, field.descriptor)?;
+                stack_frame.push_stack(value);
+            }
+            Instruction::PutStatic(index) => {
+                let field = context.class.constants.field_ref(*index)?;
+                let value = stack_frame.pop_stack().ok_or(RuntimeError::EmptyStack)?;
+                self.put_static(field.class_name, field.name, value)?;
+            }
+            Instruction::InvokeVirtual(index) => {
+                let method = context.class.constants.method_ref(*index)?;
+                let arguments = stack_frame.pop_arguments(method.descriptor)?;
+                let receiver = stack_frame.pop_reference()?;
+                if let Some(value) = self.invoke_virtual(receiver, method.name, method.descriptor, arguments)? {
+                    stack_frame.push_stack(value);
+                }
+            }
+            Instruction::InvokeSpecial(index) => {
+                let method = context.class.constants.method_ref(*index)?;
+                let arguments = stack_frame.pop_arguments(method.descriptor)?;
+                let receiver = stack_frame.pop_reference()?;
+                if let Some(value) = self.invoke_special(method.class_name, receiver, method.name, method.descriptor, arguments)? {
+                    stack_frame.push_stack(value);
+                }
+            }
+            Instruction::InvokeStatic(index) => {
+                let method = context.class.constants.method_ref(*index)?;
+                let arguments = stack_frame.pop_arguments(method.descriptor)?;
+                if let Some(value) = self.invoke_static(method.class_name, method.name, method.descriptor, arguments)? {
+                    stack_frame.push_stack(value);
                 }
             }
+            // the interpreter only knows integers, so every primitive array starts out as zeros
+            Instruction::NewArray(_) => {
+                let length = stack_frame.pop_integer()?;
+                if length < 0 {
+                    return Err(RuntimeError::GenericError { message: format!("negative array size {}", length) });
+                }
+                let array = self.allocate(Object::Array(vec![StackValue::Integer(0); length as usize]));
+                stack_frame.push_stack(array);
+            }
+            Instruction::AAewArray(_) => {
+                let length = stack_frame.pop_integer()?;
+                if length < 0 {
+                    return Err(RuntimeError::GenericError { message: format!("negative array size {}", length) });
+                }
+                let array = self.allocate(Object::Array(vec![StackValue::Null; length as usize]));
+                stack_frame.push_stack(array);
+            }
+            Instruction::New(index) => {
+                let class = context.class.constants.class_name(*index)?;
+                self.initialize(class)?;
+                let object = self.allocate(Object::Instance { class: String::from(class), fields: HashMap::new() });
+                stack_frame.push_stack(object);
+            }
+            // there are no exceptions yet, so a failing cast is not detected
+            Instruction::CheckCast(_) => (),
+            Instruction::ArrayLength(()) => {
+                let array = stack_frame.pop_reference()?;
+                let length = self.array_mut(array)?.len();
+                stack_frame.push_stack(StackValue::Integer(length as i64));
+            }
             _ => return Err(RuntimeError::GenericError { message: format!("unknown instruction") })
         };
 
         Ok(InstructionResult::Continue)
     }
 
-    fn run_method(&mut self, method: &Method, class: Arc<ClassFile<'a>>, arguments: Vec<LocalVariable>) -> Result<Option<StackValue>, RuntimeError> {
+    fn run_method(&mut self, method: &Method, class: Arc<ClassFile<'static>>, arguments: Vec<LocalVariable>) -> Result<Option<StackValue>, RuntimeError> {
         println!("running method {}", method.name);
         let mut stack_frame = StackFrame::for_method(method, arguments);
         let mut return_value: Option<StackValue> = None;
         println!("{:?}", stack_frame);
         let mut instruction_counter: usize = 0;
-        let mut ins = method.instructions();
+        let mut ins = method.instructions()?;
         let mut context = Context {
             return_value: None,
             class: class.clone(),
         };
 
         while let Some(instruction) = ins.next() {
-            println!("{}: {:?}, {}", instruction_counter, instruction, instruction.get_size());
+            println!("{}: {:?}, {}", instruction_counter, instruction, instruction.get_size(instruction_counter));
 
             match self.exec(&instruction, &mut stack_frame, &mut context) {
                 Ok(InstructionResult::Continue) => {
                     /* nop, just keep executing */
-                    instruction_counter += instruction.get_size();
+                    instruction_counter += instruction.get_size(instruction_counter);
                 }
                 Ok(InstructionResult::GotoAbsolute(offset)) => {
                     instruction_counter = offset;
@@ -420,7 +783,7 @@ impl<'a> Runtime<'a> {
                     ins.goto(instruction_counter);
                 }
                 Ok(InstructionResult::Return(return_value)) => {
-                    self.check_return_type(method.get_signature().return_type, &return_value);
+                    self.check_return_type(method.get_signature()?.return_type, &return_value)?;
                     return Ok(return_value);
                 }
                 Err(err) => return Err(err)
@@ -432,8 +795,8 @@ impl<'a> Runtime<'a> {
     }
 
     /// this is just here for internal verification.
-    /// the compiler should prevent these type of errors.
-    /// if something like this happens, the jvm has f**ked up, or the bytecode is broken
+    /// the verifier already checked the return instructions when the class was loaded.
+    /// if something like this happens, the jvm has f**ked up
     fn check_return_type(&self, return_type: ValueType, return_value: &Option<StackValue>) -> Result<(), RuntimeError> {
         return match return_type {
             ValueType::Void => if return_value.is_some() {
@@ -451,19 +814,29 @@ impl<'a> Runtime<'a> {
     }
 }
 
+/// the verifier sees the classes that are already loaded
+impl ClassHierarchy for Runtime {
+    fn class_info(&self, name: &str) -> Option<ClassInfo> {
+        self.classes.get(name).and_then(|class| ClassInfo::of(&**class).ok())
+    }
+}
+
 #[cfg(test)]
 mod test {
     use java::class_file::ClassFile;
     use java::class_file::Method;
     use java::class_file::read_class_file;
     use java::runtime::Runtime;
-    use java::runtime::StackValue;
+    use java::runtime::{LocalVariable, Object, RuntimeError, StackValue};
+    use java::verifier::Problem;
+    use java::class_file::version::{VersionError, VersionPolicy};
+    use java::samples;
 
     #[test]
     fn test_basic_math() {
-        let simple_match_sample = include_bytes!("../../../sample/SimpleMath.class");
-        let class = read_class_file(simple_match_sample).unwrap().1;
-        let mut rt = Runtime::create(class);
+        let simple_match_sample = samples::SIMPLE_MATH;
+        let class = read_class_file(simple_match_sample).unwrap();
+        let mut rt = Runtime::create(class).unwrap();
         let result = rt.exec_method_on_main("testMe").unwrap();
 
         assert_eq!(Some(StackValue::Integer(46)), result)
@@ -471,11 +844,100 @@ mod test {
 
     #[test]
     fn test_basic_math_with_loop() {
-        let simple_match_sample = include_bytes!("../../../sample/SimpleMathWithLoop.class");
-        let class = read_class_file(simple_match_sample).unwrap().1;
-        let mut rt = Runtime::create(class);
+        let simple_match_sample = samples::SIMPLE_MATH_WITH_LOOP;
+        let class = read_class_file(simple_match_sample).unwrap();
+        let mut rt = Runtime::create(class).unwrap();
         let result = rt.exec_method_on_main("testMe").unwrap();
 
         assert_eq!(Some(StackValue::Integer(203)), result)
     }
+
+    #[test]
+    fn test_switches() {
+        let class = read_class_file(samples::SWITCHES).unwrap();
+        let mut rt = Runtime::create(class).unwrap();
+        let result = rt.exec_method_on_main("testMe").unwrap();
+
+        assert_eq!(Some(StackValue::Integer(103)), result)
+    }
+
+    #[test]
+    fn test_string_switches() {
+        let class = read_class_file(samples::SWITCHES).unwrap();
+        let mut rt = Runtime::create(class).unwrap();
+
+        for &(name, expected) in &[("one", 1), ("two", 2), ("three", 0)] {
+            // not the interned constant, so `equals` has to compare the contents
+            let name = rt.allocate(Object::String(String::from(name)));
+            let result = rt.exec_method_on_main_with("named", vec![LocalVariable::from(name)]).unwrap();
+            assert_eq!(Some(StackValue::Integer(expected)), result);
+        }
+    }
+
+    #[test]
+    fn test_enum_switches() {
+        let class = read_class_file(samples::SWITCHES).unwrap();
+        let mut rt = Runtime::create(class).unwrap();
+        rt.load_class(read_class_file(samples::SWITCHES_UNIT).unwrap()).unwrap();
+        rt.load_class(read_class_file(samples::SWITCHES_MAP).unwrap()).unwrap();
+
+        for &(unit, expected) in &[("SECONDS", 1), ("MINUTES", 60), ("HOURS", 0)] {
+            let unit = rt.get_static("Switches$Unit", unit, "LSwitches$Unit;").unwrap();
+            let result = rt.exec_method_on_main_with("seconds", vec![LocalVariable::from(unit)]).unwrap();
+            assert_eq!(Some(StackValue::Integer(expected)), result);
+        }
+        assert!(rt.initialized.contains("Switches$1"));
+    }
+
+    #[test]
+    fn test_wide_locals() {
+        let class = read_class_file(samples::WIDE_LOCALS).unwrap();
+        let mut rt = Runtime::create(class).unwrap();
+        let result = rt.exec_method_on_main("testMe").unwrap();
+
+        assert_eq!(Some(StackValue::Integer(905)), result)
+    }
+
+    #[test]
+    fn test_load_class_file_from_disk() {
+        let class = read_class_file(samples::TINY).unwrap();
+        let mut rt = Runtime::create(class).unwrap();
+        rt.load_class_file("sample/SimpleMath.class").unwrap();
+        assert!(rt.classes.contains_key("SimpleMath"));
+        assert!(rt.load_class_file("sample/Missing.class").is_err());
+    }
+
+    #[test]
+    fn test_version_policy_rejects_classes() {
+        let tiny = samples::TINY;
+        let mut class = read_class_file(tiny).unwrap();
+        class.version = (62, 0);
+        match Runtime::create(class) {
+            Err(RuntimeError::UnsupportedClass(VersionError::Unsupported { major: 62, .. })) => (),
+            Err(err) => panic!("unexpected {:?}", err),
+            Ok(_) => panic!("loaded an unsupported class")
+        }
+
+        let mut class = read_class_file(tiny).unwrap();
+        class.version = (62, 0);
+        let policy = VersionPolicy { max_major: 62, ..VersionPolicy::default() };
+        assert!(Runtime::create_with_policy(class, policy).is_ok());
+    }
+
+    #[test]
+    fn test_classes_are_verified_when_loaded() {
+        let mut bytes = samples::SIMPLE_MATH.to_vec();
+        // `iadd` in `add(II)I` becomes `fadd`
+        let at = bytes.windows(4).position(|code| code == [0x1a, 0x1b, 0x60, 0xac]).unwrap();
+        bytes[at + 2] = 0x62;
+
+        match Runtime::create(read_class_file(&bytes).unwrap()) {
+            Err(RuntimeError::VerifyError(err)) => match *err.problem {
+                Problem::WrongStackType { .. } => assert_eq!((String::from("add(II)I"), 2), (err.method, err.pc)),
+                ref other => panic!("unexpected {:?}", other)
+            },
+            Err(err) => panic!("unexpected {:?}", err),
+            Ok(_) => panic!("loaded a class that is not type-safe")
+        }
+    }
 }
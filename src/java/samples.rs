@@ -0,0 +1,70 @@
+//! the compiled classes in `sample/`, shared by the tests of all modules
+
+pub const ANNOTATIONS: &[u8] = include_bytes!("../../sample/Annotations.class");
+pub const DEMO_CLASS: &[u8] = include_bytes!("../../sample/DemoClass.class");
+pub const FILTER_EXAMPLE: &[u8] = include_bytes!("../../sample/FilterExample.class");
+pub const GENERICS: &[u8] = include_bytes!("../../sample/Generics.class");
+pub const HELLO_WORLD: &[u8] = include_bytes!("../../sample/HelloWorld.class");
+pub const ANNOTATIONS_INFO: &[u8] = include_bytes!("../../sample/Annotations$Info.class");
+pub const ANNOTATIONS_MARKER: &[u8] = include_bytes!("../../sample/Annotations$Marker.class");
+pub const ANNOTATIONS_NULLABLE: &[u8] = include_bytes!("../../sample/Annotations$Nullable.class");
+pub const INTERFACES: &[u8] = include_bytes!("../../sample/Interfaces.class");
+pub const LAMBDAS: &[u8] = include_bytes!("../../sample/Lambdas.class");
+pub const LOCAL_VARIABLES: &[u8] = include_bytes!("../../sample/LocalVariables.class");
+pub const MODIFIERS: &[u8] = include_bytes!("../../sample/Modifiers.class");
+pub const MODULE_INFO: &[u8] = include_bytes!("../../sample/module/module-info.class");
+pub const NESTING: &[u8] = include_bytes!("../../sample/Nesting.class");
+pub const NESTING_ANONYMOUS: &[u8] = include_bytes!("../../sample/Nesting$1.class");
+pub const NESTING_LOCAL: &[u8] = include_bytes!("../../sample/Nesting$1Local.class");
+pub const NESTING_MEMBER: &[u8] = include_bytes!("../../sample/Nesting$Member.class");
+pub const NESTING_STATIC_MEMBER: &[u8] = include_bytes!("../../sample/Nesting$StaticMember.class");
+pub const SHAPES: &[u8] = include_bytes!("../../sample/Shapes.class");
+pub const SHAPES_CIRCLE: &[u8] = include_bytes!("../../sample/Shapes$Circle.class");
+pub const SHAPES_POLYGON: &[u8] = include_bytes!("../../sample/Shapes$Polygon.class");
+pub const SHAPES_TAG: &[u8] = include_bytes!("../../sample/Shapes$Tag.class");
+pub const SIMPLE_MATH: &[u8] = include_bytes!("../../sample/SimpleMath.class");
+pub const SIMPLE_MATH_WITH_LOOP: &[u8] = include_bytes!("../../sample/SimpleMathWithLoop.class");
+pub const STACK_FRAMES: &[u8] = include_bytes!("../../sample/StackFrames.class");
+pub const SWITCHES: &[u8] = include_bytes!("../../sample/Switches.class");
+pub const SWITCHES_MAP: &[u8] = include_bytes!("../../sample/Switches$1.class");
+pub const SWITCHES_UNIT: &[u8] = include_bytes!("../../sample/Switches$Unit.class");
+pub const TINY: &[u8] = include_bytes!("../../sample/Tiny.class");
+pub const TRY_CATCH: &[u8] = include_bytes!("../../sample/TryCatch.class");
+pub const WIDE_CONSTANTS: &[u8] = include_bytes!("../../sample/WideConstants.class");
+pub const WIDE_LOCALS: &[u8] = include_bytes!("../../sample/WideLocals.class");
+
+/// every sample with its path below `sample/`
+pub const ALL: &[(&str, &[u8])] = &[
+    ("Annotations.class", ANNOTATIONS),
+    ("DemoClass.class", DEMO_CLASS),
+    ("FilterExample.class", FILTER_EXAMPLE),
+    ("Generics.class", GENERICS),
+    ("HelloWorld.class", HELLO_WORLD),
+    ("Annotations$Info.class", ANNOTATIONS_INFO),
+    ("Annotations$Marker.class", ANNOTATIONS_MARKER),
+    ("Annotations$Nullable.class", ANNOTATIONS_NULLABLE),
+    ("Interfaces.class", INTERFACES),
+    ("Lambdas.class", LAMBDAS),
+    ("LocalVariables.class", LOCAL_VARIABLES),
+    ("Modifiers.class", MODIFIERS),
+    ("module/module-info.class", MODULE_INFO),
+    ("Nesting.class", NESTING),
+    ("Nesting$1.class", NESTING_ANONYMOUS),
+    ("Nesting$1Local.class", NESTING_LOCAL),
+    ("Nesting$Member.class", NESTING_MEMBER),
+    ("Nesting$StaticMember.class", NESTING_STATIC_MEMBER),
+    ("Shapes.class", SHAPES),
+    ("Shapes$Circle.class", SHAPES_CIRCLE),
+    ("Shapes$Polygon.class", SHAPES_POLYGON),
+    ("Shapes$Tag.class", SHAPES_TAG),
+    ("SimpleMath.class", SIMPLE_MATH),
+    ("SimpleMathWithLoop.class", SIMPLE_MATH_WITH_LOOP),
+    ("StackFrames.class", STACK_FRAMES),
+    ("Switches.class", SWITCHES),
+    ("Switches$1.class", SWITCHES_MAP),
+    ("Switches$Unit.class", SWITCHES_UNIT),
+    ("Tiny.class", TINY),
+    ("TryCatch.class", TRY_CATCH),
+    ("WideConstants.class", WIDE_CONSTANTS),
+    ("WideLocals.class", WIDE_LOCALS),
+];
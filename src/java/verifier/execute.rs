@@ -0,0 +1,772 @@
+//! the effect of a single instruction on the types of the locals and the operand stack
+//! (JVMS 4.10.1.9), and everything about a method that does not change while it is verified.
+
+use java::class_file::stack_map::{initial_locals, VerificationType};
+use java::class_file::{ClassFile, ClassQuery, CodeBlock, ConstantPoolError, ConstantType, Method, MethodDescriptor, ValueType};
+use java::instructions::{Instruction, Wide};
+use java::verifier::{component, object, ClassHierarchy, Classes, Problem, Type, TypeState, VerifyError};
+use std::str::FromStr;
+
+/// a method with code, ready to be verified
+pub struct Environment<'c, 'a: 'c> {
+    pub class: &'c ClassFile<'a>,
+    pub method: &'c Method<'a>,
+    pub code: &'c CodeBlock<'a>,
+    pub classes: Classes<'c>,
+    /// `None` for `void`
+    pub return_type: Option<Type>,
+    pub max_stack: usize,
+    pub max_locals: usize,
+    /// every instruction with its pc, in code order
+    pub instructions: Vec<(usize, Instruction)>,
+    /// true at the pc of every instruction
+    starts: Vec<bool>,
+}
+
+impl<'c, 'a> Environment<'c, 'a> {
+    /// decodes the code of `method`, `None` if it has none
+    pub fn new(class: &'c ClassFile<'a>, method: &'c Method<'a>, hierarchy: &'c dyn ClassHierarchy) -> Result<Option<Environment<'c, 'a>>, VerifyError> {
+        let code = match method.get_code() {
+            Some(code) => code,
+            None => return Ok(None)
+        };
+        let error = |pc: usize, problem: Problem| VerifyError {
+            method: format!("{}{}", method.name, method.descriptor),
+            pc,
+            instruction: None,
+            state: Box::new(TypeState { locals: Vec::new(), stack: Vec::new() }),
+            problem: Box::new(problem),
+        };
+
+        let classes = Classes::new(class, hierarchy).map_err(|err| error(0, Problem::InvalidConstant(err)))?;
+        let descriptor = method.get_signature().map_err(|err| error(0, Problem::InvalidDescriptor(err)))?;
+
+        let mut instructions = Vec::new();
+        let mut starts = vec![false; code.code().len()];
+        let mut pc = 0;
+        while pc < code.code().len() {
+            match (Instruction::read_at(code.code(), pc), Instruction::length_at(code.code(), pc)) {
+                (Some(instruction), Some(length)) => {
+                    instructions.push((pc, instruction));
+                    starts[pc] = true;
+                    pc += length;
+                }
+                _ => return Err(error(pc, Problem::InvalidInstruction))
+            }
+        }
+
+        Ok(Some(Environment {
+            class,
+            method,
+            code,
+            classes,
+            return_type: Type::from_value_type(&descriptor.return_type),
+            max_stack: usize::from(code.max_stack),
+            max_locals: usize::from(code.max_locals),
+            instructions,
+            starts,
+        }))
+    }
+
+    pub fn error(&self, pc: usize, instruction: Option<Instruction>, state: &TypeState, problem: Problem) -> VerifyError {
+        VerifyError {
+            method: format!("{}{}", self.method.name, self.method.descriptor),
+            pc,
+            instruction,
+            state: Box::new(state.clone()),
+            problem: Box::new(problem),
+        }
+    }
+
+    /// the locals at pc 0, derived from the descriptor
+    pub fn initial_state(&self) -> Result<TypeState, VerifyError> {
+        let empty = TypeState { locals: Vec::new(), stack: Vec::new() };
+        let locals = initial_locals(self.classes.name(), self.method)
+            .map_err(|err| self.error(0, None, &empty, Problem::InvalidDescriptor(err)))?;
+
+        TypeState::from_variables(&locals, &[], self.max_locals)
+            .ok_or_else(|| self.error(0, None, &empty, Problem::LocalOutOfRange { index: self.max_locals, max_locals: self.max_locals }))
+    }
+
+    pub fn is_instruction(&self, pc: usize) -> bool {
+        self.starts.get(pc).cloned().unwrap_or(false)
+    }
+
+    /// the pc a branch at `pc` jumps to, if it is an instruction
+    pub fn target(&self, pc: usize, offset: i64) -> Result<usize, Problem> {
+        let target = pc as i64 + offset;
+        if target >= 0 && self.is_instruction(target as usize) {
+            Ok(target as usize)
+        } else {
+            Err(Problem::InvalidTarget { target })
+        }
+    }
+
+    /// the type a handler puts on the stack
+    pub fn exception_type(&self, catch_type: Option<&str>) -> Type {
+        object(catch_type.unwrap_or("java/lang/Throwable"))
+    }
+}
+
+/// where execution can continue after an instruction
+pub struct Step {
+    /// branch offsets relative to the pc of the instruction
+    pub targets: Vec<i64>,
+    /// false after `goto`, the switches, returns and `athrow`
+    pub falls_through: bool,
+}
+
+impl Step {
+    fn next() -> Step {
+        Step { targets: Vec::new(), falls_through: true }
+    }
+
+    fn branch(offset: i64) -> Step {
+        Step { targets: vec![offset], falls_through: true }
+    }
+
+    fn jump(targets: Vec<i64>) -> Step {
+        Step { targets, falls_through: false }
+    }
+}
+
+fn pop(state: &mut TypeState) -> Result<Type, Problem> {
+    state.stack.pop().ok_or(Problem::StackUnderflow)
+}
+
+/// pops a value that can be used as `expected`
+fn pop_type(env: &Environment, state: &mut TypeState, expected: &Type) -> Result<Type, Problem> {
+    let found = pop(state)?;
+    if env.classes.is_assignable(&found, expected) {
+        Ok(found)
+    } else {
+        Err(Problem::WrongStackType { expected: expected.to_string(), found })
+    }
+}
+
+fn is_reference(value: &Type) -> bool {
+    matches!(value, Type::Value(VerificationType::Null | VerificationType::Object(_) | VerificationType::UninitializedThis | VerificationType::Uninitialized(_)))
+}
+
+/// pops any reference, including objects whose constructor did not run yet
+fn pop_reference(state: &mut TypeState) -> Result<Type, Problem> {
+    let found = pop(state)?;
+    if is_reference(&found) {
+        Ok(found)
+    } else {
+        Err(Problem::WrongStackType { expected: String::from("reference"), found })
+    }
+}
+
+/// pops an array whose type is one of `arrays`, or `null`
+fn pop_array(state: &mut TypeState, arrays: &[&str]) -> Result<Type, Problem> {
+    let found = pop(state)?;
+    match found {
+        Type::NULL => Ok(found),
+        Type::Value(VerificationType::Object(ref name)) if arrays.iter().any(|array| name == array) => Ok(found.clone()),
+        _ => Err(Problem::WrongStackType { expected: arrays.join(" or "), found })
+    }
+}
+
+/// pops an array of references, like `[Ljava/lang/String;` or `[[I`, or `null`
+fn pop_reference_array(state: &mut TypeState) -> Result<Type, Problem> {
+    let found = pop(state)?;
+    match found {
+        Type::NULL => Ok(found),
+        Type::Value(VerificationType::Object(ref name)) if name.starts_with("[L") || name.starts_with("[[") => Ok(found.clone()),
+        _ => Err(Problem::WrongStackType { expected: String::from("array of references"), found })
+    }
+}
+
+/// pops values that take up exactly `slots` stack slots, the top of the stack last
+fn pop_slots(state: &mut TypeState, slots: usize) -> Result<Vec<Type>, Problem> {
+    let mut values = Vec::new();
+    let mut popped = 0;
+    while popped < slots {
+        let value = pop(state)?;
+        popped += value.size();
+        values.push(value);
+    }
+    if popped != slots {
+        return Err(Problem::SplitValue);
+    }
+
+    values.reverse();
+    Ok(values)
+}
+
+pub fn push(env: &Environment, state: &mut TypeState, value: Type) -> Result<(), Problem> {
+    if state.stack_size() + value.size() > env.max_stack {
+        return Err(Problem::StackOverflow { max_stack: env.max_stack });
+    }
+
+    state.stack.push(value);
+    Ok(())
+}
+
+fn push_all(env: &Environment, state: &mut TypeState, values: &[Type]) -> Result<(), Problem> {
+    for value in values {
+        push(env, state, value.clone())?;
+    }
+
+    Ok(())
+}
+
+/// checks the operands, then pushes the result
+fn operation(env: &Environment, state: &mut TypeState, operands: &[Type], result: Option<Type>) -> Result<(), Problem> {
+    for operand in operands.iter().rev() {
+        pop_type(env, state, operand)?;
+    }
+
+    match result {
+        Some(result) => push(env, state, result),
+        None => Ok(())
+    }
+}
+
+pub fn local(env: &Environment, state: &TypeState, index: usize, size: usize) -> Result<Type, Problem> {
+    if index + size > env.max_locals {
+        return Err(Problem::LocalOutOfRange { index, max_locals: env.max_locals });
+    }
+
+    Ok(state.locals[index].clone())
+}
+
+fn load(env: &Environment, state: &mut TypeState, index: usize, expected: Type) -> Result<(), Problem> {
+    let found = local(env, state, index, expected.size())?;
+    if found != expected {
+        return Err(Problem::WrongLocalType { index, expected: expected.to_string(), found });
+    }
+
+    push(env, state, found)
+}
+
+fn load_reference(env: &Environment, state: &mut TypeState, index: usize) -> Result<(), Problem> {
+    let found = local(env, state, index, 1)?;
+    if !is_reference(&found) {
+        return Err(Problem::WrongLocalType { index, expected: String::from("reference"), found });
+    }
+
+    push(env, state, found)
+}
+
+/// overwrites the local at `index`, a long or double that is partly overwritten becomes unusable
+fn set_local(env: &Environment, state: &mut TypeState, index: usize, value: Type) -> Result<(), Problem> {
+    let size = value.size();
+    local(env, state, index, size)?;
+    if index > 0 && state.locals[index - 1].size() == 2 {
+        state.locals[index - 1] = Type::TOP;
+    }
+
+    state.locals[index] = value;
+    if size == 2 {
+        state.locals[index + 1] = Type::TOP;
+    }
+
+    Ok(())
+}
+
+fn store(env: &Environment, state: &mut TypeState, index: usize, expected: Type) -> Result<(), Problem> {
+    let value = pop_type(env, state, &expected)?;
+    set_local(env, state, index, value)
+}
+
+/// `astore` also stores the return address of a subroutine
+fn store_reference(env: &Environment, state: &mut TypeState, index: usize) -> Result<(), Problem> {
+    let value = match state.stack.last() {
+        Some(&Type::ReturnAddress(_)) => pop(state)?,
+        _ => pop_reference(state)?
+    };
+    set_local(env, state, index, value)
+}
+
+fn increment(env: &Environment, state: &TypeState, index: usize) -> Result<(), Problem> {
+    let found = local(env, state, index, 1)?;
+    if found != Type::INTEGER {
+        return Err(Problem::WrongLocalType { index, expected: String::from("int"), found });
+    }
+
+    Ok(())
+}
+
+fn value_type(descriptor: &str) -> Result<Option<Type>, Problem> {
+    let value_type = ValueType::from_str(descriptor).map_err(Problem::InvalidDescriptor)?;
+    Ok(Type::from_value_type(&value_type))
+}
+
+fn field_type(descriptor: &str) -> Result<Type, Problem> {
+    value_type(descriptor)?.ok_or(Problem::Unsupported { reason: "a field cannot be void" })
+}
+
+/// the type `ldc` pushes for a constant
+fn constant_type(env: &Environment, index: u16, wide: bool) -> Result<Type, Problem> {
+    let constant = env.class.constants.get(index).map_err(Problem::InvalidConstant)?;
+    let found = match (constant, wide) {
+        (ConstantType::Integer { .. }, false) => Type::INTEGER,
+        (ConstantType::Float { .. }, false) => Type::FLOAT,
+        (ConstantType::String { .. }, false) => object("java/lang/String"),
+        (ConstantType::Class { .. }, false) => object("java/lang/Class"),
+        (ConstantType::MethodType { .. }, false) => object("java/lang/invoke/MethodType"),
+        (ConstantType::MethodHandle { .. }, false) => object("java/lang/invoke/MethodHandle"),
+        (ConstantType::Long { .. }, true) => Type::LONG,
+        (ConstantType::Double { .. }, true) => Type::DOUBLE,
+        (ConstantType::Dynamic { name_and_type_index, .. }, _) => {
+            let (_, descriptor) = env.class.constants.name_and_type(*name_and_type_index).map_err(Problem::InvalidConstant)?;
+            let found = field_type(descriptor)?;
+            // `ldc2_w` loads the long and double ones, `ldc` all others
+            if (found.size() == 2) != wide {
+                return Err(Problem::InvalidConstant(ConstantPoolError::WrongType {
+                    index,
+                    expected: if wide { "Long, Double or Dynamic" } else { "Integer, Float, String, Class, MethodType, MethodHandle or Dynamic" },
+                    found: "Dynamic",
+                }));
+            }
+            found
+        }
+        (other, _) => return Err(Problem::InvalidConstant(ConstantPoolError::WrongType {
+            index,
+            expected: if wide { "Long, Double or Dynamic" } else { "Integer, Float, String, Class, MethodType, MethodHandle or Dynamic" },
+            found: other.name(),
+        }))
+    };
+
+    Ok(found)
+}
+
+fn class_type(env: &Environment, index: u16) -> Result<Type, Problem> {
+    env.class.constants.class_name(index).map(object).map_err(Problem::InvalidConstant)
+}
+
+/// the array descriptor for `newarray`
+fn primitive_array(atype: u8) -> Result<Type, Problem> {
+    let descriptor = match atype {
+        4 => "[Z",
+        5 => "[C",
+        6 => "[F",
+        7 => "[D",
+        8 => "[B",
+        9 => "[S",
+        10 => "[I",
+        11 => "[J",
+        _ => return Err(Problem::InvalidArrayType { atype })
+    };
+
+    Ok(object(descriptor))
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Invoke {
+    Virtual,
+    Special,
+    Static,
+    Interface,
+    Dynamic,
+}
+
+fn invoke(env: &Environment, state: &mut TypeState, index: u16, kind: Invoke) -> Result<(), Problem> {
+    let constants = &env.class.constants;
+    let (class_name, name, descriptor) = match kind {
+        Invoke::Dynamic => {
+            let call_site = env.class.invoke_dynamic(index).map_err(Problem::Malformed)?;
+            ("java/lang/Object", call_site.name, call_site.descriptor)
+        }
+        Invoke::Interface => {
+            let member = constants.interface_method_ref(index).map_err(Problem::InvalidConstant)?;
+            (member.class_name, member.name, member.descriptor)
+        }
+        // since java 8 static and special calls can also refer to interface methods
+        _ => {
+            let member = constants.method_ref(index).or_else(|_| constants.interface_method_ref(index)).map_err(Problem::InvalidConstant)?;
+            (member.class_name, member.name, member.descriptor)
+        }
+    };
+    let descriptor = MethodDescriptor::from_str(descriptor).map_err(Problem::InvalidDescriptor)?;
+
+    let arguments: Vec<Type> = descriptor.arguments.iter().filter_map(Type::from_value_type).collect();
+    operation(env, state, &arguments, None)?;
+
+    match kind {
+        Invoke::Special if name == "<init>" => {
+            let uninitialized = pop(state)?;
+            let initialized = match uninitialized {
+                Type::Value(VerificationType::UninitializedThis) => object(env.classes.name()),
+                Type::Value(VerificationType::Uninitialized(_)) => object(class_name),
+                _ => return Err(Problem::WrongStackType { expected: String::from("uninitialized object"), found: uninitialized })
+            };
+            // every copy of the new object is initialized now
+            for value in state.locals.iter_mut().chain(state.stack.iter_mut()) {
+                if *value == uninitialized {
+                    *value = initialized.clone();
+                }
+            }
+        }
+        Invoke::Virtual | Invoke::Special => {
+            pop_type(env, state, &object(class_name))?;
+        }
+        Invoke::Interface => {
+            let found = pop(state)?;
+            match found {
+                Type::Value(VerificationType::Null) | Type::Value(VerificationType::Object(_)) => (),
+                _ => return Err(Problem::WrongStackType { expected: String::from(class_name), found })
+            }
+        }
+        Invoke::Static | Invoke::Dynamic => ()
+    }
+
+    match Type::from_value_type(&descriptor.return_type) {
+        Some(value) => push(env, state, value),
+        None => Ok(())
+    }
+}
+
+fn return_value(env: &Environment, state: &mut TypeState, expected: Type) -> Result<Step, Problem> {
+    let return_type = match env.return_type {
+        Some(ref return_type) => return_type.clone(),
+        None => return Err(Problem::WrongReturn { return_type: String::from("void") })
+    };
+    let matches = match expected {
+        Type::Value(VerificationType::Object(_)) => env.classes.is_assignable(&return_type, &expected),
+        _ => return_type == expected
+    };
+    if !matches {
+        return Err(Problem::WrongReturn { return_type: return_type.to_string() });
+    }
+
+    pop_type(env, state, &return_type)?;
+    Ok(Step::jump(Vec::new()))
+}
+
+/// applies the instruction at `pc` to `state`
+pub fn execute(env: &Environment, state: &mut TypeState, pc: usize, instruction: &Instruction) -> Result<Step, Problem> {
+
+    match *instruction {
+        Instruction::NOOP(()) => (),
+        Instruction::AConstNull(()) => push(env, state, Type::NULL)?,
+        Instruction::IConstm1(()) | Instruction::IConst0(()) | Instruction::IConst1(()) | Instruction::IConst2(()) |
+        Instruction::IConst3(()) | Instruction::IConst4(()) | Instruction::IConst5(()) |
+        Instruction::BIPush(_) | Instruction::SIPush(_) => push(env, state, Type::INTEGER)?,
+        Instruction::LConst0(()) | Instruction::LConst1(()) => push(env, state, Type::LONG)?,
+        Instruction::FConst0(()) | Instruction::FConst1(()) | Instruction::FConst2(()) => push(env, state, Type::FLOAT)?,
+        Instruction::DConst0(()) | Instruction::DConst1(()) => push(env, state, Type::DOUBLE)?,
+        Instruction::LDC(index) => {
+            let value = constant_type(env, u16::from(index), false)?;
+            push(env, state, value)?
+        }
+        Instruction::LDCW(index) => {
+            let value = constant_type(env, index, false)?;
+            push(env, state, value)?
+        }
+        Instruction::LDC2W(index) => {
+            let value = constant_type(env, index, true)?;
+            push(env, state, value)?
+        }
+
+        Instruction::ILoad(index) => load(env, state, usize::from(index), Type::INTEGER)?,
+        Instruction::LLoad(index) => load(env, state, usize::from(index), Type::LONG)?,
+        Instruction::FLoad(index) => load(env, state, usize::from(index), Type::FLOAT)?,
+        Instruction::DLoad(index) => load(env, state, usize::from(index), Type::DOUBLE)?,
+        Instruction::ALoad(index) => load_reference(env, state, usize::from(index))?,
+        Instruction::ILoad0(()) => load(env, state, 0, Type::INTEGER)?,
+        Instruction::ILoad1(()) => load(env, state, 1, Type::INTEGER)?,
+        Instruction::ILoad2(()) => load(env, state, 2, Type::INTEGER)?,
+        Instruction::ILoad3(()) => load(env, state, 3, Type::INTEGER)?,
+        Instruction::LLoad0(()) => load(env, state, 0, Type::LONG)?,
+        Instruction::LLoad1(()) => load(env, state, 1, Type::LONG)?,
+        Instruction::LLoad2(()) => load(env, state, 2, Type::LONG)?,
+        Instruction::LLoad3(()) => load(env, state, 3, Type::LONG)?,
+        Instruction::FLoad0(()) => load(env, state, 0, Type::FLOAT)?,
+        Instruction::FLoad1(()) => load(env, state, 1, Type::FLOAT)?,
+        Instruction::FLoad2(()) => load(env, state, 2, Type::FLOAT)?,
+        Instruction::FLoad3(()) => load(env, state, 3, Type::FLOAT)?,
+        Instruction::DLoad0(()) => load(env, state, 0, Type::DOUBLE)?,
+        Instruction::DLoad1(()) => load(env, state, 1, Type::DOUBLE)?,
+        Instruction::DLoad2(()) => load(env, state, 2, Type::DOUBLE)?,
+        Instruction::DLoad3(()) => load(env, state, 3, Type::DOUBLE)?,
+        Instruction::ALoad0(()) => load_reference(env, state, 0)?,
+        Instruction::ALoad1(()) => load_reference(env, state, 1)?,
+        Instruction::ALoad2(()) => load_reference(env, state, 2)?,
+        Instruction::ALoad3(()) => load_reference(env, state, 3)?,
+
+        Instruction::IALoad(()) | Instruction::BALoad(()) | Instruction::CALoad(()) | Instruction::ScALoad(()) |
+        Instruction::LALoad(()) | Instruction::FALoad(()) | Instruction::DALoad(()) => {
+            let (arrays, element): (&[&str], Type) = match *instruction {
+                Instruction::IALoad(()) => (&["[I"], Type::INTEGER),
+                Instruction::BALoad(()) => (&["[B", "[Z"], Type::INTEGER),
+                Instruction::CALoad(()) => (&["[C"], Type::INTEGER),
+                Instruction::ScALoad(()) => (&["[S"], Type::INTEGER),
+                Instruction::LALoad(()) => (&["[J"], Type::LONG),
+                Instruction::FALoad(()) => (&["[F"], Type::FLOAT),
+                _ => (&["[D"], Type::DOUBLE),
+            };
+            pop_type(env, state, &Type::INTEGER)?;
+            pop_array(state, arrays)?;
+            push(env, state, element)?
+        }
+        Instruction::AALoad(()) => {
+            pop_type(env, state, &Type::INTEGER)?;
+            let element = match pop_reference_array(state)? {
+                Type::Value(VerificationType::Object(array)) => component(&array).unwrap_or(Type::NULL),
+                other => other
+            };
+            push(env, state, element)?
+        }
+
+        Instruction::IStore(index) => store(env, state, usize::from(index), Type::INTEGER)?,
+        Instruction::LStore(index) => store(env, state, usize::from(index), Type::LONG)?,
+        Instruction::FStore(index) => store(env, state, usize::from(index), Type::FLOAT)?,
+        Instruction::DStore(index) => store(env, state, usize::from(index), Type::DOUBLE)?,
+        Instruction::AStore(index) => store_reference(env, state, usize::from(index))?,
+        Instruction::IStore0(()) => store(env, state, 0, Type::INTEGER)?,
+        Instruction::IStore1(()) => store(env, state, 1, Type::INTEGER)?,
+        Instruction::IStore2(()) => store(env, state, 2, Type::INTEGER)?,
+        Instruction::IStore3(()) => store(env, state, 3, Type::INTEGER)?,
+        Instruction::LStore0(()) => store(env, state, 0, Type::LONG)?,
+        Instruction::LStore1(()) => store(env, state, 1, Type::LONG)?,
+        Instruction::LStore2(()) => store(env, state, 2, Type::LONG)?,
+        Instruction::LStore3(()) => store(env, state, 3, Type::LONG)?,
+        Instruction::FStore0(()) => store(env, state, 0, Type::FLOAT)?,
+        Instruction::FStore1(()) => store(env, state, 1, Type::FLOAT)?,
+        Instruction::FStore2(()) => store(env, state, 2, Type::FLOAT)?,
+        Instruction::FStore3(()) => store(env, state, 3, Type::FLOAT)?,
+        Instruction::DStore0(()) => store(env, state, 0, Type::DOUBLE)?,
+        Instruction::DStore1(()) => store(env, state, 1, Type::DOUBLE)?,
+        Instruction::DStore2(()) => store(env, state, 2, Type::DOUBLE)?,
+        Instruction::DStore3(()) => store(env, state, 3, Type::DOUBLE)?,
+        Instruction::AStore0(()) => store_reference(env, state, 0)?,
+        Instruction::AStore1(()) => store_reference(env, state, 1)?,
+        Instruction::AStore2(()) => store_reference(env, state, 2)?,
+        Instruction::AStore3(()) => store_reference(env, state, 3)?,
+
+        Instruction::IAStore(()) | Instruction::BAStore(()) | Instruction::CAStore(()) | Instruction::SAStore(()) |
+        Instruction::LAStore(()) | Instruction::FAStore(()) | Instruction::DAStore(()) => {
+            let (arrays, element): (&[&str], Type) = match *instruction {
+                Instruction::IAStore(()) => (&["[I"], Type::INTEGER),
+                Instruction::BAStore(()) => (&["[B", "[Z"], Type::INTEGER),
+                Instruction::CAStore(()) => (&["[C"], Type::INTEGER),
+                Instruction::SAStore(()) => (&["[S"], Type::INTEGER),
+                Instruction::LAStore(()) => (&["[J"], Type::LONG),
+                Instruction::FAStore(()) => (&["[F"], Type::FLOAT),
+                _ => (&["[D"], Type::DOUBLE),
+            };
+            pop_type(env, state, &element)?;
+            pop_type(env, state, &Type::INTEGER)?;
+            pop_array(state, arrays)?;
+        }
+        // the element type is checked at runtime, see `ArrayStoreException`
+        Instruction::AAStore(()) => {
+            pop_type(env, state, &object("java/lang/Object"))?;
+            pop_type(env, state, &Type::INTEGER)?;
+            pop_reference_array(state)?;
+        }
+
+        Instruction::Pop(()) => {
+            pop_slots(state, 1)?;
+        }
+        Instruction::Pop2(()) => {
+            pop_slots(state, 2)?;
+        }
+        Instruction::Dup(()) => {
+            let value = pop_slots(state, 1)?;
+            push_all(env, state, &value)?;
+            push_all(env, state, &value)?;
+        }
+        Instruction::DupX1(()) | Instruction::DupX2(()) | Instruction::Dup2X1(()) | Instruction::Dup2X2(()) => {
+            let (top, below) = match *instruction {
+                Instruction::DupX1(()) => (1, 1),
+                Instruction::DupX2(()) => (1, 2),
+                Instruction::Dup2X1(()) => (2, 1),
+                _ => (2, 2),
+            };
+            let top = pop_slots(state, top)?;
+            let below = pop_slots(state, below)?;
+            push_all(env, state, &top)?;
+            push_all(env, state, &below)?;
+            push_all(env, state, &top)?;
+        }
+        Instruction::Dup2(()) => {
+            let values = pop_slots(state, 2)?;
+            push_all(env, state, &values)?;
+            push_all(env, state, &values)?;
+        }
+        Instruction::Swap(()) => {
+            let top = pop_slots(state, 1)?;
+            let below = pop_slots(state, 1)?;
+            push_all(env, state, &top)?;
+            push_all(env, state, &below)?;
+        }
+
+        Instruction::IAdd(()) | Instruction::ISub(()) | Instruction::IMul(()) | Instruction::IDiv(()) | Instruction::IRem(()) |
+        Instruction::IShl(()) | Instruction::IShr(()) | Instruction::IUSHR(()) |
+        Instruction::IAnd(()) | Instruction::IOr(()) | Instruction::IXor(()) => operation(env, state, &[Type::INTEGER, Type::INTEGER], Some(Type::INTEGER))?,
+        Instruction::LAdd(()) | Instruction::LSub(()) | Instruction::LMul(()) | Instruction::LDiv(()) | Instruction::LRem(()) |
+        Instruction::LAnd(()) | Instruction::LOr(()) | Instruction::LXor(()) => operation(env, state, &[Type::LONG, Type::LONG], Some(Type::LONG))?,
+        Instruction::LShl(()) | Instruction::LShr(()) | Instruction::LUSHR(()) => operation(env, state, &[Type::LONG, Type::INTEGER], Some(Type::LONG))?,
+        Instruction::FAdd(()) | Instruction::FSub(()) | Instruction::FMul(()) | Instruction::FDiv(()) | Instruction::FRem(()) =>
+            operation(env, state, &[Type::FLOAT, Type::FLOAT], Some(Type::FLOAT))?,
+        Instruction::DAdd(()) | Instruction::DSub(()) | Instruction::DMul(()) | Instruction::DDiv(()) | Instruction::DRem(()) =>
+            operation(env, state, &[Type::DOUBLE, Type::DOUBLE], Some(Type::DOUBLE))?,
+        Instruction::INeg(()) | Instruction::I2B(()) | Instruction::I2C(()) | Instruction::I2S(()) => operation(env, state, &[Type::INTEGER], Some(Type::INTEGER))?,
+        Instruction::LNeg(()) => operation(env, state, &[Type::LONG], Some(Type::LONG))?,
+        Instruction::FNeg(()) => operation(env, state, &[Type::FLOAT], Some(Type::FLOAT))?,
+        Instruction::DNeg(()) => operation(env, state, &[Type::DOUBLE], Some(Type::DOUBLE))?,
+        Instruction::IInc((index, _)) => increment(env, state, usize::from(index))?,
+        Instruction::I2L(()) => operation(env, state, &[Type::INTEGER], Some(Type::LONG))?,
+        Instruction::I2F(()) => operation(env, state, &[Type::INTEGER], Some(Type::FLOAT))?,
+        Instruction::I2D(()) => operation(env, state, &[Type::INTEGER], Some(Type::DOUBLE))?,
+        Instruction::L2I(()) => operation(env, state, &[Type::LONG], Some(Type::INTEGER))?,
+        Instruction::L2F(()) => operation(env, state, &[Type::LONG], Some(Type::FLOAT))?,
+        Instruction::L2D(()) => operation(env, state, &[Type::LONG], Some(Type::DOUBLE))?,
+        Instruction::F2I(()) => operation(env, state, &[Type::FLOAT], Some(Type::INTEGER))?,
+        Instruction::F2L(()) => operation(env, state, &[Type::FLOAT], Some(Type::LONG))?,
+        Instruction::F2D(()) => operation(env, state, &[Type::FLOAT], Some(Type::DOUBLE))?,
+        Instruction::D2I(()) => operation(env, state, &[Type::DOUBLE], Some(Type::INTEGER))?,
+        Instruction::D2L(()) => operation(env, state, &[Type::DOUBLE], Some(Type::LONG))?,
+        Instruction::D2F(()) => operation(env, state, &[Type::DOUBLE], Some(Type::FLOAT))?,
+        Instruction::LCmp(()) => operation(env, state, &[Type::LONG, Type::LONG], Some(Type::INTEGER))?,
+        Instruction::FCmpL(()) | Instruction::FCmpG(()) => operation(env, state, &[Type::FLOAT, Type::FLOAT], Some(Type::INTEGER))?,
+        Instruction::DCmpL(()) | Instruction::DCmpG(()) => operation(env, state, &[Type::DOUBLE, Type::DOUBLE], Some(Type::INTEGER))?,
+
+        Instruction::Ifeq(offset) | Instruction::Ifne(offset) | Instruction::Iflt(offset) |
+        Instruction::Ifge(offset) | Instruction::Ifgt(offset) | Instruction::Ifle(offset) => {
+            pop_type(env, state, &Type::INTEGER)?;
+            return Ok(Step::branch(i64::from(offset)));
+        }
+        Instruction::IfICmpEQ(offset) | Instruction::IfICmpNE(offset) | Instruction::IfICmpLT(offset) |
+        Instruction::IfICmpGE(offset) | Instruction::IfICmpGT(offset) | Instruction::IfICmpLE(offset) => {
+            operation(env, state, &[Type::INTEGER, Type::INTEGER], None)?;
+            return Ok(Step::branch(i64::from(offset)));
+        }
+        Instruction::IfACmpEQ(offset) | Instruction::IfACmpNE(offset) => {
+            pop_reference(state)?;
+            pop_reference(state)?;
+            return Ok(Step::branch(i64::from(offset)));
+        }
+        Instruction::IfNull(offset) | Instruction::IfNonNull(offset) => {
+            pop_reference(state)?;
+            return Ok(Step::branch(i64::from(offset as i16)));
+        }
+        Instruction::Goto(offset) => return Ok(Step::jump(vec![i64::from(offset)])),
+        Instruction::GotoW(offset) => return Ok(Step::jump(vec![i64::from(offset)])),
+        Instruction::JSR(_) | Instruction::JSRW(_) | Instruction::Ret(_) | Instruction::Wide(Wide::Ret(_)) =>
+            return Err(Problem::Unsupported { reason: "jsr and ret are not allowed in class files with stack map frames" }),
+        Instruction::TableSwitch(ref table) => {
+            pop_type(env, state, &Type::INTEGER)?;
+            return Ok(Step::jump(table.branches().into_iter().map(i64::from).collect()));
+        }
+        Instruction::LookupSwitch(ref lookup) => {
+            pop_type(env, state, &Type::INTEGER)?;
+            return Ok(Step::jump(lookup.branches().into_iter().map(i64::from).collect()));
+        }
+
+        Instruction::IReturn(()) => return return_value(env, state, Type::INTEGER),
+        Instruction::LReturn(()) => return return_value(env, state, Type::LONG),
+        Instruction::FReturn(()) => return return_value(env, state, Type::FLOAT),
+        Instruction::DReturn(()) => return return_value(env, state, Type::DOUBLE),
+        Instruction::AReturn(()) => return return_value(env, state, object("java/lang/Object")),
+        Instruction::Return(()) => {
+            if let Some(ref return_type) = env.return_type {
+                return Err(Problem::WrongReturn { return_type: return_type.to_string() });
+            }
+            if state.locals.contains(&Type::Value(VerificationType::UninitializedThis)) {
+                return Err(Problem::UninitializedReturn);
+            }
+            return Ok(Step::jump(Vec::new()));
+        }
+
+        Instruction::GetStatic(index) | Instruction::PutStatic(index) | Instruction::GetField(index) | Instruction::PutField(index) => {
+            let member = env.class.constants.field_ref(index).map_err(Problem::InvalidConstant)?;
+            let value = field_type(member.descriptor)?;
+            match *instruction {
+                Instruction::GetStatic(_) => push(env, state, value)?,
+                Instruction::PutStatic(_) => {
+                    pop_type(env, state, &value)?;
+                }
+                Instruction::GetField(_) => {
+                    pop_type(env, state, &object(member.class_name))?;
+                    push(env, state, value)?
+                }
+                _ => {
+                    pop_type(env, state, &value)?;
+                    // constructors can assign their own fields before calling `super()`
+                    match pop(state)? {
+                        Type::Value(VerificationType::UninitializedThis) if member.class_name == env.classes.name() => (),
+                        found => if !env.classes.is_assignable(&found, &object(member.class_name)) {
+                            return Err(Problem::WrongStackType { expected: String::from(member.class_name), found });
+                        }
+                    }
+                }
+            }
+        }
+        Instruction::InvokeVirtual(index) => invoke(env, state, index, Invoke::Virtual)?,
+        Instruction::InvokeSpecial(index) => invoke(env, state, index, Invoke::Special)?,
+        Instruction::InvokeStatic(index) => invoke(env, state, index, Invoke::Static)?,
+        Instruction::InvokeInterface((index, _)) => invoke(env, state, index, Invoke::Interface)?,
+        Instruction::InvokeDynamic(index) => invoke(env, state, index, Invoke::Dynamic)?,
+
+        Instruction::New(index) => {
+            class_type(env, index)?;
+            push(env, state, Type::Value(VerificationType::Uninitialized(pc as u16)))?
+        }
+        Instruction::NewArray(atype) => {
+            let array = primitive_array(atype)?;
+            pop_type(env, state, &Type::INTEGER)?;
+            push(env, state, array)?
+        }
+        Instruction::AAewArray(index) => {
+            let element = env.class.constants.class_name(index).map_err(Problem::InvalidConstant)?;
+            let array = if element.starts_with('[') { format!("[{}", element) } else { format!("[L{};", element) };
+            pop_type(env, state, &Type::INTEGER)?;
+            push(env, state, object(&array))?
+        }
+        Instruction::MultianeWArray((index, dimensions)) => {
+            let array = env.class.constants.class_name(index).map_err(Problem::InvalidConstant)?;
+            if dimensions == 0 || array.chars().take_while(|c| *c == '[').count() < usize::from(dimensions) {
+                return Err(Problem::Unsupported { reason: "multianewarray needs at least as many array dimensions as it creates" });
+            }
+            for _ in 0..dimensions {
+                pop_type(env, state, &Type::INTEGER)?;
+            }
+            push(env, state, object(array))?
+        }
+        Instruction::ArrayLength(()) => {
+            let found = pop(state)?;
+            match found {
+                Type::NULL => (),
+                Type::Value(VerificationType::Object(ref array)) if array.starts_with('[') => (),
+                _ => return Err(Problem::WrongStackType { expected: String::from("array"), found })
+            }
+            push(env, state, Type::INTEGER)?
+        }
+        Instruction::AThrow(()) => {
+            pop_type(env, state, &object("java/lang/Throwable"))?;
+            return Ok(Step::jump(Vec::new()));
+        }
+        Instruction::CheckCast(index) => {
+            let target = class_type(env, index)?;
+            pop_type(env, state, &object("java/lang/Object"))?;
+            push(env, state, target)?
+        }
+        Instruction::InstanceOf(index) => {
+            class_type(env, index)?;
+            pop_type(env, state, &object("java/lang/Object"))?;
+            push(env, state, Type::INTEGER)?
+        }
+        Instruction::MonitorEnter(()) | Instruction::MonitorExit(()) => {
+            pop_type(env, state, &object("java/lang/Object"))?;
+        }
+        Instruction::Wide(Wide::ILoad(index)) => load(env, state, usize::from(index), Type::INTEGER)?,
+        Instruction::Wide(Wide::LLoad(index)) => load(env, state, usize::from(index), Type::LONG)?,
+        Instruction::Wide(Wide::FLoad(index)) => load(env, state, usize::from(index), Type::FLOAT)?,
+        Instruction::Wide(Wide::DLoad(index)) => load(env, state, usize::from(index), Type::DOUBLE)?,
+        Instruction::Wide(Wide::ALoad(index)) => load_reference(env, state, usize::from(index))?,
+        Instruction::Wide(Wide::IStore(index)) => store(env, state, usize::from(index), Type::INTEGER)?,
+        Instruction::Wide(Wide::LStore(index)) => store(env, state, usize::from(index), Type::LONG)?,
+        Instruction::Wide(Wide::FStore(index)) => store(env, state, usize::from(index), Type::FLOAT)?,
+        Instruction::Wide(Wide::DStore(index)) => store(env, state, usize::from(index), Type::DOUBLE)?,
+        Instruction::Wide(Wide::AStore(index)) => store_reference(env, state, usize::from(index))?,
+        Instruction::Wide(Wide::IInc(index, _)) => increment(env, state, usize::from(index))?,
+        Instruction::Breakpoint(()) | Instruction::ImpDep1(()) | Instruction::ImpDep2(()) => return Err(Problem::InvalidInstruction),
+    }
+
+    Ok(Step::next())
+}
@@ -0,0 +1,592 @@
+//! bytecode verification (JVMS 4.10).
+//!
+//! `verify_class` proves that every method of a class is type-safe before it runs, so the
+//! interpreter can rely on the types of its locals and operand stack. class files from version 50
+//! on are checked against their stack map frames by `type_check`, the types in older class files
+//! are inferred by `infer_types`.
+//!
+//! the verifier only knows the classes it is given through a `ClassHierarchy`. a class that is
+//! not known is assumed to be assignable to any other class, the runtime checks these later.
+
+mod execute;
+mod type_check;
+mod type_inference;
+
+pub use self::type_check::type_check;
+pub use self::type_inference::infer_types;
+
+use failure::Fail;
+use java::class_file::stack_map::VerificationType;
+use java::class_file::version::STACK_MAP_TABLE_SINCE;
+use java::class_file::{ClassFile, ClassFileError, ClassFlags, ClassQuery, ConstantPoolError, ValueType};
+use java::instructions::Instruction;
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fmt;
+
+/// the type of a local or a stack entry: a verification type that does not borrow from a class
+/// file, or the return address `jsr` pushes. return addresses only exist while types are
+/// inferred, they never appear in a stack map frame.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Type {
+    Value(VerificationType<'static>),
+    /// the address `jsr` pushes when it calls the subroutine at the given offset
+    ReturnAddress(u16),
+}
+
+impl Type {
+    pub const TOP: Type = Type::Value(VerificationType::Top);
+    pub const INTEGER: Type = Type::Value(VerificationType::Integer);
+    pub const FLOAT: Type = Type::Value(VerificationType::Float);
+    pub const LONG: Type = Type::Value(VerificationType::Long);
+    pub const DOUBLE: Type = Type::Value(VerificationType::Double);
+    pub const NULL: Type = Type::Value(VerificationType::Null);
+
+    pub fn from_value_type(value_type: &ValueType) -> Option<Type> {
+        VerificationType::from_value_type(value_type).map(Type::Value)
+    }
+
+    /// `Long` and `Double` occupy two local variable slots
+    pub fn size(&self) -> usize {
+        match self {
+            Type::Value(value) => value.size(),
+            Type::ReturnAddress(_) => 1
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Type::Value(value) => write!(f, "{}", value),
+            Type::ReturnAddress(offset) => write!(f, "returnAddress({})", offset),
+        }
+    }
+}
+
+/// what the verifier needs to know about a class it does not verify itself
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ClassInfo {
+    /// `None` for `java/lang/Object`
+    pub super_class: Option<String>,
+    pub is_interface: bool,
+}
+
+impl ClassInfo {
+    pub fn of<C: ClassQuery>(class: &C) -> Result<ClassInfo, ConstantPoolError> {
+        Ok(ClassInfo {
+            super_class: class.super_class_name()?.map(String::from),
+            is_interface: class.access().contains(ClassFlags::INTERFACE),
+        })
+    }
+}
+
+/// the loaded classes, used to decide if one class is assignable to another
+pub trait ClassHierarchy {
+    /// `None` if the class is not known
+    fn class_info(&self, name: &str) -> Option<ClassInfo>;
+}
+
+impl<'a> ClassHierarchy for Vec<ClassFile<'a>> {
+    fn class_info(&self, name: &str) -> Option<ClassInfo> {
+        self.iter()
+            .find(|class| class.get_class_name().ok() == Some(name))
+            .and_then(|class| ClassInfo::of(class).ok())
+    }
+}
+
+/// the class being verified together with the loaded classes
+pub struct Classes<'c> {
+    name: &'c str,
+    info: ClassInfo,
+    loaded: &'c dyn ClassHierarchy,
+}
+
+impl<'c> Classes<'c> {
+    pub fn new<'a>(class: &'c ClassFile<'a>, loaded: &'c dyn ClassHierarchy) -> Result<Classes<'c>, ConstantPoolError> {
+        Ok(Classes { name: class.get_class_name()?, info: ClassInfo::of(class)?, loaded })
+    }
+
+    /// the name of the class being verified
+    pub fn name(&self) -> &'c str {
+        self.name
+    }
+
+    fn class_info(&self, name: &str) -> Option<ClassInfo> {
+        if name == self.name {
+            Some(self.info.clone())
+        } else {
+            self.loaded.class_info(name)
+        }
+    }
+
+    /// a value of type `from` can be used where `to` is expected (JVMS 4.10.1.2)
+    pub fn is_assignable(&self, from: &Type, to: &Type) -> bool {
+        if from == to || *to == Type::TOP {
+            return true;
+        }
+
+        match (from, to) {
+            (Type::Value(VerificationType::Null), Type::Value(VerificationType::Object(_))) => true,
+            (Type::Value(VerificationType::Object(from)), Type::Value(VerificationType::Object(to))) => self.is_subtype(from, to),
+            _ => false
+        }
+    }
+
+    /// reference types, by class name or array descriptor
+    fn is_subtype(&self, from: &str, to: &str) -> bool {
+        if from == to || to == "java/lang/Object" {
+            return true;
+        }
+
+        if from.starts_with('[') {
+            return match (component(from), component(to)) {
+                (Some(Type::Value(VerificationType::Object(from))), Some(Type::Value(VerificationType::Object(to)))) => self.is_subtype(&from, &to),
+                (Some(_), Some(_)) => false,
+                _ => to == "java/lang/Cloneable" || to == "java/io/Serializable"
+            };
+        }
+        if to.starts_with('[') {
+            return false;
+        }
+
+        // like in the JVMS, interfaces are treated like `java/lang/Object`
+        match self.class_info(to) {
+            Some(ref info) if info.is_interface => return true,
+            None => return true,
+            _ => ()
+        }
+
+        let mut visited = HashSet::new();
+        let mut current = String::from(from);
+        while visited.insert(current.clone()) {
+            current = match self.class_info(&current) {
+                Some(ClassInfo { super_class: Some(super_class), .. }) => super_class,
+                Some(ClassInfo { super_class: None, .. }) => return false,
+                None => return true
+            };
+            if current == to {
+                return true;
+            }
+            if current == "java/lang/Object" {
+                return false;
+            }
+        }
+
+        false
+    }
+
+    /// the type of a value that is `a` on one path and `b` on another (JVMS 4.10.2.2).
+    /// `None` if there is no such type, e.g. for an int and a float.
+    pub fn merge(&self, a: &Type, b: &Type) -> Option<Type> {
+        if a == b {
+            return Some(a.clone());
+        }
+
+        match (a, b) {
+            (Type::Value(VerificationType::Null), Type::Value(VerificationType::Object(_))) => Some(b.clone()),
+            (Type::Value(VerificationType::Object(_)), Type::Value(VerificationType::Null)) => Some(a.clone()),
+            (Type::Value(VerificationType::Object(a)), Type::Value(VerificationType::Object(b))) => Some(object(&self.common_superclass(a, b))),
+            _ => None
+        }
+    }
+
+    /// the first superclass of `a` that `b` is assignable to, by class name or array descriptor
+    fn common_superclass(&self, a: &str, b: &str) -> String {
+        if self.is_subtype(b, a) {
+            return String::from(a);
+        }
+        if self.is_subtype(a, b) {
+            return String::from(b);
+        }
+
+        if a.starts_with('[') || b.starts_with('[') {
+            return match (component(a), component(b)) {
+                (Some(Type::Value(VerificationType::Object(a))), Some(Type::Value(VerificationType::Object(b)))) => {
+                    let element = self.common_superclass(&a, &b);
+                    if element.starts_with('[') { format!("[{}", element) } else { format!("[L{};", element) }
+                }
+                _ => String::from("java/lang/Object")
+            };
+        }
+
+        let mut visited = HashSet::new();
+        let mut current = String::from(a);
+        while visited.insert(current.clone()) {
+            current = match self.class_info(&current) {
+                Some(ClassInfo { super_class: Some(super_class), .. }) => super_class,
+                _ => break
+            };
+            if self.is_subtype(b, &current) {
+                return current;
+            }
+        }
+
+        String::from("java/lang/Object")
+    }
+}
+
+/// the type of an initialized reference, by class name or array descriptor
+pub fn object(name: &str) -> Type {
+    Type::Value(VerificationType::Object(Cow::Owned(String::from(name))))
+}
+
+/// the element type of an array type like `[I` or `[Ljava/lang/String;`
+pub fn component(array: &str) -> Option<Type> {
+    if !array.starts_with('[') {
+        return None;
+    }
+
+    let element = &array[1..];
+    match element.as_bytes().first()? {
+        b'L' if element.ends_with(';') => Some(object(&element[1..element.len() - 1])),
+        b'[' => Some(object(element)),
+        b'Z' | b'B' | b'C' | b'S' | b'I' => Some(Type::INTEGER),
+        b'F' => Some(Type::FLOAT),
+        b'J' => Some(Type::LONG),
+        b'D' => Some(Type::DOUBLE),
+        _ => None
+    }
+}
+
+/// the types of the local variables and the operand stack before an instruction.
+///
+/// unlike `FrameState`, `locals` has one entry per slot, so a `Long` or `Double` is followed by
+/// `Top`. the stack has one entry per value.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TypeState {
+    pub locals: Vec<Type>,
+    pub stack: Vec<Type>,
+}
+
+impl TypeState {
+    /// expands locals with one entry per variable into slots, padded with `Top` to `max_locals`.
+    /// `None` if they need more than `max_locals` slots.
+    pub fn from_variables<'a>(variables: &[VerificationType<'a>], stack: &[VerificationType<'a>], max_locals: usize) -> Option<TypeState> {
+        let mut locals = Vec::with_capacity(max_locals);
+        for variable in variables {
+            let size = variable.size();
+            locals.push(Type::Value(variable.clone().into_owned()));
+            if size == 2 {
+                locals.push(Type::TOP);
+            }
+        }
+        if locals.len() > max_locals {
+            return None;
+        }
+        locals.resize(max_locals, Type::TOP);
+
+        Some(TypeState { locals, stack: stack.iter().map(|value| Type::Value(value.clone().into_owned())).collect() })
+    }
+
+    /// the number of stack slots in use
+    pub fn stack_size(&self) -> usize {
+        self.stack.iter().map(|value| value.size()).sum()
+    }
+}
+
+fn write_types(f: &mut fmt::Formatter, types: &[Type]) -> fmt::Result {
+    let names: Vec<String> = types.iter().map(|value| value.to_string()).collect();
+    write!(f, "[{}]", names.join(", "))
+}
+
+/// unused locals at the end are left out
+impl fmt::Display for TypeState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let used = self.locals.iter().rposition(|local| *local != Type::TOP).map_or(0, |idx| idx + 1);
+        write!(f, "locals ")?;
+        write_types(f, &self.locals[..used])?;
+        write!(f, ", stack ")?;
+        write_types(f, &self.stack)
+    }
+}
+
+#[derive(Debug, Fail)]
+pub enum Problem {
+    #[fail(display = "{}", _0)]
+    InvalidConstant(#[cause] ConstantPoolError),
+    #[fail(display = "{}", _0)]
+    InvalidDescriptor(#[cause] ClassFileError),
+    #[fail(display = "{}", _0)]
+    Malformed(#[cause] ClassFileError),
+    #[fail(display = "unknown or truncated instruction")]
+    InvalidInstruction,
+    #[fail(display = "the operand stack is empty")]
+    StackUnderflow,
+    #[fail(display = "the operand stack needs more than max_stack {} slots", max_stack)]
+    StackOverflow { max_stack: usize },
+    #[fail(display = "expected {} on the operand stack, found {}", expected, found)]
+    WrongStackType { expected: String, found: Type },
+    #[fail(display = "expected {} in local variable {}, found {}", expected, index, found)]
+    WrongLocalType { index: usize, expected: String, found: Type },
+    #[fail(display = "local variable {} is beyond max_locals {}", index, max_locals)]
+    LocalOutOfRange { index: usize, max_locals: usize },
+    #[fail(display = "the operation would split a long or double on the operand stack")]
+    SplitValue,
+    #[fail(display = "invalid array type {}", atype)]
+    InvalidArrayType { atype: u8 },
+    #[fail(display = "the return instruction does not match the return type {}", return_type)]
+    WrongReturn { return_type: String },
+    #[fail(display = "the constructor returns before calling another constructor")]
+    UninitializedReturn,
+    #[fail(display = "jump to {}, which is not the start of an instruction", target)]
+    InvalidTarget { target: i64 },
+    #[fail(display = "no stack map frame at pc {}", target)]
+    MissingFrame { target: usize },
+    #[fail(display = "the stack map frame at pc {} is not at the start of an instruction", target)]
+    MisplacedFrame { target: usize },
+    #[fail(display = "the type state does not match the stack map frame at pc {}, which expects {}", target, expected)]
+    FrameMismatch { target: usize, expected: TypeState },
+    #[fail(display = "the operand stack does not match the one another path has at pc {}, which is {}", target, existing)]
+    IncompatibleStacks { target: usize, existing: TypeState },
+    #[fail(display = "execution falls off the end of the code")]
+    FallsOffEnd,
+    #[fail(display = "{}", reason)]
+    Unsupported { reason: &'static str },
+}
+
+/// a method that is not type-safe
+#[derive(Debug)]
+pub struct VerifyError {
+    /// name and descriptor of the method, e.g. `main([Ljava/lang/String;)V`
+    pub method: String,
+    pub pc: usize,
+    /// `None` if the problem is not caused by a single instruction
+    pub instruction: Option<Instruction>,
+    /// the type state the instruction found
+    pub state: Box<TypeState>,
+    pub problem: Box<Problem>,
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "verify error in {} at pc {}", self.method, self.pc)?;
+        if let Some(ref instruction) = self.instruction {
+            write!(f, " ({:?})", instruction)?;
+        }
+
+        write!(f, ": {}; {}", self.problem, self.state)
+    }
+}
+
+impl Fail for VerifyError {
+    fn cause(&self) -> Option<&dyn Fail> {
+        Some(&*self.problem)
+    }
+}
+
+/// verifies every method with code, stops at the first one that is not type-safe.
+/// class files older than version 50 have no stack map frames, their types are inferred.
+pub fn verify_class(class: &ClassFile, hierarchy: &dyn ClassHierarchy) -> Result<(), VerifyError> {
+    for method in &class.methods {
+        if class.version.0 < STACK_MAP_TABLE_SINCE {
+            infer_types(class, method, hierarchy)?;
+        } else {
+            type_check(class, method, hierarchy)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use java::class_file::{read_class_file, ConstantType};
+    use java::instructions::Instruction;
+    use java::samples;
+    use std::borrow::Cow;
+
+    /// replaces the only occurrence of `from` in a copy of `bytes`
+    fn patch(bytes: &[u8], from: &[u8], to: &[u8]) -> Vec<u8> {
+        let positions: Vec<usize> = (0..bytes.len() - from.len()).filter(|idx| bytes[*idx..].starts_with(from)).collect();
+        assert_eq!(1, positions.len());
+
+        let mut patched = bytes.to_vec();
+        patched[positions[0]..positions[0] + to.len()].copy_from_slice(to);
+        patched
+    }
+
+    /// the code of `testMe()I` in `SimpleMathWithLoop`
+    const LOOP_CODE: &[u8] = &[
+        0x06, 0x3b, 0x03, 0x3c, 0x1b, 0x10, 0x64, 0xa2, 0x00, 0x13, 0xb8, 0x00, 0x02, 0x3d,
+        0x1c, 0x1a, 0xb8, 0x00, 0x03, 0x3b, 0x84, 0x01, 0x01, 0xa7, 0xff, 0xed, 0x1a, 0xac,
+    ];
+
+    /// the same class with version 49, so its types are inferred
+    fn old(bytes: &[u8]) -> Vec<u8> {
+        let mut old = bytes.to_vec();
+        old[6..8].copy_from_slice(&[0, 49]);
+        old
+    }
+
+    fn verify(bytes: &[u8]) -> Result<(), VerifyError> {
+        let class = read_class_file(bytes).unwrap();
+        verify_class(&class, &Vec::new())
+    }
+
+    #[test]
+    fn compiled_classes_are_type_safe() {
+        let classes: Vec<ClassFile> = samples::ALL.iter().map(|(_, bytes)| read_class_file(bytes).unwrap()).collect();
+        for class in &classes {
+            if let Err(err) = verify_class(class, &classes) {
+                panic!("{}", err);
+            }
+        }
+    }
+
+    #[test]
+    fn it_reports_the_instruction_and_the_types_it_found() {
+        // `iadd` in `add(II)I` becomes `fadd`
+        let err = verify(&patch(samples::SIMPLE_MATH, &[0x1a, 0x1b, 0x60, 0xac], &[0x1a, 0x1b, 0x62, 0xac])).unwrap_err();
+        assert_eq!("add(II)I", err.method);
+        assert_eq!(2, err.pc);
+        assert_eq!(vec![Type::INTEGER, Type::INTEGER], err.state.stack);
+        assert_eq!(
+            "verify error in add(II)I at pc 2 (FAdd(())): expected float on the operand stack, found int; \
+             locals [int, int], stack [int, int]",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn it_checks_the_state_against_stack_map_frames() {
+        // `istore_1` before the loop becomes `pop`, so `i` is not an int at the loop header
+        let err = verify(&patch(samples::SIMPLE_MATH_WITH_LOOP, &[0x03, 0x3c, 0x1b, 0x10, 0x64], &[0x03, 0x57])).unwrap_err();
+        assert_eq!("testMe()I", err.method);
+        assert_eq!(4, err.pc);
+        match *err.problem {
+            Problem::FrameMismatch { target: 4, ref expected } => assert_eq!("locals [int, int], stack []", expected.to_string()),
+            ref other => panic!("unexpected {:?}", other)
+        }
+        assert_eq!("locals [int], stack []", err.state.to_string());
+
+        // the loop jumps back into its body, where there is no frame
+        let err = verify(&patch(samples::SIMPLE_MATH_WITH_LOOP, &[0x84, 0x01, 0x01, 0xa7, 0xff, 0xed, 0x1a, 0xac], &[0x84, 0x01, 0x01, 0xa7, 0xff, 0xf7])).unwrap_err();
+        assert_eq!(23, err.pc);
+        match *err.problem {
+            Problem::MissingFrame { target: 14 } => (),
+            ref other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn it_checks_assignability_through_the_loaded_classes() {
+        let classes: Vec<ClassFile> = samples::ALL.iter().map(|(_, bytes)| read_class_file(bytes).unwrap()).collect();
+        let member = classes.iter().find(|class| class.get_class_name().unwrap() == "Nesting$Member").unwrap();
+        let classes = Classes::new(member, &classes).unwrap();
+
+        assert!(classes.is_assignable(&object("Nesting$Member"), &object("java/lang/Object")));
+        assert!(!classes.is_assignable(&object("Nesting$Member"), &object("Nesting")));
+        assert!(classes.is_assignable(&object("Shapes$Circle"), &object("Shapes")));
+        assert!(classes.is_assignable(&Type::NULL, &object("[I")));
+        assert!(classes.is_assignable(&object("[Ljava/lang/String;"), &object("[Ljava/lang/Object;")));
+        assert!(classes.is_assignable(&object("[I"), &object("java/lang/Cloneable")));
+        assert!(!classes.is_assignable(&object("[I"), &object("[J")));
+        assert!(!classes.is_assignable(&Type::INTEGER, &Type::FLOAT));
+        // classes that are not loaded are assumed to fit
+        assert!(classes.is_assignable(&object("java/util/ArrayList"), &object("Nesting")));
+    }
+
+    #[test]
+    fn aastore_accepts_any_reference_as_the_value() {
+        let class = read_class_file(samples::SIMPLE_MATH_WITH_LOOP).unwrap();
+        let method = class.methods.iter().find(|method| method.name == "testMe").unwrap();
+        let loaded: Vec<ClassFile> = Vec::new();
+        let env = execute::Environment::new(&class, method, &loaded).unwrap().unwrap();
+
+        let store = |value: Type| {
+            let mut state = TypeState { locals: Vec::new(), stack: vec![object("[Ljava/lang/String;"), Type::INTEGER, value] };
+            execute::execute(&env, &mut state, 0, &Instruction::AAStore(())).map(|_| ())
+        };
+        assert!(store(Type::NULL).is_ok());
+        assert!(store(object("java/lang/Integer")).is_ok());
+        match store(Type::Value(VerificationType::Uninitialized(0))) {
+            Err(Problem::WrongStackType { ref expected, .. }) => assert_eq!("java/lang/Object", expected),
+            other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn old_classes_are_verified_by_type_inference() {
+        let bytes: Vec<Vec<u8>> = samples::ALL.iter().map(|(_, bytes)| old(bytes)).collect();
+        let classes: Vec<ClassFile> = bytes.iter().map(|bytes| read_class_file(bytes).unwrap()).collect();
+        for class in &classes {
+            if let Err(err) = verify_class(class, &classes) {
+                panic!("{}", err);
+            }
+        }
+    }
+
+    #[test]
+    fn ldc_pushes_the_type_of_dynamic_constants() {
+        let first = read_class_file(samples::SIMPLE_MATH).unwrap().constants.len() as u16 + 1;
+        // `add(II)I` returns the dynamic constant instead of the sum
+        let bytes = patch(samples::SIMPLE_MATH, &[0x1a, 0x1b, 0x60, 0xac], &[0x12, (first + 3) as u8, 0x00, 0xac]);
+        for &(descriptor, type_safe) in &[("I", true), ("F", false), ("J", false)] {
+            let mut class = read_class_file(&bytes).unwrap();
+            class.constants.push(ConstantType::Utf8 { value: Cow::Borrowed("answer"), bytes: Cow::Borrowed(b"answer") });
+            class.constants.push(ConstantType::Utf8 { value: Cow::Borrowed(descriptor), bytes: Cow::Borrowed(descriptor.as_bytes()) });
+            class.constants.push(ConstantType::NameAndType { name_index: first, descriptor_index: first + 1 });
+            class.constants.push(ConstantType::Dynamic { bootstrap_method_attr_index: 0, name_and_type_index: first + 2 });
+            assert_eq!(type_safe, verify_class(&class, &Vec::new()).is_ok(), "{}", descriptor);
+        }
+    }
+
+    #[test]
+    fn it_merges_types_where_paths_join() {
+        // `i++` becomes `i = 0.0f`, so `i` is an int or a float at the loop header
+        let err = verify(&old(&patch(samples::SIMPLE_MATH_WITH_LOOP, &[0x84, 0x01, 0x01, 0xa7], &[0x0b, 0x44, 0x00, 0xa7]))).unwrap_err();
+        assert_eq!(
+            "verify error in testMe()I at pc 4 (ILoad1(())): expected int in local variable 1, found top; \
+             locals [int], stack []",
+            err.to_string()
+        );
+
+        // `i++` becomes `iconst_0`, which is still on the stack when the loop jumps back
+        let err = verify(&old(&patch(samples::SIMPLE_MATH_WITH_LOOP, &[0x84, 0x01, 0x01, 0xa7], &[0x03, 0x00, 0x00, 0xa7]))).unwrap_err();
+        assert_eq!(23, err.pc);
+        match *err.problem {
+            Problem::IncompatibleStacks { target: 4, ref existing } => assert_eq!("locals [int, int], stack []", existing.to_string()),
+            ref other => panic!("unexpected {:?}", other)
+        }
+    }
+
+    #[test]
+    fn it_merges_references_to_their_common_superclass() {
+        let classes: Vec<ClassFile> = samples::ALL.iter().map(|(_, bytes)| read_class_file(bytes).unwrap()).collect();
+        let nesting = classes.iter().find(|class| class.get_class_name().unwrap() == "Nesting").unwrap();
+        let classes = Classes::new(nesting, &classes).unwrap();
+
+        assert_eq!(Some(object("Nesting")), classes.merge(&Type::NULL, &object("Nesting")));
+        assert_eq!(Some(object("java/lang/Object")), classes.merge(&object("Nesting"), &object("Nesting$Member")));
+        assert_eq!(Some(object("[Ljava/lang/Object;")), classes.merge(&object("[LNesting;"), &object("[LNesting$Member;")));
+        assert_eq!(Some(object("java/lang/Object")), classes.merge(&object("[I"), &object("[J")));
+        assert_eq!(None, classes.merge(&Type::INTEGER, &Type::FLOAT));
+        assert_eq!(None, classes.merge(&Type::Value(VerificationType::Uninitialized(0)), &object("Nesting")));
+    }
+
+    #[test]
+    fn it_follows_subroutines() {
+        // calls the subroutine at 20 with an int and then with a float in local 2. it only
+        // changes local 0, so local 2 keeps the type of each caller.
+        let subroutine = [
+            0x06, 0x3b, 0x03, 0x3d, 0xa8, 0x00, 0x10, 0x0b, 0x45, 0xa8, 0x00, 0x0b, 0x24, 0x8b,
+            0x1a, 0x60, 0xac, 0x00, 0x00, 0x00, 0x4c, 0x84, 0x00, 0x01, 0xa9, 0x01, 0x00, 0x00,
+        ];
+        assert!(verify(&old(&patch(samples::SIMPLE_MATH_WITH_LOOP, LOOP_CODE, &subroutine))).is_ok());
+
+        // the subroutine stores a float in local 0, which the caller then loads as an int
+        let mut writes_float = subroutine;
+        writes_float[21..24].copy_from_slice(&[0x0b, 0x43, 0x00]);
+        let err = verify(&old(&patch(samples::SIMPLE_MATH_WITH_LOOP, LOOP_CODE, &writes_float))).unwrap_err();
+        assert_eq!(14, err.pc);
+        match *err.problem {
+            Problem::WrongLocalType { index: 0, ref found, .. } => assert_eq!(Type::FLOAT, *found),
+            ref other => panic!("unexpected {:?}", other)
+        }
+
+        // the type checker does not allow subroutines
+        assert!(verify(&patch(samples::SIMPLE_MATH_WITH_LOOP, LOOP_CODE, &subroutine)).is_err());
+    }
+}
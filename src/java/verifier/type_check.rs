@@ -0,0 +1,111 @@
+//! the type checker for class files with stack map frames (JVMS 4.10.1).
+//!
+//! every branch target and exception handler has a frame, so the code is checked in a single
+//! pass: the type state after an instruction has to be assignable to the frame of every
+//! instruction that can follow it.
+
+use java::class_file::{ClassFile, Method};
+use java::verifier::execute::{execute, Environment};
+use java::verifier::{ClassHierarchy, Problem, TypeState, VerifyError};
+use std::collections::HashMap;
+
+/// checks a single method against its stack map frames. methods without code are always type-safe.
+pub fn type_check(class: &ClassFile, method: &Method, hierarchy: &dyn ClassHierarchy) -> Result<(), VerifyError> {
+    let env = match Environment::new(class, method, hierarchy)? {
+        Some(env) => env,
+        None => return Ok(())
+    };
+    let frames = frames(&env)?;
+
+    let mut state = env.initial_state()?;
+    // false after an instruction that does not continue with the next one
+    let mut reachable = true;
+    for &(pc, ref instruction) in &env.instructions {
+        let fail = |state: &TypeState, problem: Problem| env.error(pc, Some(instruction.clone()), state, problem);
+
+        match frames.get(&pc) {
+            Some(frame) => {
+                if reachable {
+                    matches_frame(&env, &state, pc, frame).map_err(|problem| fail(&state, problem))?;
+                }
+                state = frame.clone();
+            }
+            None if !reachable => return Err(fail(&state, Problem::MissingFrame { target: pc })),
+            None => ()
+        }
+
+        let before = state.clone();
+        check_handlers(&env, &frames, pc, &before).map_err(|problem| fail(&before, problem))?;
+        let step = execute(&env, &mut state, pc, instruction).map_err(|problem| fail(&before, problem))?;
+        // a store inside a try block changes the locals the handler sees
+        if state.locals != before.locals {
+            check_handlers(&env, &frames, pc, &state).map_err(|problem| fail(&state, problem))?;
+        }
+
+        for offset in step.targets {
+            let target = env.target(pc, offset).map_err(|problem| fail(&before, problem))?;
+            jump(&env, &frames, &state, target).map_err(|problem| fail(&state, problem))?;
+        }
+        reachable = step.falls_through;
+    }
+
+    match env.instructions.last() {
+        Some(&(pc, ref instruction)) if reachable => Err(env.error(pc, Some(instruction.clone()), &state, Problem::FallsOffEnd)),
+        _ => Ok(())
+    }
+}
+
+/// the stack map frames by pc, with their locals expanded to slots
+fn frames(env: &Environment) -> Result<HashMap<usize, TypeState>, VerifyError> {
+    let empty = TypeState { locals: Vec::new(), stack: Vec::new() };
+    let states = env.method.stack_map_states(env.classes.name())
+        .map_err(|err| env.error(0, None, &empty, Problem::InvalidDescriptor(err)))?;
+
+    let mut frames = HashMap::new();
+    for frame in states {
+        let pc = usize::from(frame.pc);
+        if !env.is_instruction(pc) {
+            return Err(env.error(pc, None, &empty, Problem::MisplacedFrame { target: pc }));
+        }
+
+        let state = TypeState::from_variables(&frame.locals, &frame.stack, env.max_locals)
+            .ok_or_else(|| env.error(pc, None, &empty, Problem::LocalOutOfRange { index: env.max_locals, max_locals: env.max_locals }))?;
+        if state.stack_size() > env.max_stack {
+            return Err(env.error(pc, None, &state, Problem::StackOverflow { max_stack: env.max_stack }));
+        }
+        frames.insert(pc, state);
+    }
+
+    Ok(frames)
+}
+
+/// every local and stack entry of `state` is assignable to the one in `frame`
+fn matches_frame(env: &Environment, state: &TypeState, target: usize, frame: &TypeState) -> Result<(), Problem> {
+    let assignable = |found: &[_], expected: &[_]| {
+        found.len() == expected.len() && found.iter().zip(expected).all(|(found, expected)| env.classes.is_assignable(found, expected))
+    };
+
+    if assignable(&state.locals, &frame.locals) && assignable(&state.stack, &frame.stack) {
+        Ok(())
+    } else {
+        Err(Problem::FrameMismatch { target, expected: frame.clone() })
+    }
+}
+
+fn jump(env: &Environment, frames: &HashMap<usize, TypeState>, state: &TypeState, target: usize) -> Result<(), Problem> {
+    match frames.get(&target) {
+        Some(frame) => matches_frame(env, state, target, frame),
+        None => Err(Problem::MissingFrame { target })
+    }
+}
+
+/// the handlers that cover `pc` start with the locals of `state` and the exception on the stack
+fn check_handlers(env: &Environment, frames: &HashMap<usize, TypeState>, pc: usize, state: &TypeState) -> Result<(), Problem> {
+    for handler in env.code.handlers_at(pc as u16) {
+        let exception = env.exception_type(handler.catch_type.as_deref());
+        let handler_state = TypeState { locals: state.locals.clone(), stack: vec![exception] };
+        jump(env, frames, &handler_state, usize::from(handler.handler_pc))?;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,229 @@
+//! the type inference verifier for class files without stack map frames (JVMS 4.10.2).
+//!
+//! the type state before every instruction is found by data-flow analysis: an instruction is
+//! executed again whenever the state that reaches it changes. where paths join, the states are
+//! merged, a local that has different types on two paths cannot be used after the join.
+//!
+//! `jsr` pushes a return address and jumps to the subroutine. `ret` continues after every `jsr`
+//! to the subroutine, with the locals the subroutine wrote and the caller's values of all others.
+
+use java::class_file::{ClassFile, Method};
+use java::instructions::{Instruction, Wide};
+use java::verifier::execute::{execute, local, push, Environment};
+use java::verifier::{ClassHierarchy, Problem, Type, TypeState, VerifyError};
+use std::collections::{BTreeSet, HashMap};
+
+/// the type state before an instruction
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Frame {
+    state: TypeState,
+    /// the locals written since the subroutine the instruction is in was called
+    written: Vec<bool>,
+}
+
+/// infers the types of a single method. methods without code are always type-safe.
+pub fn infer_types(class: &ClassFile, method: &Method, hierarchy: &dyn ClassHierarchy) -> Result<(), VerifyError> {
+    let env = match Environment::new(class, method, hierarchy)? {
+        Some(env) => env,
+        None => return Ok(())
+    };
+
+    let mut inference = Inference {
+        env: &env,
+        frames: vec![None; env.instructions.len()],
+        changed: BTreeSet::new(),
+        returns: HashMap::new(),
+    };
+    let state = env.initial_state()?;
+    inference.frames[0] = Some(Frame { state, written: vec![false; env.max_locals] });
+    inference.changed.insert(0);
+
+    while let Some(idx) = inference.changed.iter().next().cloned() {
+        inference.changed.remove(&idx);
+        inference.step(idx)?;
+    }
+
+    Ok(())
+}
+
+struct Inference<'e, 'c: 'e, 'a: 'c> {
+    env: &'e Environment<'c, 'a>,
+    /// by instruction index, `None` until the instruction is reached
+    frames: Vec<Option<Frame>>,
+    /// the instructions whose frame changed since they were last executed
+    changed: BTreeSet<usize>,
+    /// the merged frames of every `ret`, by the pc of the subroutine
+    returns: HashMap<usize, Frame>,
+}
+
+impl<'e, 'c, 'a> Inference<'e, 'c, 'a> {
+    /// executes the instruction at `idx` and merges the result into its successors
+    fn step(&mut self, idx: usize) -> Result<(), VerifyError> {
+        let env = self.env;
+        let (pc, ref instruction) = env.instructions[idx];
+        let before = self.frames[idx].clone().expect("only reached instructions are executed");
+        let fail = |state: &TypeState, problem: Problem| env.error(pc, Some(instruction.clone()), state, problem);
+
+        self.handlers(pc, &before).map_err(|problem| fail(&before.state, problem))?;
+
+        if let Some(offset) = subroutine_call(instruction) {
+            let start = env.target(pc, offset).map_err(|problem| fail(&before.state, problem))?;
+            let mut entry = Frame { state: before.state.clone(), written: vec![false; env.max_locals] };
+            push(env, &mut entry.state, Type::ReturnAddress(start as u16)).map_err(|problem| fail(&before.state, problem))?;
+            self.merge(start, entry).map_err(|problem| fail(&before.state, problem))?;
+
+            if let Some(returned) = self.returns.get(&start).cloned() {
+                self.return_to(idx, &before, &returned)?;
+            }
+            return Ok(());
+        }
+
+        if let Some(index) = subroutine_return(instruction) {
+            let start = match local(env, &before.state, index, 1).map_err(|problem| fail(&before.state, problem))? {
+                Type::ReturnAddress(start) => usize::from(start),
+                found => return Err(fail(&before.state, Problem::WrongLocalType { index, expected: String::from("returnAddress"), found }))
+            };
+            let returned = match self.returns.get(&start) {
+                Some(existing) => merge(env, existing, &before, start).map_err(|problem| fail(&before.state, problem))?,
+                None => before.clone()
+            };
+            if self.returns.get(&start) == Some(&returned) {
+                return Ok(());
+            }
+            self.returns.insert(start, returned.clone());
+
+            // every `jsr` that was already reached continues with the new state
+            for (caller, &(caller_pc, ref call)) in env.instructions.iter().enumerate() {
+                let calls_start = subroutine_call(call).and_then(|offset| env.target(caller_pc, offset).ok()) == Some(start);
+                if let (true, Some(frame)) = (calls_start, self.frames[caller].clone()) {
+                    self.return_to(caller, &frame, &returned)?;
+                }
+            }
+            return Ok(());
+        }
+
+        let mut after = before.clone();
+        let step = execute(env, &mut after.state, pc, instruction).map_err(|problem| fail(&before.state, problem))?;
+        for (index, written) in after.written.iter_mut().enumerate() {
+            *written |= after.state.locals[index] != before.state.locals[index];
+        }
+        if let Some(index) = stored_local(instruction) {
+            let size = after.state.locals[index].size();
+            for written in &mut after.written[index..index + size] {
+                *written = true;
+            }
+        }
+        // a store inside a try block changes the locals the handler sees
+        if after.state.locals != before.state.locals {
+            self.handlers(pc, &after).map_err(|problem| fail(&after.state, problem))?;
+        }
+
+        for offset in step.targets {
+            let target = env.target(pc, offset).map_err(|problem| fail(&before.state, problem))?;
+            self.merge(target, after.clone()).map_err(|problem| fail(&after.state, problem))?;
+        }
+        if step.falls_through {
+            let next = env.instructions.get(idx + 1).map(|&(next, _)| next).ok_or_else(|| fail(&after.state, Problem::FallsOffEnd))?;
+            self.merge(next, after.clone()).map_err(|problem| fail(&after.state, problem))?;
+        }
+
+        Ok(())
+    }
+
+    /// continues after the `jsr` at `caller` once the subroutine returned with `returned`
+    fn return_to(&mut self, caller: usize, call: &Frame, returned: &Frame) -> Result<(), VerifyError> {
+        let env = self.env;
+        let (pc, ref instruction) = env.instructions[caller];
+        let fail = |problem: Problem| env.error(pc, Some(instruction.clone()), &returned.state, problem);
+        let next = env.instructions.get(caller + 1).map(|&(next, _)| next).ok_or_else(|| fail(Problem::FallsOffEnd))?;
+
+        let locals = call.state.locals.iter().zip(&returned.state.locals).zip(&returned.written)
+            .map(|((caller, subroutine), written)| if *written { subroutine.clone() } else { caller.clone() })
+            .collect();
+        // a nested subroutine also writes the locals for the one that called it
+        let written = call.written.iter().zip(&returned.written).map(|(caller, subroutine)| *caller || *subroutine).collect();
+        let frame = Frame { state: TypeState { locals, stack: returned.state.stack.clone() }, written };
+
+        self.merge(next, frame).map_err(fail)
+    }
+
+    /// the handlers that cover `pc` start with the locals of `frame` and the exception on the stack
+    fn handlers(&mut self, pc: usize, frame: &Frame) -> Result<(), Problem> {
+        for handler in self.env.code.handlers_at(pc as u16) {
+            let exception = self.env.exception_type(handler.catch_type.as_deref());
+            let state = TypeState { locals: frame.state.locals.clone(), stack: vec![exception] };
+            self.merge(usize::from(handler.handler_pc), Frame { state, written: frame.written.clone() })?;
+        }
+
+        Ok(())
+    }
+
+    /// merges `frame` into the frame of the instruction at `target`
+    fn merge(&mut self, target: usize, frame: Frame) -> Result<(), Problem> {
+        let idx = self.env.instructions.binary_search_by_key(&target, |&(start, _)| start)
+            .map_err(|_| Problem::InvalidTarget { target: target as i64 })?;
+
+        let merged = match self.frames[idx] {
+            Some(ref existing) => merge(self.env, existing, &frame, target)?,
+            None => frame
+        };
+        if self.frames[idx].as_ref() != Some(&merged) {
+            self.frames[idx] = Some(merged);
+            self.changed.insert(idx);
+        }
+
+        Ok(())
+    }
+}
+
+/// locals that have different types become unusable, the stacks have to match
+fn merge(env: &Environment, existing: &Frame, frame: &Frame, target: usize) -> Result<Frame, Problem> {
+    let incompatible = || Problem::IncompatibleStacks { target, existing: existing.state.clone() };
+    if existing.state.stack.len() != frame.state.stack.len() {
+        return Err(incompatible());
+    }
+
+    let mut stack = Vec::with_capacity(frame.state.stack.len());
+    for (a, b) in existing.state.stack.iter().zip(&frame.state.stack) {
+        stack.push(env.classes.merge(a, b).ok_or_else(incompatible)?);
+    }
+    let locals = existing.state.locals.iter().zip(&frame.state.locals)
+        .map(|(a, b)| env.classes.merge(a, b).unwrap_or(Type::TOP))
+        .collect();
+    let written = existing.written.iter().zip(&frame.written).map(|(a, b)| *a || *b).collect();
+
+    Ok(Frame { state: TypeState { locals, stack }, written })
+}
+
+/// the offset of the subroutine `jsr` calls
+fn subroutine_call(instruction: &Instruction) -> Option<i64> {
+    match *instruction {
+        Instruction::JSR(offset) => Some(i64::from(offset)),
+        Instruction::JSRW(offset) => Some(i64::from(offset)),
+        _ => None
+    }
+}
+
+/// the local `ret` reads its return address from
+fn subroutine_return(instruction: &Instruction) -> Option<usize> {
+    match *instruction {
+        Instruction::Ret(index) => Some(usize::from(index)),
+        Instruction::Wide(Wide::Ret(index)) => Some(usize::from(index)),
+        _ => None
+    }
+}
+
+/// the local a store instruction writes
+fn stored_local(instruction: &Instruction) -> Option<usize> {
+    match *instruction {
+        Instruction::IStore(index) | Instruction::LStore(index) | Instruction::FStore(index) |
+        Instruction::DStore(index) | Instruction::AStore(index) => Some(usize::from(index)),
+        Instruction::IStore0(()) | Instruction::LStore0(()) | Instruction::FStore0(()) | Instruction::DStore0(()) | Instruction::AStore0(()) => Some(0),
+        Instruction::IStore1(()) | Instruction::LStore1(()) | Instruction::FStore1(()) | Instruction::DStore1(()) | Instruction::AStore1(()) => Some(1),
+        Instruction::IStore2(()) | Instruction::LStore2(()) | Instruction::FStore2(()) | Instruction::DStore2(()) | Instruction::AStore2(()) => Some(2),
+        Instruction::IStore3(()) | Instruction::LStore3(()) | Instruction::FStore3(()) | Instruction::DStore3(()) | Instruction::AStore3(()) => Some(3),
+        Instruction::Wide(Wide::IStore(index)) | Instruction::Wide(Wide::LStore(index)) | Instruction::Wide(Wide::FStore(index)) |
+        Instruction::Wide(Wide::DStore(index)) | Instruction::Wide(Wide::AStore(index)) => Some(usize::from(index)),
+        _ => None
+    }
+}
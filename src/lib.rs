@@ -0,0 +1,5 @@
+extern crate nom;
+#[macro_use]
+extern crate failure;
+
+pub mod java;
@@ -1,43 +1,50 @@
-mod java;
+extern crate rjvm;
 
-#[macro_use]
-extern crate nom;
-#[macro_use]
-extern crate failure;
-
-use java::class_file::{read_class_file, ClassFile};
-use std::fs::File;
+use rjvm::java;
+use java::class_file::{read_class_file, ClassFile, ClassQuery};
+use std::fs;
 use std::env;
-use std::io::Read;
-use java::class_file::Attribute;
-use java::class_file::CodeBlock;
 use java::runtime::*;
 
 fn main() {
     let args = env::args().collect::<Vec<String>>();
     let filename = args.get(1);
-    let mut buffer = Vec::new();
     let content = if let Some(path) = filename {
-        let mut f = File::open(path).expect("cannot open file");
-        f.read_to_end(&mut buffer).expect("cannot read file");
-        buffer.as_slice()
+        fs::read(path).expect("cannot read file")
     } else {
-        include_bytes!("../sample/Tiny.class")
+        include_bytes!("../sample/Tiny.class").to_vec()
     };
 
 
-    let report: ClassFile = read_class_file(content).unwrap().1;
+    let report: ClassFile<'static> = match read_class_file(&content) {
+        Ok(class) => class.into_owned(),
+        Err(err) => {
+            eprintln!("{}", err);
+            return;
+        }
+    };
     //println!("{:#?}", report);
 
     println!("{:?}", report.get_class_name());
 
+    if let Ok(Some(module)) = report.module_descriptor() {
+        println!("{}", module);
+        return;
+    }
+
 /*    report.methods.iter().for_each(|method| {
         println!("{:?} {:?}", method.get_access(), method.name);
         println!("{:?}", method.get_signature());
         println!("{}", java::class_file::dissasm::disassemble(method))
     })*/
 
-    let mut rt = Runtime::create(report);
+    let mut rt = match Runtime::create(report) {
+        Ok(rt) => rt,
+        Err(err) => {
+            eprintln!("{}", err);
+            return;
+        }
+    };
 
     rt.run();
 }